@@ -0,0 +1,117 @@
+import { BaseDirectory } from '@tauri-apps/api/path'
+import { invoke } from '@tauri-apps/api/core'
+import { emit } from '@tauri-apps/api/event'
+import { readFile } from '@tauri-apps/plugin-fs'
+import type { Song } from '~/types/types'
+import { AUDIO_EXTENSIONS } from './player'
+
+const ANALYSIS_SAMPLE_RATE = 22050
+const FRAME_SIZE = 1024
+const HOP_SIZE = 512
+const MIN_BPM = 60
+const MAX_BPM = 200
+
+export default defineNuxtPlugin((nuxtApp) => {
+  async function readSongFile(id: string): Promise<Uint8Array> {
+    for (const extension of AUDIO_EXTENSIONS) {
+      try {
+        return await readFile(`Vleer/Songs/${id}.${extension}`, { baseDir: BaseDirectory.Audio })
+      } catch {
+        continue
+      }
+    }
+    // Songs indexed in place live outside the music folder, only the
+    // backend can read them.
+    return new Uint8Array(await invoke<ArrayBuffer>('read_song_file', { songId: id }))
+  }
+
+  async function decodeSong(id: string): Promise<AudioBuffer> {
+    const fileContent = await readSongFile(id)
+    const ctx = new OfflineAudioContext(1, 1, ANALYSIS_SAMPLE_RATE)
+    return await ctx.decodeAudioData(fileContent.buffer as ArrayBuffer)
+  }
+
+  function mixdown(buffer: AudioBuffer): Float32Array {
+    const samples = new Float32Array(buffer.length)
+    for (let channel = 0; channel < buffer.numberOfChannels; channel++) {
+      const data = buffer.getChannelData(channel)
+      for (let i = 0; i < data.length; i++) {
+        samples[i] += data[i] / buffer.numberOfChannels
+      }
+    }
+    return samples
+  }
+
+  // Onset strength is the positive change in frame energy; the tempo is the
+  // lag with the strongest autocorrelation of that envelope.
+  function estimateBpm(samples: Float32Array, sampleRate: number): number {
+    const energies: number[] = []
+    for (let start = 0; start + FRAME_SIZE <= samples.length; start += HOP_SIZE) {
+      let energy = 0
+      for (let i = start; i < start + FRAME_SIZE; i++) {
+        energy += samples[i] * samples[i]
+      }
+      energies.push(Math.sqrt(energy / FRAME_SIZE))
+    }
+
+    const onsets = energies.map((energy, i) => i === 0 ? 0 : Math.max(0, energy - energies[i - 1]))
+    const framesPerSecond = sampleRate / HOP_SIZE
+    const minLag = Math.floor((60 / MAX_BPM) * framesPerSecond)
+    const maxLag = Math.ceil((60 / MIN_BPM) * framesPerSecond)
+
+    let bestLag = 0
+    let bestScore = 0
+    for (let lag = minLag; lag <= maxLag; lag++) {
+      let score = 0
+      for (let i = lag; i < onsets.length; i++) {
+        score += onsets[i] * onsets[i - lag]
+      }
+      if (score > bestScore) {
+        bestScore = score
+        bestLag = lag
+      }
+    }
+
+    if (bestLag === 0) return 0
+    let bpm = (60 * framesPerSecond) / bestLag
+    while (bpm < 70) bpm *= 2
+    while (bpm > 180) bpm /= 2
+    return Math.round(bpm * 10) / 10
+  }
+
+  return {
+    provide: {
+      analysis: {
+        decodeSong,
+
+        async analyzeBpm(song: Song): Promise<number> {
+          if (song.bpm) return song.bpm
+
+          const buffer = await decodeSong(song.id)
+          const bpm = estimateBpm(mixdown(buffer), buffer.sampleRate)
+          await invoke('set_song_bpm', { songId: song.id, bpm })
+          return bpm
+        },
+
+        async analyzeLibraryBpm() {
+          const songs: Song[] = await invoke('get_songs_without_bpm')
+          let failed = 0
+          for (const [index, song] of songs.entries()) {
+            try {
+              await this.analyzeBpm(song)
+            } catch (error) {
+              failed++
+              console.error('Failed to analyze BPM for', song.id, error)
+            }
+            await emit('bpm-progress', { done: index + 1, total: songs.length, failed })
+          }
+          return { analyzed: songs.length - failed, failed }
+        },
+
+        async getSongsByBpmRange(min: number, max: number): Promise<Song[]> {
+          return await invoke('get_songs_by_bpm_range', { min, max })
+        }
+      }
+    }
+  }
+})
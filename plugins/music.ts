@@ -1,5 +1,5 @@
 import { invoke } from '@tauri-apps/api/core'
-import type { History, Playlist, Song, Album } from '~/types/types'
+import type { AlbumSummary, ArtistCount, BrokenPlaylist, DownloadJobStatus, FileMetadata, GenreCount, History, LibraryRebuildReport, ListeningStats, M3uExportReport, M3uImportReport, Playlist, RestoredQueue, ScanSummary, Song, SongPage, Album, SongFilter, SongSort, StatsPeriod } from '~/types/types'
 
 export default defineNuxtPlugin((nuxtApp) => {
   return {
@@ -17,11 +17,32 @@ export default defineNuxtPlugin((nuxtApp) => {
         async addSongToPlaylist(playlistId: string, song: Song) {
           return await invoke('add_song_to_playlist', { playlistId, song })
         },
+        async addSongToPlaylists(songId: string, playlistIds: string[]): Promise<Record<string, number>> {
+          return await invoke('add_song_to_playlists', { songId, playlistIds })
+        },
+        async addSongsToPlaylist(playlistId: string, songIds: string[]): Promise<number> {
+          return await invoke('add_songs_to_playlist', { playlistId, songIds })
+        },
         async clearHistory() {
           return await invoke('clear_history')
         },
-        async getHistory(): Promise<History[]> {
-          return await invoke('get_history')
+        async exportSong(songId: string, destDir: string): Promise<string> {
+          return await invoke('export_song', { songId, destDir })
+        },
+        async getGenres(): Promise<GenreCount[]> {
+          return await invoke('get_genres')
+        },
+        async getHistory(limit?: number): Promise<History[]> {
+          return await invoke('get_history', { limit })
+        },
+        async getFullHistory(limit?: number): Promise<History[]> {
+          return await invoke('get_full_history', { limit })
+        },
+        async getHistoryRange(fromMs: number, toMs: number): Promise<History[]> {
+          return await invoke('get_history_range', { fromMs, toMs })
+        },
+        async getListeningStats(period: StatsPeriod): Promise<ListeningStats> {
+          return await invoke('get_listening_stats', { period })
         },
         async getPlaylist(id: string): Promise<Playlist | null> {
           return await invoke('get_playlist', { id })
@@ -35,6 +56,72 @@ export default defineNuxtPlugin((nuxtApp) => {
         async getSongs(): Promise<Song[]> {
           return await invoke('get_songs')
         },
+        async getSongsPaged(limit?: number, offset?: number, sortBy?: SongSort): Promise<SongPage> {
+          return await invoke('get_songs_paged', { limit, offset, sortBy })
+        },
+        async toggleLike(songId: string): Promise<boolean> {
+          return await invoke('toggle_like', { songId })
+        },
+        async getLikedSongs(): Promise<Song[]> {
+          return await invoke('get_liked_songs')
+        },
+        async incrementPlayCount(songId: string) {
+          return await invoke('increment_play_count', { songId })
+        },
+        async getMostPlayed(limit?: number): Promise<Song[]> {
+          return await invoke('get_most_played', { limit })
+        },
+        async getRecentlyAdded(limit?: number): Promise<Song[]> {
+          return await invoke('get_recently_added', { limit })
+        },
+        async getRecentlyPlayed(limit?: number): Promise<Song[]> {
+          return await invoke('get_recently_played', { limit })
+        },
+        async searchSongs(query: string, limit?: number): Promise<Song[]> {
+          return await invoke('search_songs', { query, limit })
+        },
+        async getSongsByGenre(genre: string): Promise<Song[]> {
+          return await invoke('get_songs_by_genre', { genre })
+        },
+        async updateSongDuration(songId: string, duration: number) {
+          return await invoke('update_song_duration', { songId, duration })
+        },
+        async readMetadata(path: string): Promise<FileMetadata> {
+          return await invoke('read_metadata', { path })
+        },
+        async scanLibrary(folder: string, copy = false): Promise<ScanSummary> {
+          return await invoke('scan_library', { folder, copy })
+        },
+        async cancelScan() {
+          return await invoke('cancel_scan')
+        },
+        async cancelDownload(songId: string) {
+          return await invoke('cancel_download', { songId })
+        },
+        async queueDownload(id: string, quality: string, url: string) {
+          return await invoke('queue_download', { id, quality, url })
+        },
+        async getDownloadQueue(): Promise<DownloadJobStatus[]> {
+          return await invoke('get_download_queue')
+        },
+        async clearDownloadQueue(): Promise<number> {
+          return await invoke('clear_download_queue')
+        },
+        async generateWaveform(song: Song, buckets = 1000): Promise<number[]> {
+          return await invoke('generate_waveform', { song, buckets })
+        },
+        async lastfmAuth(username: string, password: string): Promise<string> {
+          return await invoke('lastfm_auth', { username, password })
+        },
+        async getRecentLogs(lines?: number): Promise<string[]> {
+          return await invoke('get_recent_logs', { lines })
+        },
+        async clearLogs() {
+          return await invoke('clear_logs')
+        },
+        async openLogsFolder() {
+          return await invoke('open_logs_folder')
+        },
         async removeSong(songId: string) {
           return await invoke('remove_song', { songId })
         },
@@ -47,14 +134,56 @@ export default defineNuxtPlugin((nuxtApp) => {
         async removePlaylist(playlistId: string) {
           return await invoke('remove_playlist', { playlistId })
         },
+        async renamePlaylist(playlistId: string, name: string) {
+          return await invoke('rename_playlist', { playlistId, name })
+        },
+        async exportPlaylistM3u(playlistId: string, path: string): Promise<M3uExportReport> {
+          return await invoke('export_playlist_m3u', { playlistId, path })
+        },
+        async importPlaylistM3u(path: string): Promise<M3uImportReport> {
+          return await invoke('import_playlist_m3u', { path })
+        },
+        async setPlaylistCover(playlistId: string, image: string | number[]) {
+          return await invoke('set_playlist_cover', { playlistId, image })
+        },
         async removeAlbum(albumId: string) {
           return await invoke('remove_album', { albumId })
         },
         async addAlbum(album: Album) {
           return await invoke('add_album', { album })
         },
+        async getAlbumArt(songId: string): Promise<string | null> {
+          return await invoke('get_album_art', { songId })
+        },
+        async setAlbumArt(albumId: string, bytes: number[]) {
+          return await invoke('set_album_art', { albumId, bytes })
+        },
         async getAlbum(id: string): Promise<Album | null> {
           return await invoke('get_album', { id })
+        },
+        async getArtists(): Promise<ArtistCount[]> {
+          return await invoke('get_artists')
+        },
+        async getAlbumsByArtist(artist: string): Promise<AlbumSummary[]> {
+          return await invoke('get_albums_by_artist', { artist })
+        },
+        async getSongsByAlbum(albumId: string): Promise<Song[]> {
+          return await invoke('get_songs_by_album', { albumId })
+        },
+        async queueFiltered(filter: SongFilter, sortBy?: SongSort, limit?: number): Promise<number> {
+          return await invoke('queue_filtered', { filter, sortBy, limit })
+        },
+        async restoreQueue(): Promise<RestoredQueue> {
+          return await invoke('restore_queue')
+        },
+        async rebuildLibraryFromFiles(): Promise<LibraryRebuildReport> {
+          return await invoke('rebuild_library_from_files')
+        },
+        async validatePlaylists(): Promise<BrokenPlaylist[]> {
+          return await invoke('validate_playlists')
+        },
+        async prunePlaylistBrokenEntries(playlistId: string): Promise<number> {
+          return await invoke('prune_playlist_broken_entries', { playlistId })
         }
       }
     }
@@ -4,121 +4,765 @@ import { readFile } from '@tauri-apps/plugin-fs'
 import { defineNuxtPlugin } from 'nuxt/app'
 import { Howl, Howler } from 'howler'
 import { ref } from 'vue'
-import type { EQSettings, Song } from '~/types/types'
-import { listen } from '@tauri-apps/api/event'
+import type { AbLoop, ChannelLevels, EQSettings, Lyrics, NormalizationMode, OutputDevice, OutputLevels, PlaybackProgress, QueueState, RepeatMode, ReplayGain, SilentRange, Song } from '~/types/types'
+import { emit, listen } from '@tauri-apps/api/event'
 import { useNuxtApp } from '#app'
+import { estimateDecodedBytes } from '~/utils/memory'
+import { createQueue } from '~/utils/queue'
+import { clampPosition } from '~/utils/seek'
+
+const SMART_RESUME_THRESHOLD_MS = 10 * 60 * 1000
+export const AUDIO_EXTENSIONS = ['flac', 'mp3', 'ogg', 'opus', 'wav', 'm4a']
+const AUDIO_MIME_TYPES: Record<string, string> = {
+  flac: 'audio/flac',
+  mp3: 'audio/mp3',
+  ogg: 'audio/ogg',
+  opus: 'audio/ogg; codecs=opus',
+  wav: 'audio/wav',
+  m4a: 'audio/mp4'
+}
+const EQ_FREQUENCIES = [32, 64, 125, 250, 500, 1000, 2000, 4000, 8000, 16000]
+const PROGRESS_INTERVAL_MS = 250
+const QUEUE_SAVE_INTERVAL_MS = 5 * 1000
+const SPECTRUM_BANDS = 64
+const SPECTRUM_INTERVAL_MS = 1000 / 30
+const SPECTRUM_MIN_HZ = 20
+const SPECTRUM_MAX_HZ = 20000
+const PRELOAD_AHEAD_MS = 15 * 1000
+const MIN_SPEED = 0.5
+const MAX_SPEED = 2.0
+const SLEEP_FADE_MS = 10 * 1000
+const PREV_RESTART_THRESHOLD_MS = 3000
+
+interface PreparedSound {
+  song: Song
+  sound: Howl
+  url: string
+  eq: EQSettings
+  normalization: NormalizationMode
+  speed: number
+  balance: number
+  mono: boolean
+  replayGain: ReplayGain | null
+  crossfadeMs: number
+  gapless: boolean
+  memoryBudget: number
+  fileBytes: number
+  decodedBytes: number
+}
+
+// Per-channel gains between the EQ and the analyser. The input is forced to
+// stereo so mono files still reach both channels.
+interface BalanceStage {
+  input: GainNode
+  downmix: GainNode
+  splitter: ChannelSplitterNode
+  left: GainNode
+  right: GainNode
+}
+
+function createBalanceStage(ctx: AudioContext, destination: AudioNode): BalanceStage {
+  const input = ctx.createGain()
+  input.channelCount = 2
+  input.channelCountMode = 'explicit'
+  input.channelInterpretation = 'speakers'
+
+  // Mixing down to one channel with speaker interpretation averages left
+  // and right.
+  const downmix = ctx.createGain()
+  downmix.channelCount = 1
+  downmix.channelCountMode = 'explicit'
+  downmix.channelInterpretation = 'speakers'
+
+  const splitter = ctx.createChannelSplitter(2)
+  const merger = ctx.createChannelMerger(2)
+  const left = ctx.createGain()
+  const right = ctx.createGain()
+  left.connect(merger, 0, 0)
+  right.connect(merger, 0, 1)
+  merger.connect(destination)
+
+  const stage = { input, downmix, splitter, left, right }
+  routeBalanceStage(stage, false)
+  return stage
+}
+
+// In mono both channel gains are fed the downmix, so the balance still
+// applies on top of it.
+function routeBalanceStage(stage: BalanceStage, mono: boolean) {
+  stage.input.disconnect()
+  stage.downmix.disconnect()
+  stage.splitter.disconnect()
+
+  if (mono) {
+    stage.input.connect(stage.downmix)
+    stage.downmix.connect(stage.left)
+    stage.downmix.connect(stage.right)
+  } else {
+    stage.input.connect(stage.splitter)
+    stage.splitter.connect(stage.left, 0)
+    stage.splitter.connect(stage.right, 1)
+  }
+}
 
 export default defineNuxtPlugin((nuxtApp) => {
   let sound: Howl | null = null
   let analyzer: AnalyserNode | null = null
   let equalizer: BiquadFilterNode[] | null = null
+  let eqSource: AudioNode | null = null
+  let inputGain: GainNode | null = null
+  let balanceStage: BalanceStage | null = null
+  let balance = 0
+  let mono = false
+  // The sound that is fading out to be paused.
+  let pauseFading: Howl | null = null
+  let normalization: NormalizationMode = 'off'
+  let preampDb = 0
+  let speed = 1
+  let sleepTimer: ReturnType<typeof setTimeout> | null = null
+  let sleepFading = false
+  let sleepAtEndOfTrack = false
+  let replayGain: ReplayGain | null = null
+  let channelAnalyzers: AnalyserNode[] | null = null
+  let levelMeterInterval: ReturnType<typeof setInterval> | null = null
+  let spectrumInterval: ReturnType<typeof setInterval> | null = null
+  let progressInterval: ReturnType<typeof setInterval> | null = null
+  let pausedAt: number | null = null
+  let objectUrl: string | null = null
+  let memoryBudget = 0
+  let fileBytes = 0
+  let decodedBytes = 0
+  let eqListening = false
+  let advancing = false
+  let crossfadeMs = 0
+  let crossfadeStarted = false
+  // The Howl whose play has already been counted, so pausing and resuming or
+  // reloading the same song doesn't count it again.
+  let countedSound: Howl | null = null
+  let lyricIndex = -1
+  let silentRanges: SilentRange[] = []
+  let queueSavedAt = 0
+  let gapless = false
+  let preloaded: PreparedSound | null = null
+  let preloading = false
+  // Set when the next song didn't fit in the memory budget next to the
+  // current one, so the ticker doesn't keep reading it again.
+  let preloadSkipped = false
+  let fadingOut: { sound: Howl, url: string | null, fileBytes: number, decodedBytes: number, timer: ReturnType<typeof setTimeout> } | null = null
+  // The upcoming songs are stored in settings, every change to the queue
+  // saves its snapshot.
+  const queue = createQueue({
+    getQueue: () => useNuxtApp().$settings.getQueue(),
+    setQueue: songs => useNuxtApp().$settings.setQueue(songs)
+  }, () => player.saveQueue())
 
+  const abLoop = ref<AbLoop | null>(null)
+  const canSeek = ref(false)
   const currentSong = ref<Song | null>(null)
   const duration = ref(0)
   const looping = ref(false)
   const muted = ref(false)
   const paused = ref(true)
   const progress = ref(0)
+  const repeatMode = ref<RepeatMode>('off')
+  const shuffled = ref(false)
+  const levels = ref<OutputLevels | null>(null)
+  const lyrics = ref<Lyrics | null>(null)
   const time = ref(0)
   const volume = ref(50)
 
   const player = {
+    abLoop,
+    canSeek,
     currentSong,
     duration,
+    levels,
     looping,
+    lyrics,
     muted,
     paused,
     progress,
+    repeatMode,
+    shuffled,
     time,
     volume,
 
     applyEQ(eq: EQSettings) {
       if (!sound || !equalizer) return;
 
-      Object.entries(eq).forEach(([freq, gain], index) => {
-        const cleanGain = typeof gain === 'string' ? gain.replace(',', '.') : gain;
-        const numericGain = parseFloat(cleanGain);
+      this.routeEqualizer(eq.band_enabled ?? [])
 
-        if (Number.isFinite(numericGain)) {
-          this.setEqGain(index, numericGain);
-        } else {
-          console.error('Non-finite gain encountered', gain, 'Converted to:', numericGain);
-        }
+      const preamp = eq.preamp_db ?? 0
+      preampDb = Number.isFinite(preamp) ? Math.min(12, Math.max(preamp, -12)) : 0
+      this.applyInputGain()
+
+      EQ_FREQUENCIES.forEach((freq, index) => {
+        const gain = eq.values?.[freq.toString()] ?? '0'
+        const numericGain = parseFloat(gain.replace(',', '.'));
+
+        this.setEqGain(index, Number.isFinite(numericGain) ? numericGain : 0);
       });
     },
 
     async loadSong(song: Song) {
-      if (sound) {
-        sound.unload()
-      }
+      this.unload()
 
       this.currentSong.value = song
+      this.abLoop.value = null
+      this.loadLyrics(song)
+      this.loadSilentRanges(song)
+
+      let prepared = preloaded?.song.id === song.id ? preloaded : null
+      if (prepared) {
+        preloaded = null
+      } else {
+        this.discardPreloaded()
+        try {
+          prepared = await this.prepareSound(song)
+        } catch (error) {
+          await this.reportPlaybackError(song, error)
+          throw error
+        }
+      }
+
+      sound = prepared.sound
+      objectUrl = prepared.url
+      memoryBudget = prepared.memoryBudget
+      fileBytes = prepared.fileBytes
+      decodedBytes = prepared.decodedBytes
+      crossfadeMs = prepared.crossfadeMs
+      crossfadeStarted = false
+      gapless = prepared.gapless
+      preloadSkipped = false
 
+      if (sound.state() === 'loaded') {
+        this.handleSoundLoaded(prepared)
+      }
+
+      speed = prepared.speed
+      this.applySpeed()
+      this.applyOutputDevice()
+      sound.volume(this.volume.value / 100)
+      sound.mute(this.muted.value)
+      await this.saveQueue()
+    },
+
+    // Reads the file and builds its Howl without touching the current sound,
+    // so the next song can be prepared while another one is still playing.
+    async prepareSound(song: Song): Promise<PreparedSound> {
       const { $settings } = useNuxtApp()
 
-      const [lossless, streaming, eq] = await Promise.all([
+      const [lossless, streaming, keepStreamed, eq, maxAudioMemoryMb, crossfade, gaplessEnabled, normalizationMode, playbackSpeed, channelBalance, monoOutput] = await Promise.all([
         $settings.getLossless(),
         $settings.getStreaming(),
-        $settings.getEq()
+        $settings.getKeepStreamed(),
+        $settings.getEq(),
+        $settings.getMaxAudioMemoryMb(),
+        $settings.getCrossfadeMs(),
+        $settings.getGapless(),
+        $settings.getNormalization(),
+        $settings.getPlaybackSpeed(),
+        $settings.getBalance(),
+        $settings.getMono()
       ])
-      const fileExtension = lossless ? 'flac' : 'mp3'
-      const fileContent = await readFile(`Vleer/Songs/${song.id}.${fileExtension}`, { baseDir: BaseDirectory.Audio })
-      const blob = new Blob([fileContent], { type: lossless ? 'audio/flac' : 'audio/mp3' })
-      const url = URL.createObjectURL(blob)
+      const songReplayGain = normalizationMode === 'off' ? null : await this.fetchReplayGain(song.id)
+      // The preferred quality comes first, then any other format an imported
+      // file might have been stored as.
+      const preferred = lossless ? 'flac' : 'mp3'
+      const candidates = [preferred, ...AUDIO_EXTENSIONS.filter(ext => ext !== preferred)]
+      const quality = lossless ? 'lossless' : 'compressed'
+      let fileExtension = preferred
+      let fileContent: Uint8Array | null = null
+      // Songs indexed in place are read through the backend, the webview can
+      // only read from the music folder.
+      if (song.path) {
+        fileContent = new Uint8Array(await invoke<ArrayBuffer>('read_song_file', { songId: song.id }))
+        fileExtension = song.path.split('.').pop()?.toLowerCase() ?? preferred
+      } else {
+        for (const extension of candidates) {
+          try {
+            fileContent = await readFile(`Vleer/Songs/${song.id}.${extension}`, { baseDir: BaseDirectory.Audio })
+            fileExtension = extension
+            break
+          } catch {
+            continue
+          }
+        }
+      }
+      if (!fileContent && !streaming) {
+        throw new Error(`No audio file for song ${song.id} (tried ${candidates.join(', ')})`)
+      }
+
+      // Without a local copy, play straight from the backend through an audio
+      // element, which starts as soon as enough is buffered. With
+      // keep_streamed on, the file is also downloaded in the background so it
+      // is local next time.
+      let url: string
+      if (fileContent) {
+        const blob = new Blob([fileContent], { type: AUDIO_MIME_TYPES[fileExtension] })
+        url = URL.createObjectURL(blob)
+      } else {
+        const apiUrl = await $settings.getApiUrl()
+        url = `${apiUrl}/download?id=${encodeURIComponent(song.id)}&quality=${quality}`
+        if (keepStreamed) {
+          invoke('download_from_backend', { id: song.id, quality, url: apiUrl }).catch((error: unknown) => {
+            console.error('Failed to keep streamed song:', error)
+          })
+        }
+      }
+      const fileBytes = fileContent?.byteLength ?? 0
+
+      // Web Audio decodes the whole file into a float32 buffer up front, so
+      // fall back to streaming through an audio element once that would
+      // exceed what the sounds already loaded leave of the configured budget.
+      const budget = maxAudioMemoryMb * 1024 * 1024
+      const estimatedDecoded = estimateDecodedBytes(song.duration)
+      // Only the audio element keeps the pitch when the rate changes, Web Audio
+      // would play sped up speech chipmunked.
+      const html5 = !fileContent || streaming || playbackSpeed !== 1 || fileBytes + estimatedDecoded > budget - this.memoryInUse()
 
-      sound = new Howl({
+      const howl: Howl = new Howl({
         src: [url],
         format: [fileExtension],
-        html5: streaming,
-        onend: () => {
+        html5,
+        onend: async () => {
+          this.stopProgressTicker()
+          await emit('song-ended', { id: song.id })
+          // The sleep fade only turns down this song, the next one would
+          // start at full volume.
+          if (sleepAtEndOfTrack || sleepFading) {
+            this.paused.value = true
+            await this.finishSleepTimer()
+            return
+          }
+          const loop = this.abLoop.value
+          if (loop && sound === howl) {
+            howl.seek(loop.start_ms / 1000)
+            howl.play()
+            return
+          }
           if (this.looping.value) {
-            sound!.play()
-          } else {
-            this.skip()
+            countedSound = null
+            howl.play()
+            return
+          }
+          try {
+            if (!(await this.skip()) && !(this.repeatMode.value === 'all' && await this.restartQueue())) {
+              this.paused.value = true
+            }
+          } catch {
+            this.paused.value = true
           }
         },
         onload: () => {
-          this.duration.value = sound!.duration()
-          this.setupEqualizer()
-          this.setupEqListener()
-          this.applyEQ(eq)
+          if (sound === howl) {
+            this.handleSoundLoaded(prepared)
+          }
         },
         onloaderror: (id, error) => {
-          console.error('Error loading audio:', error)
+          if (sound === howl) {
+            this.paused.value = true
+            this.reportPlaybackError(song, error)
+          } else {
+            console.error('Error loading audio:', error)
+          }
+        },
+        onplayerror: (id, error) => {
+          this.paused.value = true
+          this.reportPlaybackError(song, error)
         },
         onpause: async () => {
           this.paused.value = true
-          await invoke('clear_activity')
+          pausedAt = Date.now()
+          this.stopLevelMeter()
+          this.stopProgressTicker()
+          if (sound === howl) {
+            await this.updateNowPlaying(song, true)
+            await this.saveQueuePosition()
+          }
         },
         onplay: async () => {
           this.paused.value = false
-          this.updateProgress()
-          await invoke('update_activity', {
-            details: `by ${this.currentSong.value?.artist}`,
-            largeImage: 'https://api.vleer.app/thumbnail?id=' + this.currentSong.value?.id,
-            state: this.currentSong.value?.title,
-            youtubeUrl: 'https://www.youtube.com/watch?v=' + this.currentSong.value?.id
-          })
+          this.startLevelMeter()
+          this.startProgressTicker()
+          if (countedSound !== howl) {
+            countedSound = howl
+            invoke('increment_play_count', { songId: song.id }).catch((error: unknown) => {
+              console.error('Failed to record play:', error)
+            })
+          }
+          await this.updateNowPlaying(song, false)
         },
         onseek: () => {
           this.updateProgress()
+          if (sound === howl) {
+            this.updateLyricLine()
+            this.updateNowPlaying(song, this.paused.value)
+          }
         }
       })
 
-      const updateProgressInterval = setInterval(() => {
-        if (sound && !this.paused.value) {
-          const seek = sound.seek() as number
-          this.progress.value = (seek / this.duration.value) * 100
-          this.time.value = seek
+      const prepared: PreparedSound = {
+        song,
+        sound: howl,
+        url,
+        eq,
+        normalization: normalizationMode,
+        speed: playbackSpeed,
+        balance: channelBalance,
+        mono: monoOutput,
+        replayGain: songReplayGain,
+        crossfadeMs: crossfade,
+        gapless: gaplessEnabled,
+        memoryBudget: budget,
+        fileBytes,
+        decodedBytes: html5 ? 0 : estimatedDecoded
+      }
+      return prepared
+    },
+
+    // Keeps the Discord presence and the OS media controls in sync with playback.
+    async updateNowPlaying(song: Song, paused: boolean) {
+      const position = sound ? sound.seek() as number : 0
+      const state = { song, position, duration: this.duration.value, paused }
+      invoke('update_now_playing', state).catch((error: unknown) => {
+        console.error('Failed to update media controls:', error)
+      })
+      try {
+        await invoke('update_activity', state)
+      } catch (error) {
+        console.error('Failed to update Discord activity:', error)
+      }
+    },
+
+    // Not every song has lyrics, so failing to find any just leaves them empty.
+    async loadLyrics(song: Song) {
+      this.lyrics.value = null
+      lyricIndex = -1
+      try {
+        const loaded = await invoke<Lyrics>('get_lyrics', { song })
+        if (this.currentSong.value?.id === song.id) {
+          this.lyrics.value = loaded
+          this.updateLyricLine()
+        }
+      } catch (error) {
+        console.debug('No lyrics for', song.id, error)
+      }
+    },
+
+    // Quiet parts the progress ticker skips over while skip_silence is on.
+    async loadSilentRanges(song: Song) {
+      silentRanges = []
+      const { $settings } = useNuxtApp()
+      if (!(await $settings.getSkipSilence())) return
+
+      try {
+        const ranges = await invoke<SilentRange[]>('get_silent_ranges', { songId: song.id })
+        if (this.currentSong.value?.id === song.id) {
+          silentRanges = ranges
         }
-      }, 1000)
+      } catch (error) {
+        console.error('Failed to find silence in', song.id, error)
+      }
+    },
+
+    async setSkipSilence(enabled: boolean) {
+      const { $settings } = useNuxtApp()
+      await $settings.setSkipSilence(enabled)
+      if (this.currentSong.value) {
+        await this.loadSilentRanges(this.currentSong.value)
+      }
+    },
+
+    // Emits lyric-line whenever playback crosses into another synced line,
+    // including backwards after a seek. index is -1 before the first line.
+    updateLyricLine() {
+      const current = this.lyrics.value
+      if (!current?.synced) return
 
-      sound.on('end', () => {
-        clearInterval(updateProgressInterval)
+      const timeMs = this.time.value * 1000
+      let index = -1
+      while (index + 1 < current.lines.length && (current.lines[index + 1].time_ms ?? 0) <= timeMs) {
+        index++
+      }
+      if (index === lyricIndex) return
+
+      lyricIndex = index
+      const line = current.lines[index]
+      emit('lyric-line', {
+        id: this.currentSong.value?.id,
+        index,
+        timeMs: line?.time_ms ?? null,
+        text: line?.text ?? '',
+        romanized: line?.romanized ?? null
       })
+    },
 
-      sound.volume(this.volume.value / 100)
-      sound.mute(this.muted.value)
+    async reportPlaybackError(song: Song, error: unknown) {
+      const message = error instanceof Error ? error.message : String(error)
+      console.error('Playback failed for', song.id, message)
+      await emit('playback-error', { id: song.id, message })
+    },
+
+    handleSoundLoaded(prepared: PreparedSound) {
+      // Streams and files that were never probed report a duration of 0,
+      // fall back to the stored one and disable scrubbing if both are unknown.
+      const decodedDuration = prepared.sound.duration()
+      const hasDecodedDuration = Number.isFinite(decodedDuration) && decodedDuration > 0
+      this.duration.value = hasDecodedDuration ? decodedDuration : prepared.song.duration
+
+      // The stored duration comes from backend metadata and is often off for
+      // imported files, keep it in sync with what the decoder reports.
+      const roundedDuration = Math.round(decodedDuration)
+      if (hasDecodedDuration && roundedDuration !== prepared.song.duration) {
+        prepared.song.duration = roundedDuration
+        const { $music } = useNuxtApp()
+        $music.updateSongDuration(prepared.song.id, roundedDuration).catch((error: unknown) => {
+          console.error('Failed to store song duration:', error)
+        })
+      }
+      this.canSeek.value = Number.isFinite(this.duration.value) && this.duration.value > 0
+      this.setupEqualizer()
+      this.setupEqListener()
+      this.applyEQ(prepared.eq)
+
+      normalization = prepared.normalization
+      replayGain = prepared.replayGain
+      this.applyInputGain()
+
+      balance = prepared.balance
+      mono = prepared.mono
+      this.applyBalance()
+      this.applyMono()
+    },
+
+    async fetchReplayGain(songId: string): Promise<ReplayGain | null> {
+      try {
+        return await invoke<ReplayGain>('get_replay_gain', { songId })
+      } catch (error) {
+        console.error('Failed to read ReplayGain for', songId, error)
+        return null
+      }
+    },
+
+    // Combined ReplayGain and EQ preamp, applied before the filter bands so
+    // boosting bands can be offset without clipping. Songs without ReplayGain
+    // tags play at 0 dB. Album mode falls back to the track gain and vice
+    // versa when only one of them is tagged.
+    applyInputGain() {
+      if (!inputGain) return
+
+      let gainDb = preampDb
+      if (normalization === 'track') {
+        gainDb += replayGain?.track_gain_db ?? replayGain?.album_gain_db ?? 0
+      } else if (normalization === 'album') {
+        gainDb += replayGain?.album_gain_db ?? replayGain?.track_gain_db ?? 0
+      }
+      inputGain.gain.setValueAtTime(Math.pow(10, gainDb / 20), Howler.ctx.currentTime)
+    },
+
+    async setNormalization(mode: NormalizationMode) {
+      normalization = mode
+      const { $settings } = useNuxtApp()
+      await $settings.setNormalization(mode)
+
+      const song = this.currentSong.value
+      if (mode !== 'off' && !replayGain && song) {
+        replayGain = await this.fetchReplayGain(song.id)
+      }
+      this.applyInputGain()
+    },
+
+    async preloadNext() {
+      preloading = true
+      try {
+        const { $settings } = useNuxtApp()
+        const [nextSong] = await $settings.getQueue()
+        if (nextSong) {
+          this.discardPreloaded()
+          const next = await this.prepareSound(nextSong)
+          // The file itself is held even when it is streamed, so a song that
+          // doesn't fit next to the current one is only loaded once it starts.
+          if (this.memoryInUse() + next.fileBytes + next.decodedBytes > next.memoryBudget) {
+            next.sound.unload()
+            URL.revokeObjectURL(next.url)
+            preloadSkipped = true
+          } else {
+            preloaded = next
+          }
+        }
+      } catch (error) {
+        console.error('Failed to preload next song:', error)
+      } finally {
+        preloading = false
+      }
+    },
+
+    discardPreloaded() {
+      if (!preloaded) return
+
+      preloaded.sound.unload()
+      URL.revokeObjectURL(preloaded.url)
+      preloaded = null
+    },
+
+    // The current song, one still fading out and a preloaded next one.
+    memoryInUse(): number {
+      let used = fileBytes + decodedBytes
+      if (fadingOut) {
+        used += fadingOut.fileBytes + fadingOut.decodedBytes
+      }
+      if (preloaded) {
+        used += preloaded.fileBytes + preloaded.decodedBytes
+      }
+      return used
+    },
+
+    // decodedBytes and fileBytes are the current song's, usedBytes counts
+    // everything loaded.
+    getAudioDiagnostics() {
+      return {
+        budgetBytes: memoryBudget,
+        decodedBytes,
+        fileBytes,
+        streaming: sound ? (sound as any)._html5 as boolean : false,
+        usedBytes: this.memoryInUse()
+      }
+    },
+
+    // Read straight from the sound rather than the ticker so it is accurate
+    // right after a reload, before any playback-progress event has arrived.
+    getProgress(): PlaybackProgress {
+      const position = sound ? sound.seek() as number : 0
+      return {
+        positionMs: Math.round((Number.isFinite(position) ? position : 0) * 1000),
+        durationMs: Math.round(this.duration.value * 1000),
+        isPlaying: !this.paused.value
+      }
+    },
+
+    // Everything in the queue including songs already played, so a queue view
+    // can show where playback is.
+    async getQueueState(): Promise<QueueState> {
+      return await queue.read(this.currentSong.value)
+    },
+
+    // Stores the queue by song id along with the current position, for
+    // restoreQueue on the next launch.
+    async saveQueue() {
+      queueSavedAt = Date.now()
+      const { $settings } = useNuxtApp()
+      await $settings.setQueueSnapshot({
+        ...await queue.snapshot(this.currentSong.value),
+        position: this.getProgress().positionMs / 1000
+      }).catch((error: unknown) => {
+        console.error('Failed to save the queue:', error)
+      })
+    },
+
+    // Between queue changes only the position needs saving.
+    async saveQueuePosition() {
+      queueSavedAt = Date.now()
+      const { $settings } = useNuxtApp()
+      await $settings.setQueuePosition(this.getProgress().positionMs / 1000).catch((error: unknown) => {
+        console.error('Failed to save the queue position:', error)
+      })
+    },
+
+    // Loads the repeat mode, the queue saved by saveQueue and the song that
+    // was playing at the saved position. Without a saved queue it falls back
+    // to the current_song setting. The song is only played if
+    // resume_on_launch is on. Returns whether a song was loaded.
+    async restoreQueue(): Promise<boolean> {
+      const { $music, $settings } = useNuxtApp()
+      const [restored, resumeOnLaunch, repeatMode] = await Promise.all([
+        $music.restoreQueue(),
+        $settings.getResumeOnLaunch(),
+        $settings.getRepeatMode()
+      ])
+      this.repeatMode.value = repeatMode
+      this.looping.value = repeatMode === 'one'
+
+      let position = 0
+      if (restored.current_index < 0) {
+        const lastSong = await $settings.getCurrentSong()
+        const song = lastSong ? await $music.getSong(lastSong.id) : null
+        if (!song) return false
+        await this.loadSong(song)
+      } else {
+        position = restored.position
+        await queue.restore(restored.songs, restored.current_index)
+        await this.loadSong(restored.songs[restored.current_index])
+      }
+
+      const restoredSound = sound!
+      const resume = () => {
+        if (sound !== restoredSound) return
+        if (position > 0 && this.canSeek.value) {
+          restoredSound.seek(Math.min(position, this.duration.value))
+          this.updateProgress()
+          this.saveQueuePosition()
+        }
+        if (resumeOnLaunch) {
+          this.play()
+        }
+      }
+      if (restoredSound.state() === 'loaded') {
+        resume()
+      } else {
+        restoredSound.once('load', resume)
+      }
+      return true
+    },
+
+    getOutputLevels(): OutputLevels | null {
+      if (!channelAnalyzers) return null
+
+      const toDb = (value: number) => value > 0 ? Math.max(20 * Math.log10(value), -100) : -100
+      const measure = (channelAnalyzer: AnalyserNode) => {
+        const samples = new Float32Array(channelAnalyzer.fftSize)
+        channelAnalyzer.getFloatTimeDomainData(samples)
+        let peak = 0
+        let sumSquares = 0
+        for (const sample of samples) {
+          peak = Math.max(peak, Math.abs(sample))
+          sumSquares += sample * sample
+        }
+        return { peak, rms: Math.sqrt(sumSquares / samples.length) }
+      }
+
+      const [left, right] = channelAnalyzers.map(measure)
+      const channel = ({ peak, rms }: { peak: number, rms: number }): ChannelLevels => ({
+        peakDb: toDb(peak),
+        rmsDb: toDb(rms)
+      })
+      return {
+        ...channel({
+          peak: Math.max(left.peak, right.peak),
+          rms: Math.sqrt((left.rms ** 2 + right.rms ** 2) / 2)
+        }),
+        left: channel(left),
+        right: channel(right)
+      }
+    },
+
+    async startLevelMeter() {
+      this.stopLevelMeter()
+
+      const { $settings } = useNuxtApp()
+      if (!(await $settings.getLevelMeter())) return
+
+      levelMeterInterval = setInterval(() => {
+        this.levels.value = this.getOutputLevels()
+      }, 50)
+    },
+
+    stopLevelMeter() {
+      if (levelMeterInterval) {
+        clearInterval(levelMeterInterval)
+        levelMeterInterval = null
+      }
+      this.levels.value = null
     },
 
     getAnalyzerData() {
@@ -130,27 +774,162 @@ export default defineNuxtPlugin((nuxtApp) => {
       return dataArray
     },
 
-    mute() {
+    // Folds the analyser's FFT bins into log-spaced bands between 20 Hz and
+    // 20 kHz, each the loudest bin in its range scaled to 0-1. Low bands
+    // narrower than one bin repeat that bin.
+    getSpectrum(bandCount = SPECTRUM_BANDS): number[] | null {
+      const data = this.getAnalyzerData()
+      if (!data) return null
+
+      const nyquist = Howler.ctx.sampleRate / 2
+      const binHz = nyquist / data.length
+      const maxHz = Math.min(SPECTRUM_MAX_HZ, nyquist)
+      const edge = (band: number) => SPECTRUM_MIN_HZ * Math.pow(maxHz / SPECTRUM_MIN_HZ, band / bandCount)
+
+      const bands: number[] = []
+      for (let band = 0; band < bandCount; band++) {
+        const start = Math.min(Math.floor(edge(band) / binHz), data.length - 1)
+        const end = Math.min(Math.max(Math.ceil(edge(band + 1) / binHz), start + 1), data.length)
+        let peak = 0
+        for (let bin = start; bin < end; bin++) {
+          peak = Math.max(peak, data[bin])
+        }
+        bands.push(peak / 255)
+      }
+      return bands
+    },
+
+    // Started and stopped through the start_visualizer and stop_visualizer
+    // commands so the analyser isn't polled while no visualizer is open.
+    startVisualizer() {
+      this.stopVisualizer()
+      spectrumInterval = setInterval(() => {
+        const bands = this.getSpectrum()
+        if (bands) {
+          emit('spectrum-data', bands)
+        }
+      }, SPECTRUM_INTERVAL_MS)
+    },
+
+    stopVisualizer() {
+      if (spectrumInterval) {
+        clearInterval(spectrumInterval)
+        spectrumInterval = null
+      }
+    },
+
+    // Unmuting goes back to the stored volume rather than whatever the
+    // slider was last left at.
+    async mute() {
       this.muted.value = !this.muted.value
       if (sound) {
         sound.mute(this.muted.value)
       }
       const { $settings } = useNuxtApp()
-      $settings.setMuted(this.muted.value)
+      const storedVolume = await $settings.setMuted(this.muted.value)
+      if (!this.muted.value) {
+        this.volume.value = Math.round(storedVolume * 100)
+        sound?.volume(storedVolume)
+      }
     },
 
-    pause() {
-      if (sound) {
-        sound.pause()
+    // Fades out over fade_duration_ms before pausing. Playing again before
+    // the fade ends cancels the pause.
+    async pause(fadeOut = true) {
+      if (!sound) return
+
+      const pausing = sound
+      const { $settings } = useNuxtApp()
+      const fadeMs = fadeOut && pausing.playing() ? await $settings.getFadeDurationMs() : 0
+      if (fadeMs <= 0) {
+        pauseFading = null
+        pausing.pause()
+        return
       }
+
+      pauseFading = pausing
+      pausing.fade(pausing.volume(), 0, fadeMs)
+      pausing.once('fade', () => {
+        if (pauseFading !== pausing) return
+        pauseFading = null
+        pausing.pause()
+        pausing.volume(this.volume.value / 100)
+      })
     },
 
-    play() {
-      if (sound) {
+    // Fades in from silence over fade_duration_ms, or from wherever a pause
+    // that is still fading out got to.
+    async play(fadeIn = true) {
+      if (!sound) return
+
+      if (!(await this.resumeOutput())) {
+        await this.rebuildOutput()
+        return
+      }
+
+      const { $settings } = useNuxtApp()
+      const wasFadingOut = pauseFading === sound
+      pauseFading = null
+      const pausedFor = pausedAt ? Date.now() - pausedAt : 0
+      pausedAt = null
+      if (pausedFor > SMART_RESUME_THRESHOLD_MS && await $settings.getSmartResume()) {
+        const rampMs = await $settings.getSmartResumeRampMs()
+        sound.volume(0)
+        sound.play()
+        sound.fade(0, this.volume.value / 100, rampMs)
+        return
+      }
+
+      const fadeMs = fadeIn ? await $settings.getFadeDurationMs() : 0
+      if (fadeMs > 0) {
+        const from = wasFadingOut ? sound.volume() : 0
+        sound.volume(from)
+        if (!wasFadingOut) sound.play()
+        sound.fade(from, this.volume.value / 100, fadeMs)
+        return
+      }
+      if (wasFadingOut) {
+        sound.volume(this.volume.value / 100)
+      } else {
         sound.play()
       }
     },
 
+    // After the machine sleeps the audio context can come back suspended or
+    // closed, in which case playback silently produces nothing.
+    async resumeOutput() {
+      const ctx = Howler.ctx
+      if (!ctx || ctx.state === 'running') return true
+
+      try {
+        await ctx.resume()
+      } catch (error) {
+        console.error('Failed to resume audio context:', error)
+      }
+      return (ctx.state as string) === 'running'
+    },
+
+    async rebuildOutput() {
+      const song = this.currentSong.value
+      if (!song) return
+
+      const position = sound ? sound.seek() as number : 0
+      this.unload()
+      Howler.unload()
+      analyzer = null
+      equalizer = null
+      eqSource = null
+      inputGain = null
+      balanceStage = null
+
+      await this.loadSong(song)
+      countedSound = sound
+      sound!.once('load', () => {
+        sound!.seek(position)
+        sound!.play()
+      })
+    },
+
     playPause() {
       if (sound) {
         if (this.paused.value) {
@@ -161,6 +940,288 @@ export default defineNuxtPlugin((nuxtApp) => {
       }
     },
 
+    // Goes back to the last song that was skipped past, putting the current
+    // one back at the front of the queue.
+    async prev() {
+      const wasPlaying = !this.paused.value
+      const previousSong = await queue.stepBack(this.currentSong.value)
+      if (!previousSong) {
+        throw new Error('No previous song in the queue')
+      }
+
+      await this.loadSong(previousSong)
+      if (wasPlaying) {
+        this.play()
+      }
+    },
+
+    // What previous does on the tray and media keys, like most players:
+    // restart the song unless it only just started, or when there is nothing
+    // to go back to.
+    async prevOrRewind() {
+      if (this.getProgress().positionMs > PREV_RESTART_THRESHOLD_MS || !queue.hasPlayed()) {
+        this.rewind()
+        return
+      }
+      await this.prev().catch(() => this.rewind())
+    },
+
+    // Removing the current song moves on to the one that took its place, or
+    // to the new last song when it was at the end. Returns the new length.
+    async removeFromQueue(index: number): Promise<number> {
+      const { songs, currentIndex } = await queue.read(this.currentSong.value)
+      if (!Number.isInteger(index) || index < 0 || index >= songs.length) {
+        throw new Error(`Queue index out of range: ${index}`)
+      }
+
+      songs.splice(index, 1)
+      if (index !== currentIndex) {
+        await queue.write(songs, index < currentIndex ? currentIndex - 1 : currentIndex)
+        return songs.length
+      }
+
+      const wasPlaying = !this.paused.value
+      if (songs.length === 0) {
+        this.unload()
+        this.currentSong.value = null
+        this.paused.value = true
+        await queue.write([], -1)
+        return 0
+      }
+
+      const nextIndex = Math.min(index, songs.length - 1)
+      await queue.write(songs, nextIndex)
+      await this.loadSong(songs[nextIndex])
+      if (wasPlaying) {
+        this.play()
+      }
+      return songs.length
+    },
+
+    async playAtIndex(index: number) {
+      const { songs } = await queue.read(this.currentSong.value)
+      if (!Number.isInteger(index) || index < 0 || index >= songs.length) {
+        throw new Error(`Queue index out of range: ${index}`)
+      }
+
+      await queue.write(songs, index)
+      await this.loadSong(songs[index])
+      this.play()
+    },
+
+    // Both return the new length of the whole queue, played songs included.
+    async playNext(song: Song): Promise<number> {
+      const { songs, currentIndex } = await queue.read(this.currentSong.value)
+      songs.splice(currentIndex + 1, 0, song)
+      await queue.write(songs, currentIndex)
+      return songs.length
+    },
+
+    async enqueueSong(song: Song): Promise<number> {
+      const { songs, currentIndex } = await queue.read(this.currentSong.value)
+      songs.push(song)
+      await queue.write(songs, currentIndex)
+      return songs.length
+    },
+
+    // Appends in a single settings write. With nothing loaded, the first of
+    // the added songs is the one the next skip starts.
+    async enqueueSongs(newSongs: Song[]): Promise<number> {
+      const { songs, currentIndex } = await queue.read(this.currentSong.value)
+      songs.push(...newSongs)
+      await queue.write(songs, currentIndex)
+      return songs.length
+    },
+
+    async reorderQueue(from: number, to: number) {
+      await queue.reorder(this.currentSong.value, from, to)
+    },
+
+    async replaceSongFile() {
+      const song = this.currentSong.value
+      if (!song) return
+
+      const { $settings } = useNuxtApp()
+      const [lossless, apiUrl] = await Promise.all([
+        $settings.getLossless(),
+        $settings.getApiUrl()
+      ])
+      await invoke('replace_song_file', {
+        id: song.id,
+        quality: lossless ? 'lossless' : 'compressed',
+        url: apiUrl
+      })
+
+      await this.reloadCurrentSong()
+    },
+
+    // Loads the current song again and continues from the same position,
+    // playing only if it was playing before.
+    async reloadCurrentSong() {
+      const song = this.currentSong.value
+      if (!song) return
+
+      const wasPlaying = !this.paused.value
+      const position = sound ? sound.seek() as number : 0
+
+      await this.loadSong(song)
+      countedSound = sound
+      sound!.once('load', () => {
+        sound!.seek(position)
+        if (wasPlaying) {
+          this.play()
+        }
+      })
+    },
+
+    applySpeed() {
+      if (!sound) return
+
+      const node = (sound as any)._sounds[0]?._node
+      if (node && 'preservesPitch' in node) {
+        node.preservesPitch = true
+      }
+      sound.rate(speed)
+    },
+
+    async listOutputDevices(): Promise<OutputDevice[]> {
+      const devices = await navigator.mediaDevices.enumerateDevices()
+      return devices
+        .filter(device => device.kind === 'audiooutput')
+        .map(device => ({ id: device.deviceId, name: device.label }))
+    },
+
+    // The device is matched by id, falling back to its name since ids can
+    // change between sessions and two devices can share a name. If it is no
+    // longer connected playback stays on the default output.
+    async applyOutputDevice() {
+      const { $settings } = useNuxtApp()
+      const [name, id] = await Promise.all([
+        $settings.getOutputDevice(),
+        $settings.getOutputDeviceId()
+      ])
+      let deviceId = ''
+      if (name || id) {
+        const devices = await this.listOutputDevices()
+        const device = (id ? devices.find(d => d.id === id) : undefined) ?? devices.find(d => d.name === name)
+        if (device) {
+          deviceId = device.id
+        } else {
+          console.warn('Output device not found, using the default output:', name || id)
+        }
+      }
+
+      try {
+        const ctx = Howler.ctx as AudioContext & { setSinkId?: (id: string) => Promise<void> }
+        await ctx?.setSinkId?.(deviceId)
+        const node = sound ? (sound as any)._sounds[0]?._node : null
+        if (node instanceof HTMLMediaElement && 'setSinkId' in node) {
+          await (node as any).setSinkId(deviceId)
+        }
+      } catch (error) {
+        console.error('Failed to switch output device:', error)
+      }
+    },
+
+    // null goes back to the default output.
+    async setOutputDevice(device: OutputDevice | null) {
+      const { $settings } = useNuxtApp()
+      await Promise.all([
+        $settings.setOutputDevice(device?.name ?? ''),
+        $settings.setOutputDeviceId(device?.id ?? '')
+      ])
+      await this.applyOutputDevice()
+    },
+
+    // Either a number of minutes, fading out over the last ten seconds, or
+    // 'end_of_track' to stop once the current song finishes. The queue
+    // doesn't move on once the fade has started.
+    setSleepTimer(duration: number | 'end_of_track') {
+      this.cancelSleepTimer()
+
+      if (duration === 'end_of_track') {
+        sleepAtEndOfTrack = true
+        return
+      }
+
+      const totalMs = Math.max(0, duration * 60 * 1000)
+      const fadeMs = Math.min(SLEEP_FADE_MS, totalMs)
+      sleepTimer = setTimeout(() => {
+        sleepFading = true
+        sound?.fade(sound.volume(), 0, fadeMs)
+        sleepTimer = setTimeout(() => this.finishSleepTimer(), fadeMs)
+      }, totalMs - fadeMs)
+    },
+
+    cancelSleepTimer() {
+      if (sleepTimer) {
+        clearTimeout(sleepTimer)
+        sleepTimer = null
+      }
+      if (sleepFading) {
+        sound?.volume(this.volume.value / 100)
+        sleepFading = false
+      }
+      sleepAtEndOfTrack = false
+    },
+
+    async finishSleepTimer() {
+      sleepTimer = null
+      sleepFading = false
+      sleepAtEndOfTrack = false
+
+      await this.pause(false)
+      sound?.volume(this.volume.value / 100)
+      await emit('sleep-timer-finished')
+    },
+
+    // Turns down the channel on the other side rather than panning, so
+    // nothing from one channel moves into the other.
+    applyBalance() {
+      if (!balanceStage) return
+
+      const now = Howler.ctx.currentTime
+      balanceStage.left.gain.setValueAtTime(balance > 0 ? 1 - balance : 1, now)
+      balanceStage.right.gain.setValueAtTime(balance < 0 ? 1 + balance : 1, now)
+    },
+
+    // -1 is full left, 1 full right and 0 centered.
+    async setBalance(pan: number) {
+      balance = Number.isFinite(pan) ? Math.min(1, Math.max(pan, -1)) : 0
+      this.applyBalance()
+      const { $settings } = useNuxtApp()
+      await $settings.setBalance(balance)
+    },
+
+    applyMono() {
+      if (!balanceStage) return
+
+      routeBalanceStage(balanceStage, mono)
+    },
+
+    async setMono(enabled: boolean) {
+      mono = enabled
+      this.applyMono()
+      const { $settings } = useNuxtApp()
+      await $settings.setMono(mono)
+    },
+
+    async setSpeed(value: number) {
+      const clamped = Math.min(MAX_SPEED, Math.max(value, MIN_SPEED))
+      const { $settings } = useNuxtApp()
+      await $settings.setPlaybackSpeed(clamped)
+      speed = clamped
+
+      // A sound decoded through Web Audio can't change rate without changing
+      // pitch, so reload it as an audio element.
+      const html5 = sound ? (sound as any)._html5 as boolean : true
+      if (!html5 && clamped !== 1) {
+        await this.reloadCurrentSong()
+      } else {
+        this.applySpeed()
+      }
+    },
+
     rewind() {
       if (sound) {
         sound.seek(0)
@@ -179,13 +1240,14 @@ export default defineNuxtPlugin((nuxtApp) => {
       }
     },
 
+    // Takes 0 to 100; the settings side clamps and returns what it stored.
     async setVolume(value: number) {
-      this.volume.value = value
+      const { $settings } = useNuxtApp()
+      const effective = await $settings.setVolume((Number.isFinite(value) ? value : 0) / 100)
+      this.volume.value = Math.round(effective * 100)
       if (sound) {
-        sound.volume(this.volume.value / 100)
+        sound.volume(effective)
       }
-      const { $settings } = useNuxtApp()
-      await $settings.setVolume(this.volume.value / 100)
     },
 
     setupEqualizer() {
@@ -198,8 +1260,7 @@ export default defineNuxtPlugin((nuxtApp) => {
         analyzer = ctx.createAnalyser()
         node.sourceNode = ctx.createMediaElementSource(node)
 
-        const frequencies = [32, 64, 125, 250, 500, 1000, 2000, 4000, 8000, 16000]
-        equalizer = frequencies.map(freq => {
+        equalizer = EQ_FREQUENCIES.map(freq => {
           const filter = ctx.createBiquadFilter()
           filter.type = 'peaking'
           filter.frequency.value = freq
@@ -208,59 +1269,348 @@ export default defineNuxtPlugin((nuxtApp) => {
           return filter
         })
 
-        node.sourceNode.connect(equalizer[0])
-        equalizer.reduce((prev, curr) => {
-          prev.connect(curr)
-          return curr
-        })
-        equalizer[equalizer.length - 1].connect(analyzer)
+        inputGain = ctx.createGain()
+        eqSource = node.sourceNode
+        balanceStage = createBalanceStage(ctx, analyzer)
+        this.routeEqualizer([])
         analyzer.connect(ctx.destination)
+
+        const splitter = ctx.createChannelSplitter(2)
+        analyzer.connect(splitter)
+        channelAnalyzers = [0, 1].map(channel => {
+          const channelAnalyzer = ctx.createAnalyser()
+          channelAnalyzer.fftSize = 2048
+          splitter.connect(channelAnalyzer, channel)
+          return channelAnalyzer
+        })
       }
     },
 
+    // Disabled bands are left out of the chain entirely rather than set to
+    // 0 dB, which would still add the filter's phase shift.
+    routeEqualizer(bandEnabled: boolean[]) {
+      if (!eqSource || !equalizer || !balanceStage || !inputGain) return
+
+      eqSource.disconnect()
+      inputGain.disconnect()
+      equalizer.forEach(filter => filter.disconnect())
+
+      const active = equalizer.filter((_, index) => bandEnabled[index] ?? true)
+      const chain = [eqSource, inputGain, ...active, balanceStage.input]
+      chain.reduce((prev, curr) => {
+        prev.connect(curr)
+        return curr
+      })
+    },
+
     setupEqListener() {
+      if (eqListening) return
+      eqListening = true
+
       listen('eq-change', (event: any) => {
         const newEq = event.payload as EQSettings
         this.applyEQ(newEq)
       })
     },
 
-    async skip() {
+    // Hands the current sound off to fade out on its own while the next song
+    // fades in. The fade is clamped to what is left of the outgoing track and
+    // to half of the incoming one so short tracks still get a full fade in.
+    // Returns whether it moved to another song.
+    async crossfadeToNext(remainingMs: number): Promise<boolean> {
       const { $settings } = useNuxtApp()
-      const queue = await $settings.getQueue()
-      if (queue.length > 0) {
-        const nextSong = queue.shift()
-        if (nextSong) {
-          await $settings.setQueue(queue)
-          await this.loadSong(nextSong)
-          this.play()
+      const [nextSong] = await $settings.getQueue()
+      // A skip that is already under way replaces the sound itself.
+      if (!nextSong || !sound || advancing) return false
+
+      let fadeMs = Math.max(0, Math.min(crossfadeMs, remainingMs))
+      if (nextSong.duration > 0) {
+        fadeMs = Math.min(fadeMs, (nextSong.duration * 1000) / 2)
+      }
+
+      this.finishCrossfade()
+      const outgoing = sound
+      outgoing.off('end')
+      outgoing.off('pause')
+      outgoing.fade(outgoing.volume(), 0, fadeMs)
+      fadingOut = {
+        sound: outgoing,
+        url: objectUrl,
+        fileBytes,
+        decodedBytes,
+        timer: setTimeout(() => this.finishCrossfade(), fadeMs)
+      }
+      sound = null
+      objectUrl = null
+      fileBytes = 0
+      decodedBytes = 0
+
+      // Started before awaiting anything else so no other skip can take the
+      // guard in between.
+      const skipping = this.skip(fadeMs)
+      await emit('song-ended', { id: this.currentSong.value?.id })
+      const skipped = await skipping
+      if (!skipped && !sound) {
+        this.cancelCrossfade()
+      }
+      return skipped
+    },
+
+    // Keeps the outgoing song playing at full volume when there turned out
+    // to be nothing to fade into, e.g. the queue was cleared meanwhile.
+    cancelCrossfade() {
+      if (!fadingOut) return
+
+      clearTimeout(fadingOut.timer)
+      const outgoing = fadingOut.sound
+      sound = outgoing
+      objectUrl = fadingOut.url
+      fileBytes = fadingOut.fileBytes
+      decodedBytes = fadingOut.decodedBytes
+      fadingOut = null
+      outgoing.volume(this.volume.value / 100)
+      outgoing.once('end', () => {
+        if (sound !== outgoing) return
+        this.stopProgressTicker()
+        this.paused.value = true
+      })
+    },
+
+    finishCrossfade() {
+      if (!fadingOut) return
+
+      clearTimeout(fadingOut.timer)
+      fadingOut.sound.unload()
+      if (fadingOut.url) {
+        URL.revokeObjectURL(fadingOut.url)
+      }
+      fadingOut = null
+    },
+
+    // Next as asked for by the user. With crossfade_on_skip on it crossfades
+    // like reaching the end of a song does. Skipping again while a crossfade
+    // is still running cuts the outgoing song off first.
+    async next(): Promise<boolean> {
+      this.finishCrossfade()
+      const { $settings } = useNuxtApp()
+      if (crossfadeMs > 0 && sound?.playing() && await $settings.getCrossfadeOnSkip()) {
+        crossfadeStarted = true
+        const remainingMs = this.canSeek.value ? (this.duration.value - this.time.value) * 1000 : crossfadeMs
+        return await this.crossfadeToNext(remainingMs)
+      }
+      return await this.skip()
+    },
+
+    // Returns whether it moved to another song. A skip that arrives while
+    // another one is still loading (e.g. a manual skip racing the end of the
+    // track) is dropped so the queue only advances once.
+    async skip(fadeInMs = 0): Promise<boolean> {
+      if (advancing) return false
+      advancing = true
+
+      try {
+        const { $settings } = useNuxtApp()
+        const upcoming = await $settings.getQueue()
+        const nextSong = upcoming.shift()
+        if (!nextSong) return false
+
+        if (this.currentSong.value) {
+          queue.pushPlayed(this.currentSong.value)
+        }
+        // Start the next song before writing the queue back so a preloaded
+        // sound follows the previous one without waiting on the IPC round trip.
+        await this.loadSong(nextSong)
+        if (fadeInMs > 0) {
+          sound!.volume(0)
+          sound!.play()
+          sound!.fade(0, this.volume.value / 100, fadeInMs)
+        } else {
+          // No play fade here, it would put a dip between gapless tracks.
+          this.play(false)
         }
+        await $settings.setQueue(upcoming)
+        await this.saveQueue()
+        return true
+      } finally {
+        advancing = false
+      }
+    },
+
+    // Returns false without touching the position when the current song
+    // can't be scrubbed, e.g. a stream with an unknown duration.
+    seek(seconds: number): boolean {
+      if (!sound) return false
+      if (!this.canSeek.value) {
+        console.warn('Seeking is not supported for', this.currentSong.value?.id)
+        return false
+      }
+
+      sound.seek(clampPosition(seconds, this.duration.value))
+      this.updateProgress()
+      return true
+    },
+
+    // Repeats the part of the current song between the two points until it
+    // is cleared or another song is loaded.
+    setAbLoop(startMs: number, endMs: number) {
+      const durationMs = this.duration.value * 1000
+      if (!this.canSeek.value) {
+        throw new Error('A-B loop needs a song that can be seeked')
+      }
+      if (!(startMs >= 0 && startMs < endMs && endMs <= durationMs)) {
+        throw new Error(`Invalid A-B loop ${startMs}-${endMs} ms for a ${Math.round(durationMs)} ms song`)
       }
+      this.abLoop.value = { start_ms: startMs, end_ms: endMs }
+    },
+
+    clearAbLoop() {
+      this.abLoop.value = null
+    },
+
+    // Skips forward, or back for a negative offset, from the current position.
+    seekRelative(offsetSecs: number): boolean {
+      return this.seek(this.getProgress().positionMs / 1000 + offsetSecs)
     },
 
     skipTo(percentage: number) {
+      this.seek((percentage / 100) * this.duration.value)
+    },
+
+    async setRepeatMode(mode: RepeatMode) {
+      this.repeatMode.value = mode
+      this.looping.value = mode === 'one'
+      const { $settings } = useNuxtApp()
+      await $settings.setRepeatMode(mode)
+    },
+
+    // Puts every song played so far, plus the current one, back into the queue
+    // and starts again from the first of them.
+    async restartQueue(): Promise<boolean> {
+      if (!(await queue.restart(this.currentSong.value))) return false
+
+      this.currentSong.value = null
+      return await this.skip()
+    },
+
+    // See setShuffled in utils/queue.ts for what happens to the order.
+    async setShuffle(enabled: boolean) {
+      await queue.setShuffled(this.currentSong.value, enabled)
+      this.shuffled.value = enabled
+      const { $settings } = useNuxtApp()
+      await $settings.setShuffle(enabled)
+    },
+
+    async toggleShuffle() {
+      await this.setShuffle(!this.shuffled.value)
+    },
+
+    async toggleLoop() {
+      await this.setRepeatMode(this.looping.value ? 'off' : 'one')
+    },
+
+    unload() {
       if (sound) {
-        const seekTime = (percentage / 100) * this.duration.value
-        sound.seek(seekTime)
+        sound.unload()
+        sound = null
       }
+      if (objectUrl) {
+        URL.revokeObjectURL(objectUrl)
+        objectUrl = null
+      }
+      fileBytes = 0
+      decodedBytes = 0
+      pausedAt = null
+      this.stopProgressTicker()
+      this.canSeek.value = false
     },
 
-    async toggleLoop() {
-      this.looping.value = !this.looping.value
-      const { $settings } = useNuxtApp()
-      await $settings.setLoop(this.looping.value)
+    // Only runs while playing; paused playback has nothing to report.
+    startProgressTicker() {
+      this.stopProgressTicker()
+      progressInterval = setInterval(() => {
+        this.updateProgress()
+        this.updateLyricLine()
+        emit('playback-progress', {
+          progressMs: Math.round(this.time.value * 1000),
+          durationMs: Math.round(this.duration.value * 1000)
+        })
+        // Keeps the saved position close to the real one in case the app
+        // doesn't get to save on exit.
+        if (Date.now() - queueSavedAt >= QUEUE_SAVE_INTERVAL_MS) {
+          this.saveQueuePosition()
+        }
+
+        // Ranges ending within a tick aren't worth the seek.
+        const timeMs = this.time.value * 1000
+        const silence = silentRanges.find(range => timeMs >= range.start_ms && timeMs < range.end_ms - PROGRESS_INTERVAL_MS)
+        if (silence && this.canSeek.value) {
+          this.seek(silence.end_ms / 1000)
+        }
+
+        const loop = this.abLoop.value
+        if (loop) {
+          if (this.time.value * 1000 >= loop.end_ms) {
+            this.seek(loop.start_ms / 1000)
+          }
+          return
+        }
+        if (this.looping.value || !this.canSeek.value) return
+
+        const remainingMs = (this.duration.value - this.time.value) * 1000
+        if (gapless && !preloaded && !preloading && !preloadSkipped && remainingMs <= PRELOAD_AHEAD_MS) {
+          this.preloadNext()
+        }
+        if (crossfadeMs > 0 && !crossfadeStarted && !sleepFading && remainingMs <= crossfadeMs) {
+          crossfadeStarted = true
+          this.crossfadeToNext(remainingMs)
+        }
+      }, PROGRESS_INTERVAL_MS)
     },
 
-    updateProgress() {
-      if (sound && !this.paused) {
-        const seek = sound.seek() as number
-        this.progress.value = (seek / this.duration.value) * 100
-        this.time.value = seek
-        requestAnimationFrame(() => this.updateProgress())
+    stopProgressTicker() {
+      if (progressInterval) {
+        clearInterval(progressInterval)
+        progressInterval = null
       }
+    },
+
+    updateProgress() {
+      if (!sound) return
+
+      const seek = sound.seek() as number
+      this.time.value = seek
+      this.progress.value = this.duration.value > 0 ? (seek / this.duration.value) * 100 : 0
     }
   }
 
+  listen<AbLoop | null>('ab-loop', (event) => {
+    try {
+      if (event.payload) {
+        player.setAbLoop(event.payload.start_ms, event.payload.end_ms)
+      } else {
+        player.clearAbLoop()
+      }
+    } catch (error) {
+      console.error('Failed to set A-B loop:', error)
+    }
+  })
+
+  listen<boolean>('visualizer', (event) => {
+    if (event.payload) {
+      player.startVisualizer()
+    } else {
+      player.stopVisualizer()
+    }
+  })
+
+  // Best effort: an invoke started while the window unloads may not finish,
+  // which is what the periodic save in the progress ticker is for.
+  window.addEventListener('beforeunload', () => {
+    if (player.currentSong.value) {
+      player.saveQueuePosition()
+    }
+  })
+
   return {
     provide: {
       player
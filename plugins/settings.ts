@@ -1,69 +1,346 @@
 import { invoke } from '@tauri-apps/api/core'
-import type { Settings, Song, EQSettings } from '~/types/types'
+import type { Backend, BackendStatus, DatabaseTransfer, EqPreset, LogLevel, MediaKeyBindings, NormalizationMode, PingResult, PlayerState, QueueSnapshot, RepeatMode, Settings, Song, EQSettings } from '~/types/types'
 
 export default defineNuxtPlugin((nuxtApp) => {
   return {
     provide: {
       settings: {
+        async addBackend(name: string, url: string) {
+          return await invoke('add_backend', { name, url })
+        },
+        async clearQueue() {
+          return await invoke('clear_queue')
+        },
+        async getActiveBackend(): Promise<string> {
+          return await invoke('get_active_backend')
+        },
         async getApiUrl(): Promise<string> {
           return await invoke('get_api_url')
         },
+        async getAutoSelectApi(): Promise<boolean> {
+          return await invoke('get_auto_select_api')
+        },
+        async getBalance(): Promise<number> {
+          return await invoke('get_balance')
+        },
+        async getCloseToTray(): Promise<boolean> {
+          return await invoke('get_close_to_tray')
+        },
+        async getCrossfadeMs(): Promise<number> {
+          return await invoke('get_crossfade_ms')
+        },
+        async getCrossfadeOnSkip(): Promise<boolean> {
+          return await invoke('get_crossfade_on_skip')
+        },
         async getCurrentSong(): Promise<Song | null> {
           return await invoke('get_current_song')
         },
+        async getDiscordRpcEnabled(): Promise<boolean> {
+          return await invoke('get_discord_rpc_enabled')
+        },
+        async getDownloadBandwidthLimitKbps(): Promise<number> {
+          return await invoke('get_download_bandwidth_limit_kbps')
+        },
         async getEq(): Promise<EQSettings> {
           return await invoke('get_eq')
         },
+        async getFadeDurationMs(): Promise<number> {
+          return await invoke('get_fade_duration_ms')
+        },
+        async getGapless(): Promise<boolean> {
+          return await invoke('get_gapless')
+        },
+        async getKeepStreamed(): Promise<boolean> {
+          return await invoke('get_keep_streamed')
+        },
+        async getLastfmEnabled(): Promise<boolean> {
+          return await invoke('get_lastfm_enabled')
+        },
+        async getLastfmUsername(): Promise<string> {
+          return await invoke('get_lastfm_username')
+        },
+        async getLevelMeter(): Promise<boolean> {
+          return await invoke('get_level_meter')
+        },
+        async getLogLevel(): Promise<LogLevel> {
+          return await invoke('get_log_level')
+        },
+        async getLogMaxSizeMb(): Promise<number> {
+          return await invoke('get_log_max_size_mb')
+        },
+        async getLogRetention(): Promise<number> {
+          return await invoke('get_log_retention')
+        },
         async getLossless(): Promise<boolean> {
           return await invoke('get_lossless')
         },
         async getLoop(): Promise<boolean> {
           return await invoke('get_loop')
         },
+        async getLyricsApiUrl(): Promise<string> {
+          return await invoke('get_lyrics_api_url')
+        },
+        async getMaxAudioMemoryMb(): Promise<number> {
+          return await invoke('get_max_audio_memory_mb')
+        },
+        async getMaxConcurrentDownloads(): Promise<number> {
+          return await invoke('get_max_concurrent_downloads')
+        },
+        async getMediaKeys(): Promise<MediaKeyBindings> {
+          return await invoke('get_media_keys')
+        },
+        async getMinSilenceMs(): Promise<number> {
+          return await invoke('get_min_silence_ms')
+        },
+        async getMono(): Promise<boolean> {
+          return await invoke('get_mono')
+        },
         async getMuted(): Promise<boolean> {
           return await invoke('get_muted')
         },
+        async getNormalization(): Promise<NormalizationMode> {
+          return await invoke('get_normalization')
+        },
+        async getOutputDevice(): Promise<string> {
+          return await invoke('get_output_device')
+        },
+        async getOutputDeviceId(): Promise<string> {
+          return await invoke('get_output_device_id')
+        },
+        async getPlaybackSpeed(): Promise<number> {
+          return await invoke('get_playback_speed')
+        },
         async getQueue(): Promise<Song[]> {
           return await invoke('get_queue')
         },
+        async getQueueSnapshot(): Promise<QueueSnapshot> {
+          return await invoke('get_queue_snapshot')
+        },
+        async getPlayerState(): Promise<PlayerState> {
+          return await invoke('get_player_state')
+        },
+        async getAllSettings(): Promise<Settings> {
+          return await invoke('get_all_settings')
+        },
+        async setAllSettings(settings: Settings) {
+          return await invoke('set_all_settings', { settings })
+        },
+        async exportSettings(path: string, includePlaylists = false) {
+          return await invoke('export_settings', { path, includePlaylists })
+        },
+        async importSettings(path: string) {
+          return await invoke('import_settings', { path })
+        },
+        async backupDatabase(dest: string): Promise<DatabaseTransfer> {
+          return await invoke('backup_database', { dest })
+        },
+        async restoreDatabase(src: string): Promise<DatabaseTransfer> {
+          return await invoke('restore_database', { src })
+        },
+        async getRepeatMode(): Promise<RepeatMode> {
+          return await invoke('get_repeat_mode')
+        },
+        async getResumeOnLaunch(): Promise<boolean> {
+          return await invoke('get_resume_on_launch')
+        },
+        async getRomanizeLyrics(): Promise<boolean> {
+          return await invoke('get_romanize_lyrics')
+        },
         async getShuffle(): Promise<boolean> {
           return await invoke('get_shuffle')
         },
+        async getSilenceThresholdDb(): Promise<number> {
+          return await invoke('get_silence_threshold_db')
+        },
+        async getSkipSilence(): Promise<boolean> {
+          return await invoke('get_skip_silence')
+        },
+        async getSmartResume(): Promise<boolean> {
+          return await invoke('get_smart_resume')
+        },
+        async getSmartResumeRampMs(): Promise<number> {
+          return await invoke('get_smart_resume_ramp_ms')
+        },
         async getStreaming(): Promise<boolean> {
           return await invoke('get_streaming')
         },
         async getVolume(): Promise<number> {
           return await invoke('get_volume')
         },
+        async listBackends(): Promise<Backend[]> {
+          return await invoke('list_backends')
+        },
+        async pinQueueItem(index: number) {
+          return await invoke('pin_queue_item', { index })
+        },
+        async pingBackends(): Promise<BackendStatus[]> {
+          return await invoke('ping_backends')
+        },
+        async refreshApiUrl(): Promise<string> {
+          return await invoke('refresh_api_url')
+        },
+        async pingUrls(urls: string[], timeoutMs?: number): Promise<PingResult[]> {
+          return await invoke('ping_urls', { urls, timeoutMs })
+        },
+        async removeBackend(name: string) {
+          return await invoke('remove_backend', { name })
+        },
+        async setActiveBackend(name: string) {
+          return await invoke('set_active_backend', { name })
+        },
         async setApiUrl(apiUrl: string) {
           return await invoke('set_api_url', { apiUrl })
         },
+        async setAutoSelectApi(autoSelectApi: boolean) {
+          return await invoke('set_auto_select_api', { autoSelectApi })
+        },
+        async setBalance(balance: number) {
+          return await invoke('set_balance', { balance })
+        },
+        async setCloseToTray(closeToTray: boolean) {
+          return await invoke('set_close_to_tray', { closeToTray })
+        },
+        async setCrossfadeMs(crossfadeMs: number) {
+          return await invoke('set_crossfade_ms', { crossfadeMs })
+        },
+        async setCrossfadeOnSkip(crossfadeOnSkip: boolean) {
+          return await invoke('set_crossfade_on_skip', { crossfadeOnSkip })
+        },
         async setCurrentSong(currentSong: Song | null) {
           return await invoke('set_current_song', { currentSong })
         },
+        async setDiscordRpcEnabled(discordRpcEnabled: boolean) {
+          return await invoke('set_discord_rpc_enabled', { discordRpcEnabled })
+        },
+        async setDownloadBandwidthLimitKbps(downloadBandwidthLimitKbps: number) {
+          return await invoke('set_download_bandwidth_limit_kbps', { downloadBandwidthLimitKbps })
+        },
         async setEq(eq: EQSettings) {
           return await invoke('set_eq', { eq })
         },
+        async importEqCurve(text: string): Promise<EQSettings> {
+          return await invoke('import_eq_curve', { text })
+        },
+        async exportEqCurve(): Promise<string> {
+          return await invoke('export_eq_curve')
+        },
+        async getEqPresets(): Promise<EqPreset[]> {
+          return await invoke('get_eq_presets')
+        },
+        async saveEqPreset(name: string, values: { [key: string]: string }): Promise<EqPreset> {
+          return await invoke('save_eq_preset', { name, values })
+        },
+        async applyEqPreset(name: string): Promise<EQSettings> {
+          return await invoke('apply_eq_preset', { name })
+        },
+        async deleteEqPreset(name: string) {
+          return await invoke('delete_eq_preset', { name })
+        },
+        async setFadeDurationMs(fadeDurationMs: number) {
+          return await invoke('set_fade_duration_ms', { fadeDurationMs })
+        },
+        async setGapless(gapless: boolean) {
+          return await invoke('set_gapless', { gapless })
+        },
+        async setKeepStreamed(keepStreamed: boolean) {
+          return await invoke('set_keep_streamed', { keepStreamed })
+        },
+        async setLastfmEnabled(lastfmEnabled: boolean) {
+          return await invoke('set_lastfm_enabled', { lastfmEnabled })
+        },
+        async setLevelMeter(levelMeter: boolean) {
+          return await invoke('set_level_meter', { levelMeter })
+        },
+        async setLogLevel(level: LogLevel) {
+          return await invoke('set_log_level', { level })
+        },
+        async setLogMaxSizeMb(logMaxSizeMb: number) {
+          return await invoke('set_log_max_size_mb', { logMaxSizeMb })
+        },
+        async setLogRetention(logRetention: number) {
+          return await invoke('set_log_retention', { logRetention })
+        },
         async setLossless(lossless: boolean) {
           return await invoke('set_lossless', { lossless })
         },
         async setLoop(loop: boolean) {
           return await invoke('set_loop', { loop })
         },
-        async setMuted(muted: boolean) {
+        async setLyricsApiUrl(lyricsApiUrl: string) {
+          return await invoke('set_lyrics_api_url', { lyricsApiUrl })
+        },
+        async setMaxAudioMemoryMb(maxAudioMemoryMb: number) {
+          return await invoke('set_max_audio_memory_mb', { maxAudioMemoryMb })
+        },
+        async setMaxConcurrentDownloads(maxConcurrentDownloads: number) {
+          return await invoke('set_max_concurrent_downloads', { maxConcurrentDownloads })
+        },
+        async setMediaKeys(mediaKeys: MediaKeyBindings) {
+          return await invoke('set_media_keys', { mediaKeys })
+        },
+        async setMinSilenceMs(minSilenceMs: number) {
+          return await invoke('set_min_silence_ms', { minSilenceMs })
+        },
+        async setMono(mono: boolean) {
+          return await invoke('set_mono', { mono })
+        },
+        async setMuted(muted: boolean): Promise<number> {
           return await invoke('set_muted', { muted })
         },
-        async setQueue(queue: Song[]) {
-          return await invoke('set_queue', { queue });
+        async setNormalization(normalization: NormalizationMode) {
+          return await invoke('set_normalization', { normalization })
+        },
+        async setOutputDevice(outputDevice: string) {
+          return await invoke('set_output_device', { outputDevice })
+        },
+        async setOutputDeviceId(outputDeviceId: string) {
+          return await invoke('set_output_device_id', { outputDeviceId })
+        },
+        async setPlaybackSpeed(playbackSpeed: number) {
+          return await invoke('set_playback_speed', { playbackSpeed })
+        },
+        // Only the ids are stored, getQueue looks the songs up again.
+        async setQueue(queue: (Song | string)[]) {
+          return await invoke('set_queue', { queue: queue.map(song => typeof song === 'string' ? song : song.id) });
+        },
+        async setQueuePosition(position: number) {
+          return await invoke('set_queue_position', { position })
+        },
+        async setQueueSnapshot(queueSnapshot: QueueSnapshot) {
+          return await invoke('set_queue_snapshot', { queueSnapshot })
+        },
+        async setRepeatMode(repeatMode: RepeatMode) {
+          return await invoke('set_repeat_mode', { repeatMode })
+        },
+        async setResumeOnLaunch(resumeOnLaunch: boolean) {
+          return await invoke('set_resume_on_launch', { resumeOnLaunch })
+        },
+        async setRomanizeLyrics(romanizeLyrics: boolean) {
+          return await invoke('set_romanize_lyrics', { romanizeLyrics })
         },
         async setShuffle(shuffle: boolean) {
           return await invoke('set_shuffle', { shuffle })
         },
+        async setSilenceThresholdDb(silenceThresholdDb: number) {
+          return await invoke('set_silence_threshold_db', { silenceThresholdDb })
+        },
+        async setSkipSilence(skipSilence: boolean) {
+          return await invoke('set_skip_silence', { skipSilence })
+        },
+        async setSmartResume(smartResume: boolean) {
+          return await invoke('set_smart_resume', { smartResume })
+        },
+        async setSmartResumeRampMs(smartResumeRampMs: number) {
+          return await invoke('set_smart_resume_ramp_ms', { smartResumeRampMs })
+        },
         async setStreaming(streaming: boolean) {
           return await invoke('set_streaming', { streaming })
         },
-        async setVolume(volume: number) {
+        async setVolume(volume: number): Promise<number> {
           return await invoke('set_volume', { volume })
+        },
+        async unpinQueueItem(index: number) {
+          return await invoke('unpin_queue_item', { index })
         }
       }
     }
@@ -1,38 +1,566 @@
-use anyhow::anyhow;
+use crate::db::music::{self, MusicDatabase};
+use crate::db::settings::SettingsDatabase;
+use crate::db::types::{PingFailure, PingResult, ReplayGain};
+use crate::utils::error::AppError;
+use crate::utils::metadata;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use lazy_static::lazy_static;
 use reqwest::Client;
-use std::path::PathBuf;
-use tauri::Result as TauriResult;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
 use tokio::time::Instant;
-use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use std::fs::{self, File};
-use std::io::copy;
+use std::io::Write;
 use std::result::Result;
 
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+pub(crate) const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Audio formats a song file can be stored as, in lookup order.
+pub(crate) const AUDIO_EXTENSIONS: [&str; 6] = ["flac", "mp3", "ogg", "opus", "wav", "m4a"];
+
+static DOWNLOAD_LIMIT_KBPS: AtomicU32 = AtomicU32::new(0);
+
+lazy_static! {
+    // End of the last reserved transfer slot, shared by every running download.
+    static ref NEXT_SLOT: tokio::sync::Mutex<Instant> = tokio::sync::Mutex::new(Instant::now());
+    static ref DOWNLOAD_RATES: Mutex<HashMap<String, f64>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Serialize)]
+struct DownloadProgress {
+    id: String,
+    downloaded: u64,
+    total: Option<u64>,
+    eta_seconds: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+struct DownloadRate {
+    bytes_per_second: u64,
+    active: usize,
+}
+
+/// Exponential moving average of the download rate in bytes per second.
+struct ThroughputEstimator {
+    rate: f64,
+    last_bytes: u64,
+    last_sample: Instant,
+}
+
+impl ThroughputEstimator {
+    const SMOOTHING: f64 = 0.3;
+
+    fn new() -> Self {
+        ThroughputEstimator {
+            rate: 0.0,
+            last_bytes: 0,
+            last_sample: Instant::now(),
+        }
+    }
+
+    fn sample(&mut self, downloaded: u64) {
+        let elapsed = self.last_sample.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+        let instant_rate = (downloaded - self.last_bytes) as f64 / elapsed;
+        self.rate = if self.rate == 0.0 {
+            instant_rate
+        } else {
+            Self::SMOOTHING * instant_rate + (1.0 - Self::SMOOTHING) * self.rate
+        };
+        self.last_bytes = downloaded;
+        self.last_sample = Instant::now();
+    }
+
+    fn eta_seconds(&self, downloaded: u64, total: Option<u64>) -> Option<u64> {
+        let remaining = total?.saturating_sub(downloaded);
+        if self.rate < 1.0 {
+            return None;
+        }
+        Some((remaining as f64 / self.rate).ceil() as u64)
+    }
+}
+
+/// Cancellation tokens of the running downloads, by song id.
+#[derive(Default)]
+pub struct DownloadTokens {
+    tokens: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl DownloadTokens {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, CancellationToken>> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn start(&self, id: &str) -> Result<CancellationToken, AppError> {
+        let mut tokens = self.lock();
+        if tokens.contains_key(id) {
+            return Err(AppError::InvalidInput(format!(
+                "{} is already downloading",
+                id
+            )));
+        }
+        let token = CancellationToken::new();
+        tokens.insert(id.to_string(), token.clone());
+        Ok(token)
+    }
+
+    fn cancel(&self, id: &str) -> bool {
+        match self.lock().get(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn finish(&self, id: &str) {
+        self.lock().remove(id);
+    }
+}
+
+/// Removes a download from the aggregate rate and the running downloads when
+/// it finishes, fails or is cancelled.
+struct ActiveDownload {
+    app: AppHandle,
+    id: String,
+}
+
+impl Drop for ActiveDownload {
+    fn drop(&mut self) {
+        emit_download_rate(&self.app, &self.id, None);
+        self.app.state::<DownloadTokens>().finish(&self.id);
+    }
+}
+
+pub fn set_download_limit(kbps: u32) {
+    DOWNLOAD_LIMIT_KBPS.store(kbps, Ordering::Relaxed);
+}
+
+async fn refresh_download_limit(app: &AppHandle) {
+    let settings_db = app.state::<SettingsDatabase>();
+    if let Some(kbps) = settings_db
+        .get_setting("download_bandwidth_limit_kbps")
+        .await
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        set_download_limit(kbps);
+    }
+}
+
+/// Waits until `bytes` fit under the bandwidth limit. Each chunk reserves a
+/// slot proportional to its size, so concurrent downloads share the budget.
+async fn throttle(bytes: usize) {
+    let kbps = DOWNLOAD_LIMIT_KBPS.load(Ordering::Relaxed);
+    if kbps == 0 {
+        return;
+    }
+
+    let bytes_per_second = kbps as f64 * 1000.0 / 8.0;
+    let cost = Duration::from_secs_f64(bytes as f64 / bytes_per_second);
+    let slot_end = {
+        let mut next_slot = NEXT_SLOT.lock().await;
+        *next_slot = (*next_slot).max(Instant::now()) + cost;
+        *next_slot
+    };
+    tokio::time::sleep_until(slot_end).await;
+}
+
 #[tauri::command]
 pub async fn download_from_backend(
+    app: AppHandle,
     id: String,
     quality: String,
     url: String,
-) -> Result<(), tauri::Error> {
+) -> Result<(), AppError> {
+    download_song(&app, &id, &quality, &url).await
+}
+
+/// Downloads a song into the library, shared by `download_from_backend` and
+/// the download queue.
+pub(crate) async fn download_song(
+    app: &AppHandle,
+    id: &str,
+    quality: &str,
+    url: &str,
+) -> Result<(), AppError> {
+    refresh_download_limit(app).await;
+
+    let token = app.state::<DownloadTokens>().start(id)?;
+    let _active = ActiveDownload {
+        app: app.clone(),
+        id: id.to_string(),
+    };
+
+    // Written to a .part file first, so a failed or cancelled download never
+    // leaves a truncated song behind.
+    let path = song_file_path(id, quality);
+    let temp_path = path.with_extension("part");
+    let result = tokio::select! {
+        result = stream_download(app, id, quality, url, &temp_path) => result,
+        _ = token.cancelled() => Err(AppError::Cancelled(format!(
+            "Download of {} was cancelled",
+            id
+        ))),
+    };
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    fs::rename(&temp_path, &path).map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        AppError::from(e)
+    })?;
+
+    Ok(())
+}
+
+async fn stream_download(
+    app: &AppHandle,
+    id: &str,
+    quality: &str,
+    url: &str,
+    temp_path: &Path,
+) -> Result<(), AppError> {
     let client = Client::new();
-    let response = client
+    let mut response = client
         .get(format!("{}/download?id={}&quality={}", url, id, quality))
         .send()
+        .await?
+        .error_for_status()?;
+
+    let total = response.content_length();
+
+    let mut file = File::create(temp_path)?;
+    let mut downloaded: u64 = 0;
+    let mut estimator = ThroughputEstimator::new();
+    let mut last_emit = Instant::now();
+
+    while let Some(chunk) = response.chunk().await? {
+        throttle(chunk.len()).await;
+        file.write_all(&chunk)?;
+        downloaded += chunk.len() as u64;
+
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            estimator.sample(downloaded);
+            emit_download_progress(app, id, downloaded, total, &estimator);
+            emit_download_rate(app, id, Some(estimator.rate));
+            last_emit = Instant::now();
+        }
+    }
+
+    estimator.sample(downloaded);
+    emit_download_progress(app, id, downloaded, total, &estimator);
+
+    if let Some(total) = total {
+        if downloaded != total {
+            return Err(AppError::Network(format!(
+                "Download of {} ended early ({} of {} bytes)",
+                id, downloaded, total
+            )));
+        }
+    }
+    file.sync_all()?;
+
+    Ok(())
+}
+
+/// Stops a running download. Its partial file is deleted and
+/// `download_from_backend` or `replace_song_file` returns a `cancelled`
+/// error.
+#[tauri::command]
+pub fn cancel_download(
+    downloads: State<'_, DownloadTokens>,
+    song_id: String,
+) -> Result<(), AppError> {
+    if downloads.cancel(&song_id) {
+        Ok(())
+    } else {
+        Err(AppError::NotFound(format!(
+            "No download running for {}",
+            song_id
+        )))
+    }
+}
+
+fn emit_download_progress(
+    app: &AppHandle,
+    id: &str,
+    downloaded: u64,
+    total: Option<u64>,
+    estimator: &ThroughputEstimator,
+) {
+    let _ = app.emit(
+        "download-progress",
+        DownloadProgress {
+            id: id.to_string(),
+            downloaded,
+            total,
+            eta_seconds: estimator.eta_seconds(downloaded, total),
+        },
+    );
+}
+
+fn emit_download_rate(app: &AppHandle, id: &str, rate: Option<f64>) {
+    let mut rates = DOWNLOAD_RATES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match rate {
+        Some(rate) => rates.insert(id.to_string(), rate),
+        None => rates.remove(id),
+    };
+
+    let _ = app.emit(
+        "download-rate",
+        DownloadRate {
+            bytes_per_second: rates.values().sum::<f64>() as u64,
+            active: rates.len(),
+        },
+    );
+}
+
+/// Downloads a song again over the file that is in use for it, which is
+/// either in `Songs/` or where it was indexed in place. It shares the
+/// bandwidth limit and `cancel_download` with regular downloads, and can't
+/// run while the same song is downloading.
+#[tauri::command]
+pub async fn replace_song_file(
+    app: AppHandle,
+    music_db: State<'_, MusicDatabase>,
+    id: String,
+    quality: String,
+    url: String,
+) -> Result<(), AppError> {
+    let path = music_db.song_file(&id).await?;
+    refresh_download_limit(&app).await;
+
+    let token = app.state::<DownloadTokens>().start(&id)?;
+    let _active = ActiveDownload {
+        app: app.clone(),
+        id: id.clone(),
+    };
+
+    let temp_path = path.with_extension("replace.part");
+    let result = tokio::select! {
+        result = download_replacement(&id, &quality, &url, &temp_path) => result,
+        _ = token.cancelled() => Err(AppError::Cancelled(format!(
+            "Replacing the file of {} was cancelled",
+            id
+        ))),
+    };
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    fs::rename(&temp_path, &path).map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        AppError::from(e)
+    })?;
+
+    Ok(())
+}
+
+/// Downloads the replacement into `temp_path` and checks it against the
+/// length and checksum the backend sent. Without a checksum the file has to
+/// at least parse as audio, so an error page never replaces a song.
+async fn download_replacement(
+    id: &str,
+    quality: &str,
+    url: &str,
+    temp_path: &Path,
+) -> Result<(), AppError> {
+    let client = Client::new();
+    let mut response = client
+        .get(format!("{}/download", url))
+        .query(&[("id", id), ("quality", quality)])
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let expected_len = response.content_length();
+    let expected_md5 = expected_md5(response.headers());
+
+    let mut file = File::create(temp_path)?;
+    let mut hasher = md5::Context::new();
+    let mut downloaded: u64 = 0;
+    while let Some(chunk) = response.chunk().await? {
+        throttle(chunk.len()).await;
+        file.write_all(&chunk)?;
+        hasher.consume(&chunk);
+        downloaded += chunk.len() as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+
+    if downloaded == 0 {
+        return Err(AppError::Network(format!(
+            "Downloaded file for {} is empty",
+            id
+        )));
+    }
+    if let Some(expected_len) = expected_len {
+        if downloaded != expected_len {
+            return Err(AppError::Network(format!(
+                "Downloaded file for {} is truncated ({} of {} bytes)",
+                id, downloaded, expected_len
+            )));
+        }
+    }
+    match expected_md5 {
+        Some(expected_md5) => {
+            if hasher.compute().0 != expected_md5 {
+                return Err(AppError::Network(format!(
+                    "Downloaded file for {} doesn't match its checksum",
+                    id
+                )));
+            }
+        }
+        None => {
+            let temp_path = temp_path.to_path_buf();
+            tokio::task::spawn_blocking(move || metadata::probe_audio(&temp_path))
+                .await
+                .map_err(|e| AppError::Io(e.to_string()))?
+                .map_err(|e| {
+                    AppError::Decode(format!("Downloaded file for {} isn't audio: {}", id, e))
+                })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The MD5 the backend sent for a download, either as `Content-MD5` or as an
+/// `md5=` entry of `Digest`, both base64 encoded.
+fn expected_md5(headers: &reqwest::header::HeaderMap) -> Option<[u8; 16]> {
+    let content_md5 = headers
+        .get("content-md5")
+        .and_then(|value| value.to_str().ok());
+    let digest_md5 = headers
+        .get("digest")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|digest| {
+            digest.split(',').find_map(|entry| {
+                let (algorithm, value) = entry.trim().split_once('=')?;
+                algorithm.eq_ignore_ascii_case("md5").then_some(value)
+            })
+        });
+    let encoded = content_md5.or(digest_md5)?;
+    BASE64_STANDARD.decode(encoded.trim()).ok()?.try_into().ok()
+}
+
+#[tauri::command]
+pub async fn export_song(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+    dest_dir: String,
+) -> Result<String, AppError> {
+    let song = music::get_song(music_db.clone(), song_id.clone())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Song {} is not in the library", song_id)))?;
+
+    let source = music_db.song_file(&song_id).await?;
+    let extension = source
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+
+    let stem = sanitize_file_name(&format!("{} - {}", song.artist, song.title));
+    let dest = unique_path(Path::new(&dest_dir), &stem, extension);
+
+    fs::copy(&source, &dest)?;
+
+    Ok(dest.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub async fn get_replay_gain(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+) -> Result<ReplayGain, AppError> {
+    let path = music_db.song_file(&song_id).await?;
+    let metadata = tokio::task::spawn_blocking(move || metadata::read_metadata(&path))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?
+        .map_err(AppError::Decode)?;
+
+    Ok(ReplayGain {
+        track_gain_db: metadata.track_gain_db,
+        album_gain_db: metadata.album_gain_db,
+    })
+}
+
+/// Reads a song's audio file for the player. Songs indexed in place live
+/// outside the folders the webview may read from.
+#[tauri::command]
+pub async fn read_song_file(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+) -> Result<tauri::ipc::Response, AppError> {
+    let path = music_db.song_file(&song_id).await?;
+    let data = tokio::fs::read(&path)
         .await
-        .map_err(|e| anyhow!(e.to_string()))?;
+        .map_err(|e| AppError::from(e).with_context(&path.display().to_string()))?;
+    Ok(tauri::ipc::Response::new(data))
+}
+
+pub(crate) fn find_song_file(id: &str) -> Result<PathBuf, AppError> {
+    let songs_dir = get_music_path().join("Songs");
+    AUDIO_EXTENSIONS
+        .iter()
+        .map(|ext| songs_dir.join(format!("{}.{}", id, ext)))
+        .find(|path| path.exists())
+        .ok_or_else(|| {
+            AppError::FileNotFound(format!(
+                "No audio file for song {} (tried {})",
+                id,
+                AUDIO_EXTENSIONS.join(", ")
+            ))
+        })
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    sanitized.trim().trim_end_matches('.').to_string()
+}
 
-    let base_path = get_music_path();
+fn unique_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut counter = 1;
+    while path.exists() {
+        path = dir.join(format!("{} ({}).{}", stem, counter, extension));
+        counter += 1;
+    }
+    path
+}
 
-    let mut path = base_path.clone();
+fn song_file_path(id: &str, quality: &str) -> PathBuf {
+    let mut path = get_music_path();
     path.push("Songs");
     let extension = if quality == "compressed" { "mp3" } else { "flac" };
     path.push(format!("{}.{}", id, extension));
-
-    let mut file = File::create(&path).map_err(|e| anyhow!(e.to_string()))?;
-    let content = response.bytes().await.map_err(|e| anyhow!(e.to_string()))?;
-    copy(&mut content.as_ref(), &mut file).map_err(|e| anyhow!(e.to_string()))?;
-
-    Ok(())
+    path
 }
 
 #[tauri::command]
@@ -67,6 +595,18 @@ pub fn get_music_path() -> PathBuf {
         fs::create_dir_all(&path).expect("Failed to create Covers directory");
     }
     path.pop();
+
+    path.push("Waveforms");
+    if !path.exists() {
+        fs::create_dir_all(&path).expect("Failed to create Waveforms directory");
+    }
+    path.pop();
+
+    path.push("Silence");
+    if !path.exists() {
+        fs::create_dir_all(&path).expect("Failed to create Silence directory");
+    }
+    path.pop();
     return path;
 }
 
@@ -91,38 +631,65 @@ pub fn _get_config_path() -> PathBuf {
     return path;
 }
 
+/// Pings every URL concurrently, each with its own timeout (5 seconds by
+/// default). Reachable URLs come first, fastest first.
 #[tauri::command]
-pub async fn ping_urls(urls: Vec<String>) -> TauriResult<Vec<(String, u128)>> {
-    ping_urls_helper(&urls).await.map_err(|e| e.into())
+pub async fn ping_urls(urls: Vec<String>, timeout_ms: Option<u64>) -> Vec<PingResult> {
+    let timeout = timeout_ms.map_or(DEFAULT_PING_TIMEOUT, Duration::from_millis);
+    ping_urls_helper(&urls, timeout).await
 }
 
-async fn ping_urls_helper(
-    urls: &[String],
-) -> Result<Vec<(String, u128)>, anyhow::Error> {
-    let mut handles: Vec<JoinHandle<Result<(String, u128), anyhow::Error>>> = vec![];
-
-    for url in urls.iter() {
-        let url_clone = url.clone();
+pub(crate) async fn ping_urls_helper(urls: &[String], timeout: Duration) -> Vec<PingResult> {
+    let client = Client::new();
+    let mut pings: FuturesUnordered<_> = urls
+        .iter()
+        .map(|url| ping_url(&client, url, timeout))
+        .collect();
 
-        let handle = tokio::spawn(async move {
-            let start = Instant::now();
-            let result = Client::new().head(&url_clone).send().await;
-            let latency = start.elapsed().as_millis();
-            match result {
-                Ok(_) => Ok((url_clone, latency)),
-                Err(e) => Err(anyhow!(e.to_string())),
-            }
-        });
-        handles.push(handle);
+    let mut results = Vec::with_capacity(urls.len());
+    while let Some(result) = pings.next().await {
+        results.push(result);
     }
 
-    let mut results = Vec::new();
-    for handle in handles {
-        if let Ok(Ok(result)) = handle.await {
-            results.push(result);
-        }
+    results.sort_by_key(|result| result.latency_ms.unwrap_or(u64::MAX));
+    results
+}
+
+/// Resolves the host before sending a HEAD request, so a name that doesn't
+/// resolve is reported as a DNS failure rather than a timeout or refused
+/// connection. Only the request counts towards the latency.
+async fn ping_url(client: &Client, url: &str, timeout: Duration) -> PingResult {
+    let failed = |failure| PingResult {
+        url: url.to_string(),
+        reachable: false,
+        latency_ms: None,
+        failure: Some(failure),
+    };
+
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return failed(PingFailure::InvalidUrl);
+    };
+    let (Some(host), Some(port)) = (parsed.host_str(), parsed.port_or_known_default()) else {
+        return failed(PingFailure::InvalidUrl);
+    };
+
+    let lookup_started = Instant::now();
+    match tokio::time::timeout(timeout, tokio::net::lookup_host((host, port))).await {
+        Ok(Ok(mut addrs)) if addrs.next().is_some() => {}
+        Ok(_) => return failed(PingFailure::Dns),
+        Err(_) => return failed(PingFailure::Timeout),
     }
 
-    results.sort_by(|a, b| a.1.cmp(&b.1));
-    Ok(results)
-}
\ No newline at end of file
+    let remaining = timeout.saturating_sub(lookup_started.elapsed());
+    let start = Instant::now();
+    match client.head(url).timeout(remaining).send().await {
+        Ok(_) => PingResult {
+            url: url.to_string(),
+            reachable: true,
+            latency_ms: Some(start.elapsed().as_millis() as u64),
+            failure: None,
+        },
+        Err(e) if e.is_timeout() => failed(PingFailure::Timeout),
+        Err(_) => failed(PingFailure::Connection),
+    }
+}
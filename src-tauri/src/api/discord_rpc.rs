@@ -1,18 +1,100 @@
+use crate::db::settings::SettingsDatabase;
+use crate::db::types::Song;
 use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
 use lazy_static::lazy_static;
 use std::env;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, TryLockError};
 use std::thread;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+
+const THUMBNAIL_URL: &str = "https://api.vleer.app/thumbnail";
+const YOUTUBE_URL: &str = "https://www.youtube.com/watch";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+/// What the presence should currently show, kept so it can be restored after
+/// a reconnect.
+#[derive(Clone)]
+struct Presence {
+    song: Song,
+    position: f64,
+    duration: f64,
+    paused: bool,
+    updated_at: Instant,
+}
 
 lazy_static! {
     static ref DRPC_CLIENT: Mutex<Option<DiscordIpcClient>> =
         Mutex::new(Some(DiscordIpcClient::new("1194990403963858984").unwrap()));
+    static ref LAST_PRESENCE: Mutex<Option<Presence>> = Mutex::new(None);
+}
+
+// Locking: never acquire another lock or await while holding DRPC_CLIENT.
+// LAST_PRESENCE is only held to copy a value in or out, never across an IPC
+// call. IPC calls can block on the Discord socket, so commands must not wait
+// for the client lock on the command thread; anything that talks to Discord
+// either try_locks or runs on its own thread.
+fn lock_client() -> MutexGuard<'static, Option<DiscordIpcClient>> {
+    DRPC_CLIENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn lock_presence() -> MutexGuard<'static, Option<Presence>> {
+    LAST_PRESENCE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
 }
 
 fn is_discord_rpc_disabled() -> bool {
     let value = env::var("disable_discord_rpc").unwrap_or_else(|_| "not set".to_string());
-    value == "1"
+    value == "1" || !ENABLED.load(Ordering::Relaxed)
+}
+
+/// Keeps trying to connect in the background while presence is enabled, so
+/// it shows up even when Discord is started after the app or restarted.
+/// Retries back off exponentially up to a minute. Nothing connects before the
+/// `discord_rpc_enabled` setting has been read.
+pub fn start_connection_manager(app: &tauri::App) {
+    let app_handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let settings_db = app_handle.state::<SettingsDatabase>();
+        if let Ok(value) = settings_db.get_setting("discord_rpc_enabled").await {
+            ENABLED.store(value != "false", Ordering::Relaxed);
+        }
+        thread::spawn(run_connection_manager);
+    });
+}
+
+fn run_connection_manager() {
+    let mut backoff = MIN_BACKOFF;
+    let mut next_attempt = Instant::now();
+    loop {
+        if is_discord_rpc_disabled() || CONNECTED.load(Ordering::Relaxed) {
+            backoff = MIN_BACKOFF;
+            next_attempt = Instant::now();
+        } else if Instant::now() >= next_attempt {
+            match connect_rpc() {
+                Ok(()) => {
+                    log::info!("Connected to Discord");
+                    backoff = MIN_BACKOFF;
+                    restore_presence();
+                }
+                Err(e) => {
+                    log::debug!("{}, retrying in {}s", e, backoff.as_secs());
+                    next_attempt = Instant::now() + backoff;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
 }
 
 #[tauri::command]
@@ -21,10 +103,13 @@ pub fn connect_rpc() -> Result<(), String> {
         return Err("Discord RPC is disabled".to_string());
     }
 
-    let mut drpc = DRPC_CLIENT.lock().map_err(|e| e.to_string())?;
+    let mut drpc = lock_client();
     if let Some(ref mut client) = *drpc {
         match client.connect() {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                CONNECTED.store(true, Ordering::Relaxed);
+                Ok(())
+            }
             Err(e) => Err(format!("Failed to connect to Discord IPC: {}", e)),
         }
     } else {
@@ -32,64 +117,156 @@ pub fn connect_rpc() -> Result<(), String> {
     }
 }
 
+/// Turns the presence on or off at runtime. Disabling clears the activity
+/// and closes the connection; enabling connects and restores the last one.
+/// Called by `set_discord_rpc_enabled`, which also saves the setting.
+pub fn set_discord_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if enabled {
+        // Connect right away instead of waiting for the next retry.
+        thread::spawn(|| {
+            if !CONNECTED.load(Ordering::Relaxed) && connect_rpc().is_ok() {
+                restore_presence();
+            }
+        });
+    } else {
+        thread::spawn(|| {
+            if let Some(ref mut client) = *lock_client() {
+                if CONNECTED.swap(false, Ordering::Relaxed) {
+                    let _ = client.clear_activity();
+                    let _ = client.close();
+                }
+            }
+        });
+    }
+}
+
+/// Marks the connection as lost after a failed IPC call so the background
+/// task reconnects.
+fn handle_ipc_error(client: &mut DiscordIpcClient, action: &str, error: impl std::fmt::Display) {
+    log::warn!("Failed to {} Discord activity: {}", action, error);
+    if CONNECTED.swap(false, Ordering::Relaxed) {
+        let _ = client.close();
+    }
+}
+
 #[tauri::command]
 pub fn clear_activity() -> Result<(), String> {
-    if is_discord_rpc_disabled() {
-        return Err("Discord RPC is disabled".to_string());
+    *lock_presence() = None;
+    if is_discord_rpc_disabled() || !CONNECTED.load(Ordering::Relaxed) {
+        return Ok(());
     }
 
-    let mut drpc = DRPC_CLIENT.lock().map_err(|e| e.to_string())?;
+    let mut drpc = match DRPC_CLIENT.try_lock() {
+        Ok(drpc) => drpc,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            // An update is in flight; clear once it's done instead of waiting here.
+            thread::spawn(|| {
+                if let Some(ref mut client) = *lock_client() {
+                    if let Err(e) = client.clear_activity() {
+                        handle_ipc_error(client, "clear", e);
+                    }
+                }
+            });
+            return Ok(());
+        }
+    };
     if let Some(ref mut client) = *drpc {
         match client.clear_activity() {
             Ok(_) => Ok(()),
-            Err(e) => Err(e.to_string()),
+            Err(e) => {
+                let message = e.to_string();
+                handle_ipc_error(client, "clear", e);
+                Err(message)
+            }
         }
     } else {
         Err("Discord IPC client not initialized".to_string())
     }
 }
 
+/// Shows the song as a "Listening to" presence. While playing, the start and
+/// end timestamps make Discord count the elapsed and remaining time; while
+/// paused they are left out so the clock stops.
 #[tauri::command]
 pub fn update_activity(
-    state: String,
-    details: String,
-    large_image: String,
-    youtube_url: Option<String>,
+    song: Song,
+    position: f64,
+    duration: f64,
+    paused: bool,
 ) -> Result<(), String> {
-    if is_discord_rpc_disabled() {
-        return Err("Discord RPC is disabled".to_string());
+    let presence = Presence {
+        song,
+        position,
+        duration,
+        paused,
+        updated_at: Instant::now(),
+    };
+    *lock_presence() = Some(presence.clone());
+    if is_discord_rpc_disabled() || !CONNECTED.load(Ordering::Relaxed) {
+        // Remembered and shown once presence is enabled and connected.
+        return Ok(());
     }
 
-    thread::spawn(move || {
-        let drpc = DRPC_CLIENT.lock().map_err(|e| e.to_string());
-        if let Ok(mut drpc) = drpc {
-            if let Some(ref mut client) = *drpc {
-                let start_timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("Time went backwards")
-                    .as_secs() as i64;
-
-                let mut activity_builder = activity::Activity::new()
-                    .state(&state)
-                    .details(&details)
-                    .assets(
-                        activity::Assets::new()
-                            .large_image(&large_image)
-                    )
-                    .timestamps(activity::Timestamps::new().start(start_timestamp))
-                    .activity_type(activity::ActivityType::Listening);
-
-                if let Some(ref url) = youtube_url {
-                    let youtube_button = activity::Button::new("YouTube", url);
-                    activity_builder = activity_builder.buttons(vec![youtube_button]);
-                }
+    thread::spawn(move || send_presence(&presence));
+    Ok(())
+}
 
-                match client.set_activity(activity_builder) {
-                    Ok(_) => (),
-                    Err(e) => panic!("Failed to set activity: {}", e),
-                }
+fn restore_presence() {
+    let presence = lock_presence().clone();
+    if let Some(mut presence) = presence {
+        if !presence.paused {
+            presence.position += presence.updated_at.elapsed().as_secs_f64();
+        }
+        send_presence(&presence);
+    }
+}
+
+fn send_presence(presence: &Presence) {
+    let mut drpc = lock_client();
+    if let Some(ref mut client) = *drpc {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_millis() as i64;
+        let song = &presence.song;
+        let details = song.title.clone();
+        let state = format!("by {}", song.artist);
+        let large_image = format!("{}?id={}", THUMBNAIL_URL, song.id);
+        let youtube_url = format!("{}?v={}", YOUTUBE_URL, song.id);
+        let (small_image, small_text) = if presence.paused {
+            ("pause", "Paused")
+        } else {
+            ("play", "Playing")
+        };
+
+        let mut assets = activity::Assets::new()
+            .large_image(&large_image)
+            .small_image(small_image)
+            .small_text(small_text);
+        if !song.album.is_empty() {
+            assets = assets.large_text(&song.album);
+        }
+
+        let mut activity_builder = activity::Activity::new()
+            .state(&state)
+            .details(&details)
+            .assets(assets)
+            .activity_type(activity::ActivityType::Listening)
+            .buttons(vec![activity::Button::new("YouTube", &youtube_url)]);
+
+        if !presence.paused {
+            let start = now_ms - (presence.position.max(0.0) * 1000.0) as i64;
+            let mut timestamps = activity::Timestamps::new().start(start);
+            if presence.duration > 0.0 {
+                timestamps = timestamps.end(start + (presence.duration * 1000.0) as i64);
             }
+            activity_builder = activity_builder.timestamps(timestamps);
         }
-    });
-    Ok(())
+
+        if let Err(e) = client.set_activity(activity_builder) {
+            handle_ipc_error(client, "set", e);
+        }
+    }
 }
@@ -0,0 +1,200 @@
+use crate::api::commands::download_song;
+use crate::db::settings::SettingsDatabase;
+use crate::utils::error::AppError;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+const DEFAULT_MAX_CONCURRENT: usize = 3;
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadState {
+    Queued,
+    Active,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Clone, Debug)]
+pub struct DownloadJob {
+    pub id: String,
+    pub quality: String,
+    pub url: String,
+}
+
+/// Payload of the `download-job` event and the entries of
+/// `get_download_queue`.
+#[derive(Clone, Debug, Serialize)]
+pub struct DownloadJobStatus {
+    pub id: String,
+    pub quality: String,
+    pub state: DownloadState,
+    pub error: Option<String>,
+}
+
+struct Queue {
+    max_concurrent: usize,
+    active: Vec<DownloadJob>,
+    pending: VecDeque<DownloadJob>,
+}
+
+/// Runs queued downloads first in, first out, at most `max_concurrent` at a
+/// time, so downloading a whole album doesn't start every song at once.
+pub struct DownloadManager {
+    queue: Mutex<Queue>,
+}
+
+impl Default for DownloadManager {
+    fn default() -> Self {
+        DownloadManager {
+            queue: Mutex::new(Queue {
+                max_concurrent: DEFAULT_MAX_CONCURRENT,
+                active: Vec::new(),
+                pending: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+impl DownloadManager {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn set_max_concurrent(&self, app: &AppHandle, max_concurrent: u32) {
+        self.lock().max_concurrent = (max_concurrent as usize).max(1);
+        self.start_next(app);
+    }
+
+    fn enqueue(&self, app: &AppHandle, job: DownloadJob) -> Result<(), AppError> {
+        {
+            let mut queue = self.lock();
+            let mut queued = queue.active.iter().chain(queue.pending.iter());
+            if queued.any(|other| other.id == job.id) {
+                return Err(AppError::InvalidInput(format!(
+                    "{} is already queued",
+                    job.id
+                )));
+            }
+            emit_job(app, &job, DownloadState::Queued, None);
+            queue.pending.push_back(job);
+        }
+        self.start_next(app);
+        Ok(())
+    }
+
+    /// Starts pending jobs while there are free slots.
+    fn start_next(&self, app: &AppHandle) {
+        let mut queue = self.lock();
+        while queue.active.len() < queue.max_concurrent {
+            let Some(job) = queue.pending.pop_front() else {
+                break;
+            };
+            queue.active.push(job.clone());
+            emit_job(app, &job, DownloadState::Active, None);
+
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let result = download_song(&app, &job.id, &job.quality, &job.url).await;
+                let manager = app.state::<DownloadManager>();
+                manager.lock().active.retain(|other| other.id != job.id);
+                match result {
+                    Ok(()) => emit_job(&app, &job, DownloadState::Done, None),
+                    Err(AppError::Cancelled(_)) => {
+                        emit_job(&app, &job, DownloadState::Cancelled, None)
+                    }
+                    Err(e) => {
+                        log::warn!("Queued download of {} failed: {}", job.id, e);
+                        emit_job(&app, &job, DownloadState::Failed, Some(e.to_string()));
+                    }
+                }
+                manager.start_next(&app);
+            });
+        }
+    }
+
+    fn statuses(&self) -> Vec<DownloadJobStatus> {
+        let queue = self.lock();
+        let active = queue
+            .active
+            .iter()
+            .map(|job| status(job, DownloadState::Active, None));
+        let pending = queue
+            .pending
+            .iter()
+            .map(|job| status(job, DownloadState::Queued, None));
+        active.chain(pending).collect()
+    }
+
+    fn clear_pending(&self, app: &AppHandle) -> usize {
+        let cleared: Vec<DownloadJob> = self.lock().pending.drain(..).collect();
+        for job in &cleared {
+            emit_job(app, job, DownloadState::Cancelled, None);
+        }
+        cleared.len()
+    }
+}
+
+/// Creates the manager with the `max_concurrent_downloads` setting applied.
+pub fn setup(app: &tauri::App) {
+    app.manage(DownloadManager::default());
+
+    let app_handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let settings_db = app_handle.state::<SettingsDatabase>();
+        if let Some(max_concurrent) = settings_db
+            .get_setting("max_concurrent_downloads")
+            .await
+            .ok()
+            .and_then(|value| value.parse().ok())
+        {
+            app_handle
+                .state::<DownloadManager>()
+                .set_max_concurrent(&app_handle, max_concurrent);
+        }
+    });
+}
+
+fn status(job: &DownloadJob, state: DownloadState, error: Option<String>) -> DownloadJobStatus {
+    DownloadJobStatus {
+        id: job.id.clone(),
+        quality: job.quality.clone(),
+        state,
+        error,
+    }
+}
+
+fn emit_job(app: &AppHandle, job: &DownloadJob, state: DownloadState, error: Option<String>) {
+    let _ = app.emit("download-job", status(job, state, error));
+}
+
+/// Adds a download to the queue. It starts right away when fewer than
+/// `max_concurrent_downloads` are running.
+#[tauri::command]
+pub fn queue_download(
+    app: AppHandle,
+    manager: State<'_, DownloadManager>,
+    id: String,
+    quality: String,
+    url: String,
+) -> Result<(), AppError> {
+    manager.enqueue(&app, DownloadJob { id, quality, url })
+}
+
+/// Running downloads first, then the waiting ones in the order they'll start.
+#[tauri::command]
+pub fn get_download_queue(manager: State<'_, DownloadManager>) -> Vec<DownloadJobStatus> {
+    manager.statuses()
+}
+
+/// Drops every download that hasn't started yet and returns how many there
+/// were. Running downloads keep going; use `cancel_download` to stop them.
+#[tauri::command]
+pub fn clear_download_queue(app: AppHandle, manager: State<'_, DownloadManager>) -> usize {
+    manager.clear_pending(&app)
+}
@@ -0,0 +1,349 @@
+use crate::api::commands::get_music_path;
+use crate::db::settings::SettingsDatabase;
+use crate::db::types::{LyricLine, Lyrics, Song};
+use crate::utils::error::AppError;
+use crate::utils::romanize::romanize;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::State;
+
+// How long a song the lyrics API had nothing for is left alone before it is
+// asked again.
+const NO_LYRICS_RETRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Response of the LRCLIB `/get` endpoint. Other APIs with the same shape work too.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LyricsResponse {
+    synced_lyrics: Option<String>,
+    plain_lyrics: Option<String>,
+}
+
+/// Loads the lyrics for a song from `Songs/{id}.lrc`, or fetches them from the
+/// configured lyrics API and saves them there for next time. Songs the API
+/// has no lyrics for are remembered in `Songs/{id}.nolyrics` for a week, so
+/// playing them doesn't ask again every time. With `romanize_lyrics` on, the
+/// lines also carry their romanized text, see `add_romanized`.
+#[tauri::command]
+pub async fn get_lyrics(
+    settings_db: State<'_, SettingsDatabase>,
+    song: Song,
+) -> Result<Lyrics, AppError> {
+    let mut lyrics = load_lyrics(&settings_db, &song).await?;
+    let romanize = settings_db
+        .get_setting("romanize_lyrics")
+        .await
+        .is_ok_and(|value| value == "true");
+    if romanize {
+        add_romanized(&song.id, &mut lyrics);
+    }
+    Ok(lyrics)
+}
+
+async fn load_lyrics(settings_db: &SettingsDatabase, song: &Song) -> Result<Lyrics, AppError> {
+    let path = lyrics_path(&song.id);
+    if path.exists() {
+        let text = fs::read_to_string(&path)
+            .map_err(|e| AppError::from(e).with_context(&path.display().to_string()))?;
+        return Ok(parse_lyrics(&text));
+    }
+
+    let api_url = settings_db
+        .get_setting("lyrics_api_url")
+        .await
+        .unwrap_or_default();
+    if api_url.trim().is_empty() {
+        return Err(no_lyrics(&song.id));
+    }
+    let missing_path = path.with_extension("nolyrics");
+    if recently_missing(&missing_path) {
+        return Err(no_lyrics(&song.id));
+    }
+
+    let text = match fetch_lyrics(api_url.trim().trim_end_matches('/'), song).await {
+        Ok(text) => text,
+        Err(e @ AppError::NotFound(_)) => {
+            if let Err(write_error) = fs::write(&missing_path, "") {
+                log::warn!(
+                    "Failed to remember missing lyrics for {}: {}",
+                    song.id,
+                    write_error
+                );
+            }
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+    if let Err(e) = fs::write(&path, &text) {
+        log::warn!("Failed to cache lyrics for {}: {}", song.id, e);
+    }
+    Ok(parse_lyrics(&text))
+}
+
+/// Fills in the romanized lines from `Songs/{id}.romanized.lrc`, or romanizes
+/// the lyrics and saves them there, so the file can be corrected by hand
+/// (kanji are left as they are). A file that doesn't line up with the lyrics
+/// is ignored but not overwritten. Lyrics with nothing to romanize keep
+/// `romanized` empty on every line and aren't saved.
+fn add_romanized(id: &str, lyrics: &mut Lyrics) {
+    let path = lyrics_path(id).with_extension("romanized.lrc");
+    if let Ok(text) = fs::read_to_string(&path) {
+        let stored = parse_lyrics(&text);
+        if stored.lines.len() == lyrics.lines.len() {
+            for (line, romanized) in lyrics.lines.iter_mut().zip(stored.lines) {
+                line.romanized = Some(romanized.text).filter(|text| *text != line.text);
+            }
+            return;
+        }
+        log::warn!(
+            "Ignoring {}, its lines don't match the lyrics",
+            path.display()
+        );
+    }
+
+    for line in &mut lyrics.lines {
+        line.romanized = romanize(&line.text);
+    }
+    if path.exists() || lyrics.lines.iter().all(|line| line.romanized.is_none()) {
+        return;
+    }
+    if let Err(e) = fs::write(&path, format_romanized(lyrics)) {
+        log::warn!("Failed to cache romanized lyrics for {}: {}", id, e);
+    }
+}
+
+/// The romanized lines, falling back to the original where there is nothing
+/// to romanize, in the same format `parse_lyrics` reads.
+fn format_romanized(lyrics: &Lyrics) -> String {
+    lyrics
+        .lines
+        .iter()
+        .map(|line| {
+            let text = line.romanized.as_deref().unwrap_or(&line.text);
+            match line.time_ms {
+                Some(ms) => format!(
+                    "[{:02}:{:02}.{:03}]{}",
+                    ms / 60_000,
+                    ms / 1000 % 60,
+                    ms % 1000,
+                    text
+                ),
+                None => text.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn no_lyrics(id: &str) -> AppError {
+    AppError::NotFound(format!("No lyrics for song {}", id))
+}
+
+fn lyrics_path(id: &str) -> PathBuf {
+    get_music_path().join("Songs").join(format!("{}.lrc", id))
+}
+
+fn recently_missing(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|at| SystemTime::now().duration_since(at).ok())
+        .is_some_and(|age| age < NO_LYRICS_RETRY)
+}
+
+/// Returns the synced lyrics when the API has them, otherwise the plain ones.
+async fn fetch_lyrics(api_url: &str, song: &Song) -> Result<String, AppError> {
+    let mut query = vec![
+        ("track_name", song.title.clone()),
+        ("artist_name", song.artist.clone()),
+    ];
+    if !song.album.is_empty() {
+        query.push(("album_name", song.album.clone()));
+    }
+    if song.duration > 0 {
+        query.push(("duration", song.duration.to_string()));
+    }
+
+    let response = Client::new()
+        .get(format!("{}/get", api_url))
+        .header("User-Agent", concat!("Vleer/", env!("CARGO_PKG_VERSION")))
+        .query(&query)
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(no_lyrics(&song.id));
+    }
+    let body = response.error_for_status()?.text().await?;
+    let lyrics: LyricsResponse =
+        serde_json::from_str(&body).map_err(|e| AppError::Decode(e.to_string()))?;
+
+    lyrics
+        .synced_lyrics
+        .or(lyrics.plain_lyrics)
+        .filter(|text| !text.trim().is_empty())
+        .ok_or_else(|| no_lyrics(&song.id))
+}
+
+/// Synced lyrics when the text has LRC timestamps, otherwise every line as is.
+pub fn parse_lyrics(text: &str) -> Lyrics {
+    let synced = parse_lrc(text);
+    if !synced.is_empty() {
+        return Lyrics {
+            synced: true,
+            lines: synced
+                .into_iter()
+                .map(|(time, text)| LyricLine {
+                    time_ms: Some(time.as_millis() as u64),
+                    text,
+                    romanized: None,
+                })
+                .collect(),
+        };
+    }
+
+    Lyrics {
+        synced: false,
+        lines: text
+            .trim()
+            .lines()
+            .map(|line| LyricLine {
+                time_ms: None,
+                text: line.trim().to_string(),
+                romanized: None,
+            })
+            .collect(),
+    }
+}
+
+/// Parses the timestamped lines of an LRC file, sorted by time. A line can
+/// carry several timestamps (`[00:12.00][01:30.50]Chorus`), and an
+/// `[offset:ms]` tag shifts every line earlier by that many milliseconds.
+pub fn parse_lrc(text: &str) -> Vec<(Duration, String)> {
+    let mut offset_ms: i64 = 0;
+    let mut lines: Vec<(i64, String)> = Vec::new();
+
+    for line in text.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag_len) = rest.strip_prefix('[').and_then(|tag| tag.find(']')) {
+            let tag = &rest[1..=tag_len];
+            rest = &rest[tag_len + 2..];
+            if let Some(time) = parse_timestamp(tag) {
+                times.push(time);
+            } else if let Some(offset) = tag.strip_prefix("offset:") {
+                offset_ms = offset.trim().parse().unwrap_or(0);
+            }
+        }
+        for time in times {
+            lines.push((time, rest.trim().to_string()));
+        }
+    }
+
+    lines.sort_by_key(|(time, _)| *time);
+    lines
+        .into_iter()
+        .map(|(time, text)| {
+            let ms = (time - offset_ms).max(0) as u64;
+            (Duration::from_millis(ms), text)
+        })
+        .collect()
+}
+
+/// Parses `mm:ss`, `mm:ss.xx` or `mm:ss:xx` into milliseconds.
+fn parse_timestamp(tag: &str) -> Option<i64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    if minutes.is_empty() || !minutes.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let minutes: i64 = minutes.parse().ok()?;
+    let seconds: f64 = seconds.replacen(':', ".", 1).parse().ok()?;
+    if !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    Some(minutes * 60_000 + (seconds * 1000.0).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(lines: &[(Duration, String)]) -> Vec<(u128, &str)> {
+        lines
+            .iter()
+            .map(|(time, text)| (time.as_millis(), text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn line_with_several_timestamps_repeats() {
+        let lines = parse_lrc("[00:12.00][01:30.50]Chorus\n[00:20.00]Verse");
+        assert_eq!(
+            ms(&lines),
+            vec![(12_000, "Chorus"), (20_000, "Verse"), (90_500, "Chorus")]
+        );
+    }
+
+    #[test]
+    fn hundredths_and_thousandths() {
+        assert_eq!(parse_timestamp("01:02.34"), Some(62_340));
+        assert_eq!(parse_timestamp("01:02.345"), Some(62_345));
+        assert_eq!(parse_timestamp("01:02"), Some(62_000));
+        assert_eq!(parse_timestamp("01:02:34"), Some(62_340));
+    }
+
+    #[test]
+    fn invalid_timestamps() {
+        assert_eq!(parse_timestamp("ar:Artist"), None);
+        assert_eq!(parse_timestamp(":12.00"), None);
+        assert_eq!(parse_timestamp("00:61.00"), None);
+        assert_eq!(parse_timestamp("0012"), None);
+    }
+
+    #[test]
+    fn offset_moves_lines_earlier() {
+        let lines = parse_lrc("[offset:500]\n[00:01.00]One\n[00:00.20]Zero");
+        assert_eq!(ms(&lines), vec![(0, "Zero"), (500, "One")]);
+
+        let lines = parse_lrc("[offset:-250]\n[00:01.00]One");
+        assert_eq!(ms(&lines), vec![(1_250, "One")]);
+    }
+
+    #[test]
+    fn garbage_lines_are_skipped() {
+        let text = "[ar:Artist]\n[ti:Title]\nno tag here\n[broken\n[xx:yy]Nope\n[00:05.00]Yes";
+        assert_eq!(ms(&parse_lrc(text)), vec![(5_000, "Yes")]);
+    }
+
+    #[test]
+    fn plain_text_is_not_synced() {
+        let lyrics = parse_lyrics("First line\nSecond line\n");
+        assert!(!lyrics.synced);
+        assert_eq!(lyrics.lines.len(), 2);
+        assert!(lyrics.lines.iter().all(|line| line.time_ms.is_none()));
+    }
+
+    #[test]
+    fn romanized_lines_read_back_in_order() {
+        let mut lyrics = parse_lyrics("[00:01.50]사랑해\n[01:02.345]Hello\n[10:00.00]きょう");
+        for line in &mut lyrics.lines {
+            line.romanized = romanize(&line.text);
+        }
+        let stored = parse_lyrics(&format_romanized(&lyrics));
+        let lines: Vec<_> = stored
+            .lines
+            .iter()
+            .map(|line| (line.time_ms, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (Some(1_500), "saranghae"),
+                (Some(62_345), "Hello"),
+                (Some(600_000), "kyou")
+            ]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::db::types::Song;
+use crate::utils::scrobble;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter};
+
+pub(crate) const ARTWORK_URL: &str = "https://api.vleer.app/thumbnail";
+
+/// The last state pushed by the player and when it arrived.
+static LAST: Mutex<Option<(NowPlaying, Instant)>> = Mutex::new(None);
+
+/// A transport command from the OS media controls. The player lives in the
+/// webview, so these are forwarded there as `media-control` events.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum MediaAction {
+    Play,
+    Pause,
+    PlayPause,
+    Next,
+    Previous,
+    Stop,
+    /// Relative seek in seconds.
+    Seek {
+        offset: f64,
+    },
+    /// Absolute position in seconds.
+    SetPosition {
+        position: f64,
+    },
+}
+
+/// What the OS media controls should show, pushed by the player.
+#[derive(Clone, Debug, Default)]
+pub struct NowPlaying {
+    pub song: Option<Song>,
+    pub paused: bool,
+    pub position: f64,
+    pub duration: f64,
+}
+
+impl NowPlaying {
+    pub fn artwork_url(&self) -> Option<String> {
+        self.song
+            .as_ref()
+            .map(|song| format!("{}?id={}", ARTWORK_URL, song.id))
+    }
+}
+
+/// The last state pushed by the player. While playing, the position is moved
+/// on by the time since it was pushed.
+pub fn current() -> NowPlaying {
+    let last = LAST.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some((now_playing, pushed_at)) = last.as_ref() else {
+        return NowPlaying::default();
+    };
+
+    let mut now_playing = now_playing.clone();
+    if !now_playing.paused && now_playing.song.is_some() {
+        now_playing.position += pushed_at.elapsed().as_secs_f64();
+        if now_playing.duration > 0.0 {
+            now_playing.position = now_playing.position.min(now_playing.duration);
+        }
+    }
+    now_playing
+}
+
+pub fn emit_action(app: &AppHandle, action: MediaAction) {
+    if let Err(e) = app.emit("media-control", action) {
+        log::warn!("Failed to forward media control: {}", e);
+    }
+}
+
+/// Skips forward, or back for a negative offset, from the current position.
+/// The player clamps the result to the song.
+#[tauri::command]
+pub fn seek_relative(app: AppHandle, offset_secs: i64) {
+    emit_action(
+        &app,
+        MediaAction::Seek {
+            offset: offset_secs as f64,
+        },
+    );
+}
+
+/// Start and end of an A-B loop in the current song, in milliseconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct AbLoop {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// Repeats the part of the current song between `start_ms` and `end_ms`
+/// until it is cleared or the song changes.
+#[tauri::command]
+pub fn set_ab_loop(app: AppHandle, start_ms: u64, end_ms: u64) -> Result<(), String> {
+    if start_ms >= end_ms {
+        return Err(format!(
+            "A-B loop start ({} ms) must be before its end ({} ms)",
+            start_ms, end_ms
+        ));
+    }
+    let now_playing = current();
+    if now_playing.song.is_none() {
+        return Err("No song is playing".to_string());
+    }
+    let duration_ms = (now_playing.duration * 1000.0) as u64;
+    if now_playing.duration > 0.0 && end_ms > duration_ms {
+        return Err(format!(
+            "A-B loop end ({} ms) is past the end of the song ({} ms)",
+            end_ms, duration_ms
+        ));
+    }
+    app.emit("ab-loop", Some(AbLoop { start_ms, end_ms }))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn clear_ab_loop(app: AppHandle) -> Result<(), String> {
+    app.emit("ab-loop", None::<AbLoop>)
+        .map_err(|e| e.to_string())
+}
+
+/// Starts the integration for the current platform, if there is one.
+pub fn setup(app: &tauri::App) {
+    #[cfg(target_os = "linux")]
+    super::mpris::setup(app.handle().clone());
+    #[cfg(target_os = "windows")]
+    super::smtc::setup(app.handle().clone());
+    #[cfg(target_os = "macos")]
+    super::now_playing_macos::setup(app.handle().clone());
+}
+
+/// Releases the platform integration before the app exits.
+pub fn shutdown() {
+    #[cfg(target_os = "windows")]
+    super::smtc::shutdown();
+}
+
+#[tauri::command]
+pub fn update_now_playing(
+    app: AppHandle,
+    song: Option<Song>,
+    paused: bool,
+    position: f64,
+    duration: f64,
+) {
+    let now_playing = NowPlaying {
+        song,
+        paused,
+        position,
+        duration,
+    };
+    scrobble::update(&now_playing);
+    *LAST.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) =
+        Some((now_playing.clone(), Instant::now()));
+
+    #[cfg(target_os = "linux")]
+    super::mpris::update(now_playing);
+    #[cfg(target_os = "windows")]
+    super::smtc::update(now_playing);
+    #[cfg(target_os = "macos")]
+    super::now_playing_macos::update(&app, now_playing);
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    let _ = now_playing;
+    #[cfg(not(target_os = "macos"))]
+    let _ = app;
+}
@@ -0,0 +1,86 @@
+use super::media_controls::{emit_action, MediaAction};
+use crate::db::settings::SettingsDatabase;
+use crate::db::types::MediaKeyBindings;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+/// Shortcuts registered by `apply`, so rebinding can release them first.
+static REGISTERED: Mutex<Vec<Shortcut>> = Mutex::new(Vec::new());
+
+/// Binds the media keys from the `media_keys` setting once it has been read.
+pub fn setup(app: &tauri::App) {
+    let app_handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let settings_db = app_handle.state::<SettingsDatabase>();
+        let bindings = match settings_db.get_setting("media_keys").await {
+            Ok(value) => serde_json::from_str(&value).unwrap_or_default(),
+            Err(_) => MediaKeyBindings::default(),
+        };
+        apply(&app_handle, &bindings);
+    });
+}
+
+/// Checks that every bound shortcut can be parsed.
+pub fn validate(bindings: &MediaKeyBindings) -> Result<(), String> {
+    for (accelerator, _) in actions(bindings) {
+        parse(accelerator)?;
+    }
+    Ok(())
+}
+
+/// Replaces the current bindings. A key that another app has already grabbed
+/// is skipped with a warning so the remaining ones still work.
+pub fn apply(app: &AppHandle, bindings: &MediaKeyBindings) {
+    let global_shortcut = app.global_shortcut();
+    let mut registered = REGISTERED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for shortcut in registered.drain(..) {
+        if let Err(e) = global_shortcut.unregister(shortcut) {
+            log::warn!("Failed to unregister media key {:?}: {}", shortcut, e);
+        }
+    }
+
+    for (accelerator, action) in actions(bindings) {
+        let shortcut = match parse(accelerator) {
+            Ok(shortcut) => shortcut,
+            Err(e) => {
+                log::warn!("{}", e);
+                continue;
+            }
+        };
+        let result = global_shortcut.on_shortcut(shortcut, move |app, _, event| {
+            if event.state == ShortcutState::Pressed {
+                emit_action(app, action.clone());
+            }
+        });
+        match result {
+            Ok(()) => registered.push(shortcut),
+            Err(e) => log::warn!("Failed to bind media key {}: {}", accelerator, e),
+        }
+    }
+}
+
+fn actions(bindings: &MediaKeyBindings) -> impl Iterator<Item = (&str, MediaAction)> {
+    [
+        (&bindings.play_pause, MediaAction::PlayPause),
+        (&bindings.next, MediaAction::Next),
+        (&bindings.previous, MediaAction::Previous),
+        (&bindings.stop, MediaAction::Stop),
+    ]
+    .into_iter()
+    .filter_map(|(accelerator, action)| {
+        accelerator
+            .as_deref()
+            .filter(|accelerator| !accelerator.trim().is_empty())
+            .map(|accelerator| (accelerator, action))
+    })
+}
+
+fn parse(accelerator: &str) -> Result<Shortcut, String> {
+    accelerator
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid shortcut {}: {}", accelerator, e))
+}
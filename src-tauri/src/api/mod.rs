@@ -1,3 +1,15 @@
 pub mod updater;
 pub mod commands;
-pub mod discord_rpc;
\ No newline at end of file
+pub mod discord_rpc;
+pub mod downloads;
+pub mod tray;
+pub mod media_controls;
+pub mod lyrics;
+pub mod media_keys;
+pub mod visualizer;
+#[cfg(target_os = "linux")]
+pub mod mpris;
+#[cfg(target_os = "windows")]
+pub mod smtc;
+#[cfg(target_os = "macos")]
+pub mod now_playing_macos;
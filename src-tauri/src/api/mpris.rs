@@ -0,0 +1,126 @@
+use super::media_controls::{emit_action, MediaAction, NowPlaying};
+use mpris_server::{Metadata, PlaybackStatus, Player, Time, TrackId};
+use std::sync::OnceLock;
+use tauri::AppHandle;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+static UPDATES: OnceLock<UnboundedSender<NowPlaying>> = OnceLock::new();
+
+/// Publishes the player on the session bus as `org.mpris.MediaPlayer2.Vleer`.
+/// The MPRIS player isn't `Send`, so it runs on its own thread with a local
+/// executor and gets updates through a channel.
+pub fn setup(app: AppHandle) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    if UPDATES.set(sender).is_err() {
+        return;
+    }
+
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                log::warn!("Failed to start MPRIS runtime: {}", e);
+                return;
+            }
+        };
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&runtime, async move {
+            if let Err(e) = run(app, receiver).await {
+                log::warn!("MPRIS is unavailable: {}", e);
+            }
+        });
+    });
+}
+
+pub fn update(now_playing: NowPlaying) {
+    if let Some(sender) = UPDATES.get() {
+        let _ = sender.send(now_playing);
+    }
+}
+
+async fn run(
+    app: AppHandle,
+    mut updates: UnboundedReceiver<NowPlaying>,
+) -> mpris_server::zbus::Result<()> {
+    let player = Player::builder("Vleer")
+        .identity("Vleer")
+        .desktop_entry("vleer")
+        .can_control(true)
+        .can_play(true)
+        .can_pause(true)
+        .can_go_next(true)
+        .can_go_previous(true)
+        .can_seek(true)
+        .build()
+        .await?;
+
+    let handle = app.clone();
+    player.connect_play(move |_| emit_action(&handle, MediaAction::Play));
+    let handle = app.clone();
+    player.connect_pause(move |_| emit_action(&handle, MediaAction::Pause));
+    let handle = app.clone();
+    player.connect_play_pause(move |_| emit_action(&handle, MediaAction::PlayPause));
+    let handle = app.clone();
+    player.connect_next(move |_| emit_action(&handle, MediaAction::Next));
+    let handle = app.clone();
+    player.connect_previous(move |_| emit_action(&handle, MediaAction::Previous));
+    let handle = app.clone();
+    player.connect_stop(move |_| emit_action(&handle, MediaAction::Stop));
+    let handle = app.clone();
+    player.connect_seek(move |_, offset| {
+        let offset = offset.as_micros() as f64 / 1_000_000.0;
+        emit_action(&handle, MediaAction::Seek { offset });
+    });
+    let handle = app;
+    player.connect_set_position(move |_, _, position| {
+        let position = position.as_micros() as f64 / 1_000_000.0;
+        emit_action(&handle, MediaAction::SetPosition { position });
+    });
+
+    tokio::task::spawn_local(player.run());
+
+    while let Some(now_playing) = updates.recv().await {
+        player.set_metadata(metadata(&now_playing)).await?;
+        player
+            .set_playback_status(match (&now_playing.song, now_playing.paused) {
+                (None, _) => PlaybackStatus::Stopped,
+                (Some(_), true) => PlaybackStatus::Paused,
+                (Some(_), false) => PlaybackStatus::Playing,
+            })
+            .await?;
+        player.set_position(seconds(now_playing.position));
+    }
+    Ok(())
+}
+
+fn metadata(now_playing: &NowPlaying) -> Metadata {
+    let Some(song) = &now_playing.song else {
+        return Metadata::new();
+    };
+
+    let mut builder = Metadata::builder()
+        .title(song.title.clone())
+        .artist([song.artist.clone()])
+        .album(song.album.clone())
+        .length(seconds(now_playing.duration));
+    if let Some(art_url) = now_playing.artwork_url() {
+        builder = builder.art_url(art_url);
+    }
+    // Object paths only allow ASCII letters, digits and underscores.
+    let track_path: String = song
+        .id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if let Ok(track_id) = TrackId::try_from(format!("/app/vleer/track/t{}", track_path).as_str()) {
+        builder = builder.trackid(track_id);
+    }
+    builder.build()
+}
+
+fn seconds(value: f64) -> Time {
+    Time::from_micros((value.max(0.0) * 1_000_000.0) as i64)
+}
@@ -0,0 +1,112 @@
+use super::media_controls::{emit_action, MediaAction, NowPlaying};
+use block2::RcBlock;
+use objc2::rc::Retained;
+use objc2::runtime::AnyObject;
+use objc2_foundation::{NSDictionary, NSNumber, NSString};
+use objc2_media_player::{
+    MPChangePlaybackPositionCommandEvent, MPMediaItemPropertyAlbumTitle, MPMediaItemPropertyArtist,
+    MPMediaItemPropertyPlaybackDuration, MPMediaItemPropertyTitle, MPNowPlayingInfoCenter,
+    MPNowPlayingInfoPropertyElapsedPlaybackTime, MPNowPlayingInfoPropertyPlaybackRate,
+    MPNowPlayingPlaybackState, MPRemoteCommandCenter, MPRemoteCommandEvent,
+    MPRemoteCommandHandlerStatus,
+};
+use std::ptr::NonNull;
+use tauri::AppHandle;
+
+/// Handles the remote commands sent by Control Center, the Touch Bar, media
+/// keys and headphones. MediaPlayer expects to be driven from the main thread.
+pub fn setup(app: AppHandle) {
+    let handle = app.clone();
+    if let Err(e) = app.run_on_main_thread(move || register_commands(handle)) {
+        log::warn!("Failed to register remote commands: {}", e);
+    }
+}
+
+pub fn update(app: &AppHandle, now_playing: NowPlaying) {
+    if let Err(e) = app.run_on_main_thread(move || show(&now_playing)) {
+        log::warn!("Failed to update now playing info: {}", e);
+    }
+}
+
+fn register_commands(app: AppHandle) {
+    let center = unsafe { MPRemoteCommandCenter::sharedCommandCenter() };
+    let commands = unsafe {
+        [
+            (center.playCommand(), MediaAction::Play),
+            (center.pauseCommand(), MediaAction::Pause),
+            (center.togglePlayPauseCommand(), MediaAction::PlayPause),
+            (center.nextTrackCommand(), MediaAction::Next),
+            (center.previousTrackCommand(), MediaAction::Previous),
+            (center.stopCommand(), MediaAction::Stop),
+        ]
+    };
+    for (command, action) in commands {
+        let handle = app.clone();
+        let handler = RcBlock::new(move |_: NonNull<MPRemoteCommandEvent>| {
+            emit_action(&handle, action.clone());
+            MPRemoteCommandHandlerStatus::Success
+        });
+        unsafe {
+            command.setEnabled(true);
+            command.addTargetWithHandler(&handler);
+        }
+    }
+
+    // Scrubbing in Control Center sends the absolute position to jump to.
+    let handler = RcBlock::new(move |event: NonNull<MPRemoteCommandEvent>| {
+        let event = event.cast::<MPChangePlaybackPositionCommandEvent>();
+        let position = unsafe { event.as_ref().positionTime() };
+        emit_action(&app, MediaAction::SetPosition { position });
+        MPRemoteCommandHandlerStatus::Success
+    });
+    unsafe {
+        let command = center.changePlaybackPositionCommand();
+        command.setEnabled(true);
+        command.addTargetWithHandler(&handler);
+    }
+}
+
+fn show(now_playing: &NowPlaying) {
+    let center = unsafe { MPNowPlayingInfoCenter::defaultCenter() };
+    let Some(song) = &now_playing.song else {
+        unsafe {
+            center.setNowPlayingInfo(None);
+            center.setPlaybackState(MPNowPlayingPlaybackState::Stopped);
+        }
+        return;
+    };
+
+    // The system advances the elapsed time by itself at the given rate, so
+    // this only needs to be refreshed when the track, state or position jumps.
+    let rate = if now_playing.paused { 0.0 } else { 1.0 };
+    let keys = unsafe {
+        [
+            MPMediaItemPropertyTitle,
+            MPMediaItemPropertyArtist,
+            MPMediaItemPropertyAlbumTitle,
+            MPMediaItemPropertyPlaybackDuration,
+            MPNowPlayingInfoPropertyElapsedPlaybackTime,
+            MPNowPlayingInfoPropertyPlaybackRate,
+        ]
+    };
+    let values: Vec<Retained<AnyObject>> = unsafe {
+        vec![
+            Retained::cast(NSString::from_str(&song.title)),
+            Retained::cast(NSString::from_str(&song.artist)),
+            Retained::cast(NSString::from_str(&song.album)),
+            Retained::cast(NSNumber::new_f64(now_playing.duration)),
+            Retained::cast(NSNumber::new_f64(now_playing.position)),
+            Retained::cast(NSNumber::new_f64(rate)),
+        ]
+    };
+    let info = NSDictionary::from_vec(&keys, values);
+
+    unsafe {
+        center.setNowPlayingInfo(Some(&info));
+        center.setPlaybackState(if now_playing.paused {
+            MPNowPlayingPlaybackState::Paused
+        } else {
+            MPNowPlayingPlaybackState::Playing
+        });
+    }
+}
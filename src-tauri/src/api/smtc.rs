@@ -0,0 +1,188 @@
+use super::media_controls::{emit_action, MediaAction, NowPlaying};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use tauri::AppHandle;
+use windows::core::{Result, HSTRING};
+use windows::Foundation::{EventRegistrationToken, TimeSpan, TypedEventHandler, Uri};
+use windows::Media::Playback::MediaPlayer;
+use windows::Media::{
+    MediaPlaybackStatus, MediaPlaybackType, PlaybackPositionChangeRequestedEventArgs,
+    SystemMediaTransportControls, SystemMediaTransportControlsButton,
+    SystemMediaTransportControlsButtonPressedEventArgs,
+    SystemMediaTransportControlsTimelineProperties,
+};
+use windows::Storage::Streams::RandomAccessStreamReference;
+
+enum Message {
+    Update(NowPlaying),
+    Shutdown,
+}
+
+static MESSAGES: OnceLock<Sender<Message>> = OnceLock::new();
+static WORKER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// Registers with the System Media Transport Controls so the Windows media
+/// overlay and the media keys reflect and control playback. The controls are
+/// owned by a dedicated thread that gets updates through a channel.
+pub fn setup(app: AppHandle) {
+    let (sender, receiver) = mpsc::channel();
+    if MESSAGES.set(sender).is_err() {
+        return;
+    }
+
+    let worker = thread::spawn(move || {
+        if let Err(e) = run(app, receiver) {
+            log::warn!("System media controls are unavailable: {}", e);
+        }
+    });
+    *WORKER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(worker);
+}
+
+pub fn update(now_playing: NowPlaying) {
+    if let Some(sender) = MESSAGES.get() {
+        let _ = sender.send(Message::Update(now_playing));
+    }
+}
+
+/// Removes the app from the media overlay and waits for the controls to be
+/// released, so nothing is left behind once the process exits.
+pub fn shutdown() {
+    if let Some(sender) = MESSAGES.get() {
+        let _ = sender.send(Message::Shutdown);
+    }
+    let worker = WORKER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take();
+    if let Some(worker) = worker {
+        let _ = worker.join();
+    }
+}
+
+fn run(app: AppHandle, messages: Receiver<Message>) -> Result<()> {
+    // A MediaPlayer that never plays anything is the simplest way for a
+    // desktop app to get its own transport controls without a window handle.
+    // Its built-in command handling is turned off so the buttons reach us.
+    let player = MediaPlayer::new()?;
+    player.CommandManager()?.SetIsEnabled(false)?;
+    let controls = player.SystemMediaTransportControls()?;
+    controls.SetIsEnabled(true)?;
+    controls.SetIsPlayEnabled(true)?;
+    controls.SetIsPauseEnabled(true)?;
+    controls.SetIsStopEnabled(true)?;
+    controls.SetIsNextEnabled(true)?;
+    controls.SetIsPreviousEnabled(true)?;
+
+    let (button_token, position_token) = register_handlers(&controls, app)?;
+
+    let mut current_song: Option<String> = None;
+    for message in messages {
+        match message {
+            Message::Update(now_playing) => {
+                if let Err(e) = show(&controls, &now_playing, &mut current_song) {
+                    log::warn!("Failed to update system media controls: {}", e);
+                }
+            }
+            Message::Shutdown => break,
+        }
+    }
+
+    let _ = controls.RemoveButtonPressed(button_token);
+    let _ = controls.RemovePlaybackPositionChangeRequested(position_token);
+    let _ = controls
+        .DisplayUpdater()
+        .and_then(|updater| updater.ClearAll());
+    let _ = controls.SetIsEnabled(false);
+    let _ = player.Close();
+    Ok(())
+}
+
+fn register_handlers(
+    controls: &SystemMediaTransportControls,
+    app: AppHandle,
+) -> Result<(EventRegistrationToken, EventRegistrationToken)> {
+    let handle = app.clone();
+    let button_token = controls.ButtonPressed(&TypedEventHandler::new(
+        move |_, args: &Option<SystemMediaTransportControlsButtonPressedEventArgs>| {
+            let Some(args) = args else {
+                return Ok(());
+            };
+            let action = match args.Button()? {
+                SystemMediaTransportControlsButton::Play => MediaAction::Play,
+                SystemMediaTransportControlsButton::Pause => MediaAction::Pause,
+                SystemMediaTransportControlsButton::Stop => MediaAction::Stop,
+                SystemMediaTransportControlsButton::Next => MediaAction::Next,
+                SystemMediaTransportControlsButton::Previous => MediaAction::Previous,
+                _ => return Ok(()),
+            };
+            emit_action(&handle, action);
+            Ok(())
+        },
+    ))?;
+
+    let position_token = controls.PlaybackPositionChangeRequested(&TypedEventHandler::new(
+        move |_, args: &Option<PlaybackPositionChangeRequestedEventArgs>| {
+            if let Some(args) = args {
+                let position = args.RequestedPlaybackPosition()?.Duration as f64 / 10_000_000.0;
+                emit_action(&app, MediaAction::SetPosition { position });
+            }
+            Ok(())
+        },
+    ))?;
+
+    Ok((button_token, position_token))
+}
+
+fn show(
+    controls: &SystemMediaTransportControls,
+    now_playing: &NowPlaying,
+    current_song: &mut Option<String>,
+) -> Result<()> {
+    let Some(song) = &now_playing.song else {
+        *current_song = None;
+        controls.DisplayUpdater()?.ClearAll()?;
+        return controls.SetPlaybackStatus(MediaPlaybackStatus::Stopped);
+    };
+
+    // Display properties only change with the track; pausing and seeking just
+    // touch the status and timeline.
+    if current_song.as_deref() != Some(song.id.as_str()) {
+        let updater = controls.DisplayUpdater()?;
+        updater.ClearAll()?;
+        updater.SetType(MediaPlaybackType::Music)?;
+        let properties = updater.MusicProperties()?;
+        properties.SetTitle(&HSTRING::from(song.title.as_str()))?;
+        properties.SetArtist(&HSTRING::from(song.artist.as_str()))?;
+        properties.SetAlbumTitle(&HSTRING::from(song.album.as_str()))?;
+        if let Some(artwork_url) = now_playing.artwork_url() {
+            let uri = Uri::CreateUri(&HSTRING::from(artwork_url))?;
+            updater.SetThumbnail(&RandomAccessStreamReference::CreateFromUri(&uri)?)?;
+        }
+        updater.Update()?;
+        *current_song = Some(song.id.clone());
+    }
+
+    controls.SetPlaybackStatus(if now_playing.paused {
+        MediaPlaybackStatus::Paused
+    } else {
+        MediaPlaybackStatus::Playing
+    })?;
+
+    let timeline = SystemMediaTransportControlsTimelineProperties::new()?;
+    timeline.SetStartTime(time_span(0.0))?;
+    timeline.SetEndTime(time_span(now_playing.duration))?;
+    timeline.SetMinSeekTime(time_span(0.0))?;
+    timeline.SetMaxSeekTime(time_span(now_playing.duration))?;
+    timeline.SetPosition(time_span(now_playing.position))?;
+    controls.UpdateTimelineProperties(&timeline)
+}
+
+/// Converts seconds to a WinRT time span, which counts 100ns ticks.
+fn time_span(seconds: f64) -> TimeSpan {
+    TimeSpan {
+        Duration: (seconds.max(0.0) * 10_000_000.0) as i64,
+    }
+}
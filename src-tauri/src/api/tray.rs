@@ -0,0 +1,69 @@
+use crate::db::settings::SettingsDatabase;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Emitter, Manager, Window, WindowEvent};
+
+static CLOSE_TO_TRAY: AtomicBool = AtomicBool::new(false);
+
+pub fn setup(app: &tauri::App) -> tauri::Result<()> {
+    let play_pause = MenuItem::with_id(app, "play_pause", "Play/Pause", true, None::<&str>)?;
+    let next = MenuItem::with_id(app, "next", "Next", true, None::<&str>)?;
+    let previous = MenuItem::with_id(app, "previous", "Previous", true, None::<&str>)?;
+    let show = MenuItem::with_id(app, "show", "Show Vleer", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&play_pause, &next, &previous, &show, &quit])?;
+
+    let mut tray = TrayIconBuilder::with_id("main")
+        .tooltip("Vleer")
+        .menu(&menu)
+        .on_menu_event(|app, event| match event.id().as_ref() {
+            "play_pause" | "next" | "previous" => {
+                let _ = app.emit("tray-action", event.id().as_ref());
+            }
+            "show" => show_main_window(app),
+            "quit" => app.exit(0),
+            _ => {}
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+
+    let app_handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let settings_db = app_handle.state::<SettingsDatabase>();
+        if let Ok(value) = settings_db.get_setting("close_to_tray").await {
+            CLOSE_TO_TRAY.store(value == "true", Ordering::Relaxed);
+        }
+    });
+
+    Ok(())
+}
+
+pub fn set_close_to_tray(enabled: bool) {
+    CLOSE_TO_TRAY.store(enabled, Ordering::Relaxed);
+}
+
+/// Hides the window instead of quitting so playback keeps going in the tray.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        if CLOSE_TO_TRAY.load(Ordering::Relaxed) {
+            api.prevent_close();
+            let _ = window.hide();
+        }
+    }
+}
+
+fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+#[tauri::command]
+pub fn minimize_to_tray(window: Window) -> Result<(), String> {
+    window.hide().map_err(|e| e.to_string())
+}
@@ -3,7 +3,7 @@ use tauri_plugin_dialog::{DialogExt, MessageDialogKind, MessageDialogButtons};
 use tauri_plugin_updater::UpdaterExt;
 
 pub async fn check_for_updates(app: AppHandle) {
-    println!("Checking for updates...");
+    log::info!("Checking for updates...");
 
     let updater = app.updater().unwrap();
     let response = updater.check().await;
@@ -40,7 +40,7 @@ pub async fn check_for_updates(app: AppHandle) {
                                     });
                             }
                             Err(e) => {
-                                println!("Error installing new update: {:?}", e);
+                                log::error!("Error installing new update: {:?}", e);
                                 app.dialog()
                                     .message("Failed to install new update. The new update can be downloaded from Github")
                                     .kind(MessageDialogKind::Error)
@@ -50,9 +50,9 @@ pub async fn check_for_updates(app: AppHandle) {
                     });
                 });
         }
-        Ok(None) => println!("No updates available."),
+        Ok(None) => log::info!("No updates available."),
         Err(e) => {
-            println!("Failed to check for updates: {:?}", e);
+            log::warn!("Failed to check for updates: {:?}", e);
         }
     }
 }
@@ -0,0 +1,18 @@
+use tauri::{AppHandle, Emitter};
+
+// The audio graph lives in the webview player, which computes the spectrum
+// from its analyser and emits `spectrum-data` while the visualizer is on.
+fn set_visualizer(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    app.emit("visualizer", enabled).map_err(|e| e.to_string())
+}
+
+/// Starts the `spectrum-data` stream of band magnitudes, about 30 per second.
+#[tauri::command]
+pub fn start_visualizer(app: AppHandle) -> Result<(), String> {
+    set_visualizer(&app, true)
+}
+
+#[tauri::command]
+pub fn stop_visualizer(app: AppHandle) -> Result<(), String> {
+    set_visualizer(&app, false)
+}
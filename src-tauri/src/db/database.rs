@@ -1,15 +1,36 @@
-use include_dir::{include_dir, Dir};
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use super::migrations::{self, schema_version};
+use super::{music::MusicDatabase, settings::SettingsDatabase};
+use crate::utils::error::AppError;
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
 use std::fs;
-use tauri::Manager;
-
-static MIGRATIONS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/db/migrations");
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+const DB_FILE: &str = "data.db";
+// A database waiting to replace `DB_FILE` on the next start, and where the
+// replaced one is kept.
+const RESTORE_FILE: &str = "data.db.restore";
+const REPLACED_FILE: &str = "data.db.bak";
+const REQUIRED_TABLES: [&str; 4] = ["schema_version", "settings", "songs", "playlists"];
+
+/// Payload of the `database-backup` and `database-restore` events.
+#[derive(Clone, Debug, Serialize)]
+pub struct DatabaseTransfer {
+    pub path: String,
+    pub schema_version: i64,
+    /// Set when a restore goes back to an older schema than the one in use.
+    pub downgrade: bool,
+}
 
 pub fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     let app_data_dir = app.path().app_data_dir().unwrap();
     fs::create_dir_all(&app_data_dir).expect("Failed to create app data directory");
 
-    let db_path = app_data_dir.join("data.db");
+    let db_path = app_data_dir.join(DB_FILE);
+    if let Err(e) = apply_staged_restore(&app_data_dir) {
+        log::error!("Failed to restore database: {}", e);
+    }
     let is_new_db = !db_path.exists();
     if is_new_db {
         fs::File::create(&db_path).expect("Failed to create database file");
@@ -28,13 +49,19 @@ pub fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     });
 
     app.manage(pool.clone());
+    app.manage(MusicDatabase { pool: pool.clone() });
+    app.manage(SettingsDatabase { pool: pool.clone() });
 
     tokio::task::block_in_place(|| {
         tauri::async_runtime::block_on(async {
-            apply_migrations(&pool).await?;
+            migrations::apply(&pool).await?;
+            let music_db = MusicDatabase { pool: pool.clone() };
+            if let Err(e) = music_db.backfill_added_at().await {
+                log::warn!("Failed to backfill when songs were added: {}", e);
+            }
             if is_new_db {
                 if let Err(e) = super::settings::initialize_settings(&pool).await {
-                    eprintln!("Failed to initialize settings: {}", e);
+                    log::error!("Failed to initialize settings: {}", e);
                 }
             }
             Ok::<(), Box<dyn std::error::Error>>(())
@@ -44,63 +71,149 @@ pub fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn apply_migrations(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS schema_version (
-            version INTEGER PRIMARY KEY,
-            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        );",
-    )
-    .execute(pool)
-    .await?;
-
-    let current_version: Option<i64> =
-        sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
-            .fetch_one(pool)
+/// Swaps in a database staged by `restore_database`. Runs before the pool is
+/// opened, so nothing is holding the old file.
+fn apply_staged_restore(app_data_dir: &Path) -> std::io::Result<()> {
+    let staged = app_data_dir.join(RESTORE_FILE);
+    if !staged.exists() {
+        return Ok(());
+    }
+
+    let db_path = app_data_dir.join(DB_FILE);
+    if db_path.exists() {
+        fs::rename(&db_path, app_data_dir.join(REPLACED_FILE))?;
+    }
+    // A leftover write-ahead log belongs to the old database and would be
+    // replayed on top of the restored one.
+    for suffix in ["-wal", "-shm"] {
+        let _ = fs::remove_file(app_data_dir.join(format!("{}{}", DB_FILE, suffix)));
+    }
+    fs::rename(&staged, &db_path)?;
+    log::info!("Restored database from backup");
+    Ok(())
+}
+
+fn data_dir(app: &AppHandle) -> Result<PathBuf, AppError> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| AppError::Io(e.to_string()))
+}
+
+/// Writes a consistent copy of the database to `dest` while the app keeps
+/// running. `VACUUM INTO` reads from a single transaction, so nothing written
+/// during the backup ends up half in it.
+#[tauri::command]
+pub async fn backup_database(
+    app: AppHandle,
+    pool: State<'_, SqlitePool>,
+    dest: String,
+) -> Result<DatabaseTransfer, AppError> {
+    // VACUUM INTO refuses to overwrite, and writing next to the destination
+    // first keeps an older backup intact if this one fails.
+    let partial = format!("{}.partial", dest);
+    let _ = fs::remove_file(&partial);
+    sqlx::query("VACUUM INTO ?")
+        .bind(&partial)
+        .execute(&*pool)
+        .await?;
+    fs::rename(&partial, &dest).map_err(|e| AppError::from(e).with_context(&dest))?;
+
+    let backup = DatabaseTransfer {
+        path: dest,
+        schema_version: schema_version(&pool).await?,
+        downgrade: false,
+    };
+    log::info!("Backed up database to {}", backup.path);
+    let _ = app.emit("database-backup", &backup);
+    Ok(backup)
+}
+
+/// Replaces the database with a backup made by `backup_database`. The file is
+/// checked first and then put in place on a restart, since every part of the
+/// app shares the open pool. Backups from older versions are migrated as
+/// usual on that start; ones from a newer version of the app are rejected.
+#[tauri::command]
+pub async fn restore_database(
+    app: AppHandle,
+    pool: State<'_, SqlitePool>,
+    src: String,
+) -> Result<DatabaseTransfer, AppError> {
+    let backup_version = validate_backup(&src)
+        .await
+        .map_err(|e| e.with_context(&src))?;
+    let latest = migrations::latest_version();
+    if backup_version > latest {
+        return Err(AppError::InvalidInput(format!(
+            "{} has schema version {}, this version of the app supports up to {}",
+            src, backup_version, latest
+        )));
+    }
+
+    let current_version = schema_version(&pool).await?;
+    let restore = DatabaseTransfer {
+        path: src,
+        schema_version: backup_version,
+        downgrade: backup_version < current_version,
+    };
+    if restore.downgrade {
+        log::warn!(
+            "Restoring {} goes back from schema version {} to {}; data added since then is lost",
+            restore.path,
+            current_version,
+            backup_version
+        );
+    }
+
+    let staged = data_dir(&app)?.join(RESTORE_FILE);
+    fs::copy(&restore.path, &staged).map_err(|e| AppError::from(e).with_context(&restore.path))?;
+    log::info!("Staged {} to be restored on restart", restore.path);
+    let _ = app.emit("database-restore", &restore);
+    app.request_restart();
+    Ok(restore)
+}
+
+/// Opens `path` read-only and checks it is an intact database of this app,
+/// returning its schema version.
+async fn validate_backup(path: &str) -> Result<i64, AppError> {
+    if !Path::new(path).is_file() {
+        return Err(AppError::FileNotFound(path.to_string()));
+    }
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .map_err(|e| AppError::InvalidInput(format!("Not a database: {}", e)))?;
+
+    let result: Result<i64, AppError> = async {
+        let check: String = sqlx::query_scalar("PRAGMA quick_check")
+            .fetch_one(&pool)
             .await?;
+        if check != "ok" {
+            return Err(AppError::InvalidInput(format!(
+                "Database is damaged: {}",
+                check
+            )));
+        }
 
-    let current_version = current_version.unwrap_or(0);
-
-    let mut migration_files: Vec<(i64, &str)> = MIGRATIONS_DIR
-        .files()
-        .filter_map(|file| {
-            let file_name = file.path().file_name()?.to_str()?;
-            if file_name.ends_with(".sql") && file_name.starts_with('v') {
-                let version: i64 = file_name
-                    .trim_start_matches('v')
-                    .trim_end_matches(".sql")
-                    .parse()
-                    .ok()?;
-                Some((version, file.contents_utf8()?))
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    migration_files.sort_by_key(|(version, _)| *version);
-
-    for (version, content) in migration_files {
-        if version > current_version {
-            let statements: Vec<&str> = content
-                .split(';')
-                .map(|s| s.trim())
-                .filter(|s| !s.is_empty())
-                .collect();
-
-            for statement in statements {
-                sqlx::query(statement)
-                    .execute(pool)
-                    .await
-                    .map_err(|e| format!("Failed to execute migration {}: {}", version, e))?;
+        for table in REQUIRED_TABLES {
+            let exists: Option<String> = sqlx::query_scalar(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?",
+            )
+            .bind(table)
+            .fetch_optional(&pool)
+            .await?;
+            if exists.is_none() {
+                return Err(AppError::InvalidInput(format!(
+                    "Not a backup of this app, the {} table is missing",
+                    table
+                )));
             }
-
-            sqlx::query("INSERT INTO schema_version (version) VALUES (?)")
-                .bind(version)
-                .execute(pool)
-                .await?;
         }
+        Ok(schema_version(&pool).await?)
     }
+    .await;
 
-    Ok(())
+    pool.close().await;
+    result
 }
\ No newline at end of file
@@ -0,0 +1,191 @@
+use crate::db::music::MusicDatabase;
+use crate::db::settings::{apply_settings, SettingsDatabase};
+use crate::db::types::Settings;
+use crate::utils::error::AppError;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::fs;
+use tauri::{AppHandle, State};
+
+/// Bumped whenever a change to the file format means older versions of the
+/// app can't read it anymore. Adding a setting doesn't count, missing ones
+/// keep their current value on import.
+const EXPORT_VERSION: u32 = 1;
+// Left out of exports and ignored on import: the Last.fm session is a
+// credential, and the rest is playback state (with full songs and covers)
+// that means nothing on another machine.
+const NOT_EXPORTED: [&str; 5] = [
+    "lastfm_session_key",
+    "current_song",
+    "queue",
+    "queue_pins",
+    "queue_snapshot",
+];
+
+#[derive(Serialize, Deserialize)]
+struct SettingsExport {
+    version: u32,
+    settings: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    playlists: Option<Vec<PlaylistExport>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PlaylistExport {
+    id: String,
+    name: String,
+    date_created: String,
+    song_ids: Vec<String>,
+}
+
+/// Writes every setting, and the playlists if `include_playlists` is set, to
+/// a JSON file that `import_settings` can read back on another machine.
+/// The Last.fm session and the queue aren't included. Playlists only keep
+/// the song ids; the songs themselves and playlist covers aren't included.
+#[tauri::command]
+pub async fn export_settings(
+    music_db: State<'_, MusicDatabase>,
+    settings_db: State<'_, SettingsDatabase>,
+    path: String,
+    include_playlists: bool,
+) -> Result<(), AppError> {
+    let settings = settings_db
+        .get_all()
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    let playlists = if include_playlists {
+        Some(export_playlists(&music_db).await?)
+    } else {
+        None
+    };
+
+    let mut settings = serde_json::to_value(settings).map_err(|e| AppError::Io(e.to_string()))?;
+    if let Some(fields) = settings.as_object_mut() {
+        for key in NOT_EXPORTED {
+            fields.remove(key);
+        }
+    }
+
+    let export = SettingsExport {
+        version: EXPORT_VERSION,
+        settings,
+        playlists,
+    };
+    let json = serde_json::to_string_pretty(&export).map_err(|e| AppError::Io(e.to_string()))?;
+    fs::write(&path, json).map_err(|e| AppError::from(e).with_context(&path))?;
+    log::info!("Exported settings to {}", path);
+    Ok(())
+}
+
+/// Applies a file written by `export_settings`. Settings go through their
+/// usual setters, so invalid values are rejected the same way and the ones
+/// with side effects (media keys, download limits, ...) take effect right
+/// away. Settings missing from the file, and the ones `export_settings`
+/// leaves out, are left as they are. Playlists are
+/// added next to the existing ones; entries for songs that aren't in the
+/// library are skipped.
+#[tauri::command]
+pub async fn import_settings(
+    app: AppHandle,
+    music_db: State<'_, MusicDatabase>,
+    settings_db: State<'_, SettingsDatabase>,
+    path: String,
+) -> Result<(), AppError> {
+    let json = fs::read_to_string(&path).map_err(|e| AppError::from(e).with_context(&path))?;
+    let export = parse_export(&json).map_err(|e| e.with_context(&path))?;
+
+    // Start from the current settings so an export from an older version,
+    // which doesn't know about newer settings, leaves those untouched.
+    let current = settings_db
+        .get_all()
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    let mut merged = serde_json::to_value(current).map_err(|e| AppError::Io(e.to_string()))?;
+    if let (Some(fields), serde_json::Value::Object(imported)) =
+        (merged.as_object_mut(), export.settings)
+    {
+        for (key, value) in imported {
+            if fields.contains_key(&key) && !NOT_EXPORTED.contains(&key.as_str()) {
+                fields.insert(key, value);
+            }
+        }
+    }
+    let settings: Settings = serde_json::from_value(merged)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid settings in {}: {}", path, e)))?;
+
+    if let Some(playlists) = export.playlists {
+        import_playlists(&music_db, playlists).await?;
+    }
+    apply_settings(app, settings_db, settings, &NOT_EXPORTED)
+        .await
+        .map_err(AppError::InvalidInput)?;
+    log::info!("Imported settings from {}", path);
+    Ok(())
+}
+
+fn parse_export(json: &str) -> Result<SettingsExport, AppError> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| AppError::InvalidInput(format!("Not a settings export: {}", e)))?;
+    let version = value
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| AppError::InvalidInput("Not a settings export: no version".into()))?;
+    if version == 0 || version > EXPORT_VERSION as u64 {
+        return Err(AppError::InvalidInput(format!(
+            "Settings export has format version {}, this version of the app reads up to {}",
+            version, EXPORT_VERSION
+        )));
+    }
+    serde_json::from_value(value)
+        .map_err(|e| AppError::InvalidInput(format!("Not a settings export: {}", e)))
+}
+
+async fn export_playlists(music_db: &MusicDatabase) -> Result<Vec<PlaylistExport>, AppError> {
+    let rows = sqlx::query("SELECT id, name, date_created FROM playlists")
+        .fetch_all(&music_db.pool)
+        .await?;
+
+    let mut playlists = Vec::new();
+    for row in rows {
+        let id: String = row.get("id");
+        let song_ids =
+            sqlx::query_scalar("SELECT song_id FROM playlist_songs WHERE playlist_id = ?")
+                .bind(&id)
+                .fetch_all(&music_db.pool)
+                .await?;
+        playlists.push(PlaylistExport {
+            id,
+            name: row.get("name"),
+            date_created: row.get("date_created"),
+            song_ids,
+        });
+    }
+    Ok(playlists)
+}
+
+async fn import_playlists(
+    music_db: &MusicDatabase,
+    playlists: Vec<PlaylistExport>,
+) -> Result<(), AppError> {
+    let mut tx = music_db.pool.begin().await?;
+    for playlist in playlists {
+        sqlx::query("INSERT OR IGNORE INTO playlists (id, name, date_created) VALUES (?, ?, ?)")
+            .bind(&playlist.id)
+            .bind(&playlist.name)
+            .bind(&playlist.date_created)
+            .execute(&mut *tx)
+            .await?;
+        for song_id in &playlist.song_ids {
+            sqlx::query(
+                "INSERT OR IGNORE INTO playlist_songs (playlist_id, song_id)
+                 SELECT ?, id FROM songs WHERE id = ?",
+            )
+            .bind(&playlist.id)
+            .bind(song_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(())
+}
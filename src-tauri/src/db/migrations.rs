@@ -0,0 +1,95 @@
+use include_dir::{include_dir, Dir};
+use sqlx::sqlite::SqlitePool;
+
+static MIGRATIONS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/db/migrations");
+
+/// Brings the schema up to date. Migrations are the `v{N}.sql` files in
+/// `src/db/migrations`, applied in order of `N`; the applied ones are recorded
+/// in `schema_version`. To change the schema, add the next file rather than
+/// editing one that has already shipped.
+///
+/// Each migration runs in its own transaction together with its
+/// `schema_version` row, so one that fails halfway leaves the database as it
+/// was and is tried again on the next start.
+pub async fn apply(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );",
+    )
+    .execute(pool)
+    .await?;
+
+    let current_version = schema_version(pool).await?;
+    if current_version > latest_version() {
+        log::warn!(
+            "Database has schema version {}, newer than the {} this version knows",
+            current_version,
+            latest_version()
+        );
+    }
+
+    for (version, content) in migrations() {
+        if version <= current_version {
+            continue;
+        }
+
+        let mut tx = pool.begin().await?;
+        let statements = content
+            .split(';')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty());
+        for statement in statements {
+            sqlx::query(statement)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to execute migration {}: {}", version, e))?;
+        }
+
+        sqlx::query("INSERT INTO schema_version (version) VALUES (?)")
+            .bind(version)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        log::info!("Applied database migration {}", version);
+    }
+
+    Ok(())
+}
+
+/// The version the schema has once every migration is applied.
+pub fn latest_version() -> i64 {
+    migrations().last().map_or(0, |(version, _)| *version)
+}
+
+/// The newest migration applied to the database behind `pool`.
+pub async fn schema_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(version.unwrap_or(0))
+}
+
+/// Every migration in the order they are applied.
+fn migrations() -> Vec<(i64, &'static str)> {
+    let mut migration_files: Vec<(i64, &str)> = MIGRATIONS_DIR
+        .files()
+        .filter_map(|file| {
+            let file_name = file.path().file_name()?.to_str()?;
+            if file_name.ends_with(".sql") && file_name.starts_with('v') {
+                let version: i64 = file_name
+                    .trim_start_matches('v')
+                    .trim_end_matches(".sql")
+                    .parse()
+                    .ok()?;
+                Some((version, file.contents_utf8()?))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    migration_files.sort_by_key(|(version, _)| *version);
+    migration_files
+}
@@ -0,0 +1,5 @@
+ALTER TABLE songs ADD COLUMN added_at INTEGER;
+
+UPDATE songs SET added_at = CAST(strftime('%s', date_added) AS INTEGER) * 1000;
+
+CREATE INDEX IF NOT EXISTS idx_songs_added_at ON songs(added_at);
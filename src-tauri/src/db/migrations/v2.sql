@@ -0,0 +1,3 @@
+ALTER TABLE songs ADD COLUMN genre TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_songs_genre ON songs(genre);
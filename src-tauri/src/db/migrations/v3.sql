@@ -0,0 +1,3 @@
+ALTER TABLE songs ADD COLUMN bpm REAL;
+
+CREATE INDEX IF NOT EXISTS idx_songs_bpm ON songs(bpm);
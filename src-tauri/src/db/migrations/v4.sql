@@ -0,0 +1,9 @@
+ALTER TABLE songs ADD COLUMN play_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE songs ADD COLUMN last_played TEXT;
+
+UPDATE songs SET
+    play_count = (SELECT COUNT(*) FROM history h WHERE h.song_id = songs.id),
+    last_played = (SELECT MAX(h.date_played) FROM history h WHERE h.song_id = songs.id);
+
+CREATE INDEX IF NOT EXISTS idx_songs_play_count ON songs(play_count);
+CREATE INDEX IF NOT EXISTS idx_songs_last_played ON songs(last_played);
@@ -0,0 +1,3 @@
+ALTER TABLE songs ADD COLUMN liked INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_songs_liked ON songs(liked);
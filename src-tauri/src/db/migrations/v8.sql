@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS scrobble_queue (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    artist TEXT NOT NULL,
+    track TEXT NOT NULL,
+    album TEXT NOT NULL DEFAULT '',
+    duration INTEGER NOT NULL DEFAULT 0,
+    timestamp INTEGER NOT NULL
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS eq_presets (
+    name TEXT PRIMARY KEY,
+    gains TEXT NOT NULL,
+    builtin INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT OR IGNORE INTO eq_presets (name, gains, builtin) VALUES
+    ('Flat', '{"32":"0.0","64":"0.0","125":"0.0","250":"0.0","500":"0.0","1000":"0.0","2000":"0.0","4000":"0.0","8000":"0.0","16000":"0.0"}', 1),
+    ('Bass Boost', '{"32":"6.0","64":"5.0","125":"4.0","250":"2.0","500":"0.0","1000":"0.0","2000":"0.0","4000":"0.0","8000":"0.0","16000":"0.0"}', 1),
+    ('Treble Boost', '{"32":"0.0","64":"0.0","125":"0.0","250":"0.0","500":"0.0","1000":"1.0","2000":"2.0","4000":"4.0","8000":"5.0","16000":"6.0"}', 1),
+    ('Vocal', '{"32":"-2.0","64":"-2.0","125":"-1.0","250":"1.0","500":"3.0","1000":"3.0","2000":"2.0","4000":"1.0","8000":"0.0","16000":"-1.0"}', 1),
+    ('Rock', '{"32":"4.0","64":"3.0","125":"2.0","250":"0.0","500":"-1.0","1000":"-1.0","2000":"0.0","4000":"2.0","8000":"3.0","16000":"4.0"}', 1),
+    ('Pop', '{"32":"-1.0","64":"0.0","125":"1.0","250":"2.0","500":"3.0","1000":"2.0","2000":"1.0","4000":"0.0","8000":"-1.0","16000":"-1.0"}', 1),
+    ('Electronic', '{"32":"4.0","64":"3.0","125":"1.0","250":"0.0","500":"-1.0","1000":"1.0","2000":"0.0","4000":"1.0","8000":"3.0","16000":"4.0"}', 1);
\ No newline at end of file
@@ -1,4 +1,6 @@
 pub mod music;
 pub mod settings;
 pub mod database;
+pub mod export;
+pub mod migrations;
 pub mod types;
\ No newline at end of file
@@ -1,12 +1,42 @@
-use crate::db::types::{Album, History, Playlist, Song};
+use crate::api::commands::{find_song_file, get_music_path, AUDIO_EXTENSIONS};
+use crate::db::settings::{parse_queue, SettingsDatabase};
+use crate::db::types::{
+    Album, AlbumSummary, ArtistCount, BrokenPlaylist, GenreCount, History, ImageSource,
+    LibraryRebuildReport, ListeningStats, M3uExportReport, M3uImportReport, Playlist,
+    QueueSnapshot, RestoredQueue, Song, SongFilter, SongPage, SongPlays, SongSort, StatsPeriod,
+};
+use crate::utils::commands::{import_file, path_id};
+use crate::utils::error::AppError;
+use crate::utils::metadata::{self, split_genres, TrackMetadata};
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use base64::Engine;
-use chrono::Utc;
+use chrono::{DateTime, Datelike, Days, Local, NaiveTime, TimeZone, Utc};
+use futures_util::TryStreamExt;
+use sqlx::sqlite::SqliteRow;
 use sqlx::Row;
 use sqlx::SqlitePool;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use tauri::State;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+const MAX_QUEUE_FILTERED: usize = 5000;
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+const MAX_SEARCH_LIMIT: usize = 500;
+const DEFAULT_PAGE_SIZE: usize = 100;
+const DEFAULT_PLAYED_LIMIT: usize = 50;
+const DEFAULT_HISTORY_LIMIT: usize = 5;
+const MAX_PAGE_SIZE: usize = 1000;
+// Songs without an artist or album tag are grouped under these names.
+const ARTIST_NAME: &str = "COALESCE(NULLIF(TRIM(s.artist), ''), 'Unknown Artist')";
+const ALBUM_NAME: &str = "COALESCE(NULLIF(TRIM(s.album), ''), 'Unknown Album')";
+const TOP_STATS_LIMIT: i64 = 10;
+// `date_played` is stored as UTC RFC 3339, so comparing the strings orders
+// plays the same as comparing the moments.
+const HISTORY_IN_RANGE: &str = "FROM history h JOIN songs s ON s.id = h.song_id
+     WHERE h.date_played >= ? AND h.date_played < ?";
+const SONG_COLUMNS: &str = "s.id, s.title, s.artist, s.album, s.cover, s.date_added, s.duration, \
+     s.genre, s.bpm, s.play_count, s.last_played, s.liked, s.path";
 
 pub struct MusicDatabase {
     pub pool: SqlitePool,
@@ -14,9 +44,7 @@ pub struct MusicDatabase {
 
 impl MusicDatabase {
     fn get_song_cover(&self, id: &str) -> String {
-        let cover_path = Path::new("Vleer")
-            .join("Covers")
-            .join(format!("{}.png", id));
+        let cover_path = get_music_path().join("Covers").join(format!("{}.png", id));
 
         if cover_path.exists() {
             fs::read(cover_path)
@@ -27,30 +55,200 @@ impl MusicDatabase {
             String::new()
         }
     }
+
+    /// Fills in `added_at` (milliseconds since the epoch) for songs the
+    /// migration couldn't date from `date_added`, from the modification time
+    /// of their file.
+    pub(crate) async fn backfill_added_at(&self) -> Result<(), sqlx::Error> {
+        let ids: Vec<String> = sqlx::query_scalar("SELECT id FROM songs WHERE added_at IS NULL")
+            .fetch_all(&self.pool)
+            .await?;
+        for id in ids {
+            let added_at = self
+                .song_file(&id)
+                .await
+                .ok()
+                .and_then(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_else(Utc::now);
+            sqlx::query("UPDATE songs SET added_at = ? WHERE id = ?")
+                .bind(added_at.timestamp_millis())
+                .bind(&id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The audio file of a song: where it was indexed for songs that weren't
+    /// copied into the library, otherwise the file in `Songs/`.
+    pub(crate) async fn song_file(&self, id: &str) -> Result<PathBuf, AppError> {
+        let path: Option<String> = sqlx::query_scalar("SELECT path FROM songs WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+        match path {
+            Some(path) if Path::new(&path).is_file() => Ok(PathBuf::from(path)),
+            Some(path) => Err(AppError::FileNotFound(path)),
+            None => find_song_file(id),
+        }
+    }
+
+    /// The songs with the given ids that are in the library, by id.
+    pub(crate) async fn find_songs(
+        &self,
+        ids: &[String],
+    ) -> Result<HashMap<String, Song>, AppError> {
+        let mut found = HashMap::new();
+        if ids.is_empty() {
+            return Ok(found);
+        }
+
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let sql = format!(
+            "SELECT {} FROM songs s WHERE s.id IN ({})",
+            SONG_COLUMNS, placeholders
+        );
+        let mut query = sqlx::query(&sql);
+        for id in ids {
+            query = query.bind(id);
+        }
+        for row in query.fetch_all(&self.pool).await? {
+            let song = self.song_from_row(&row)?;
+            found.insert(song.id.clone(), song);
+        }
+        Ok(found)
+    }
+
+    /// Adds a song read from a local file unless one with the same id exists.
+    /// `path` is where the file was indexed, `None` when it is in `Songs/`.
+    /// Returns whether it was inserted.
+    pub(crate) async fn insert_imported_song(
+        &self,
+        id: &str,
+        path: Option<&Path>,
+        metadata: TrackMetadata,
+        date_added: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let artist = metadata.artist.unwrap_or_default();
+        let album = metadata.album.unwrap_or_default();
+
+        // Written before the song is added, so one whose cover couldn't be
+        // saved isn't left in the library to be skipped by the next scan.
+        if let Some(cover) = &metadata.cover {
+            let cover_path = get_music_path().join("Covers").join(format!("{}.png", id));
+            if !cover_path.exists() {
+                fs::write(&cover_path, cover).map_err(|e| {
+                    AppError::from(e).with_context(&cover_path.display().to_string())
+                })?;
+            }
+        }
+
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO songs (id, title, artist, album, cover, date_added, added_at, duration, genre, path) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id)
+        .bind(metadata.title.unwrap_or_else(|| id.to_string()))
+        .bind(&artist)
+        .bind(&album)
+        .bind("")
+        .bind(date_added.to_rfc3339())
+        .bind(date_added.timestamp_millis())
+        .bind(metadata.duration)
+        .bind(metadata.genre)
+        .bind(path.map(|path| path.to_string_lossy().into_owned()))
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        if let Some(cover) = metadata.cover {
+            // Fill in the art of a matching album that doesn't have any yet.
+            let album_id: Option<String> = sqlx::query_scalar(
+                "SELECT id FROM albums WHERE name = ? AND artist = ? AND album_art IS NULL",
+            )
+            .bind(&album)
+            .bind(&artist)
+            .fetch_optional(&self.pool)
+            .await?;
+            if let Some(album_id) = album_id {
+                if let Err(e) = self.store_album_art(&album_id, &cover).await {
+                    log::warn!("Failed to store the art of album {}: {}", album_id, e);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Writes the image to `Covers/albums/` and points the album at it.
+    async fn store_album_art(&self, album_id: &str, data: &[u8]) -> Result<(), AppError> {
+        let art_dir = get_music_path().join("Covers").join("albums");
+        fs::create_dir_all(&art_dir)?;
+        let art_path = art_dir.join(format!("{}.png", album_id));
+        fs::write(&art_path, data)?;
+
+        sqlx::query("UPDATE albums SET album_art = ? WHERE id = ?")
+            .bind(art_path.to_string_lossy().into_owned())
+            .bind(album_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    fn playlist_cover(&self, row: &SqliteRow) -> Option<String> {
+        row.get::<Option<String>, _>("cover")
+            .and_then(|path| fs::read(path).ok())
+            .map(|data| BASE64_STANDARD.encode(data))
+    }
+
+    fn song_from_row(&self, row: &SqliteRow) -> Result<Song, AppError> {
+        let mut song = Song {
+            id: row.get("id"),
+            title: row.get("title"),
+            artist: row.get("artist"),
+            album: row.get("album"),
+            cover: row.get("cover"),
+            date_added: parse_date(row, "date_added")?,
+            duration: row.get("duration"),
+            genre: row.get("genre"),
+            bpm: row.get("bpm"),
+            play_count: row.get("play_count"),
+            last_played: row
+                .get::<Option<String>, _>("last_played")
+                .and_then(|date| date.parse().ok()),
+            liked: row.get("liked"),
+            path: row.get("path"),
+            pinned: false,
+        };
+        song.cover = self.get_song_cover(&song.id);
+        Ok(song)
+    }
 }
 
 #[tauri::command]
 pub async fn add_playlist(
     music_db: State<'_, MusicDatabase>,
     playlist: Playlist,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("INSERT INTO playlists (id, name, date_created) VALUES (?, ?, ?)")
         .bind(playlist.id)
         .bind(playlist.name)
         .bind(playlist.date_created.to_rfc3339())
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn add_song(music_db: State<'_, MusicDatabase>, song: Song) -> Result<(), String> {
+pub async fn add_song(music_db: State<'_, MusicDatabase>, song: Song) -> Result<(), AppError> {
     let song_id = song.id.clone();
     let cover_data = song.cover.clone();
 
     sqlx::query(
-        "INSERT INTO songs (id, title, artist, album, cover, date_added, duration) VALUES (?, ?, ?, ?, ?, ?, ?)"
+        "INSERT INTO songs (id, title, artist, album, cover, date_added, added_at, duration, genre) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
     )
     .bind(&song_id)
     .bind(&song.title)
@@ -58,15 +256,16 @@ pub async fn add_song(music_db: State<'_, MusicDatabase>, song: Song) -> Result<
     .bind(&song.album)
     .bind(&cover_data)
     .bind(song.date_added.to_rfc3339())
+    .bind(song.date_added.timestamp_millis())
     .bind(song.duration)
+    .bind(&song.genre)
     .execute(&music_db.pool)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
-    let cover_path = Path::new("Vleer")
+    let cover_path = get_music_path()
         .join("Covers")
         .join(format!("{}.png", song_id));
-    fs::write(cover_path, cover_data).map_err(|e| e.to_string())?;
+    fs::write(cover_path, cover_data)?;
 
     Ok(())
 }
@@ -75,7 +274,7 @@ pub async fn add_song(music_db: State<'_, MusicDatabase>, song: Song) -> Result<
 pub async fn add_song_to_history(
     music_db: State<'_, MusicDatabase>,
     song: Song,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let history = History {
         id: Utc::now().timestamp_millis().to_string(),
         date_played: Utc::now(),
@@ -86,8 +285,7 @@ pub async fn add_song_to_history(
         .bind(history.date_played.to_rfc3339())
         .bind(history.song.id)
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
@@ -96,67 +294,326 @@ pub async fn add_song_to_playlist(
     music_db: State<'_, MusicDatabase>,
     playlist_id: String,
     song: Song,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("INSERT INTO playlist_songs (playlist_id, song_id) VALUES (?, ?)")
         .bind(playlist_id)
         .bind(song.id)
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn clear_history(music_db: State<'_, MusicDatabase>) -> Result<(), String> {
+pub async fn add_song_to_playlists(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+    playlist_ids: Vec<String>,
+) -> Result<HashMap<String, u64>, AppError> {
+    let mut tx = music_db.pool.begin().await?;
+
+    let mut added = HashMap::new();
+    for playlist_id in playlist_ids {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO playlist_songs (playlist_id, song_id) VALUES (?, ?)",
+        )
+        .bind(&playlist_id)
+        .bind(&song_id)
+        .execute(&mut *tx)
+        .await?;
+        *added.entry(playlist_id).or_insert(0) += result.rows_affected();
+    }
+
+    tx.commit().await?;
+    Ok(added)
+}
+
+#[tauri::command]
+pub async fn add_songs_to_playlist(
+    music_db: State<'_, MusicDatabase>,
+    playlist_id: String,
+    song_ids: Vec<String>,
+) -> Result<u64, AppError> {
+    let mut tx = music_db.pool.begin().await?;
+
+    let mut added = 0;
+    for song_id in song_ids {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO playlist_songs (playlist_id, song_id) VALUES (?, ?)",
+        )
+        .bind(&playlist_id)
+        .bind(&song_id)
+        .execute(&mut *tx)
+        .await?;
+        added += result.rows_affected();
+    }
+
+    tx.commit().await?;
+    Ok(added)
+}
+
+/// Flips the liked flag of a song and returns the new state.
+#[tauri::command]
+pub async fn toggle_like(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+) -> Result<bool, AppError> {
+    let liked: Option<bool> =
+        sqlx::query_scalar("UPDATE songs SET liked = NOT liked WHERE id = ? RETURNING liked")
+            .bind(&song_id)
+            .fetch_optional(&music_db.pool)
+            .await?;
+    liked.ok_or_else(|| AppError::NotFound(format!("Song {} is not in the library", song_id)))
+}
+
+#[tauri::command]
+pub async fn get_liked_songs(music_db: State<'_, MusicDatabase>) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s WHERE s.liked ORDER BY s.title COLLATE NOCASE, s.id",
+        SONG_COLUMNS
+    ))
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
+    }
+
+    Ok(songs)
+}
+
+/// Counts a play of the song and records when it happened.
+#[tauri::command]
+pub async fn increment_play_count(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+) -> Result<(), AppError> {
+    sqlx::query("UPDATE songs SET play_count = play_count + 1, last_played = ? WHERE id = ?")
+        .bind(Utc::now().to_rfc3339())
+        .bind(song_id)
+        .execute(&music_db.pool)
+        .await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_most_played(
+    music_db: State<'_, MusicDatabase>,
+    limit: Option<usize>,
+) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s WHERE s.play_count > 0
+         ORDER BY s.play_count DESC, s.last_played DESC, s.id LIMIT ?",
+        SONG_COLUMNS
+    ))
+    .bind(limit.unwrap_or(DEFAULT_PLAYED_LIMIT).min(MAX_PAGE_SIZE) as i64)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
+    }
+
+    Ok(songs)
+}
+
+#[tauri::command]
+pub async fn get_recently_played(
+    music_db: State<'_, MusicDatabase>,
+    limit: Option<usize>,
+) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s WHERE s.last_played IS NOT NULL
+         ORDER BY s.last_played DESC, s.id LIMIT ?",
+        SONG_COLUMNS
+    ))
+    .bind(limit.unwrap_or(DEFAULT_PLAYED_LIMIT).min(MAX_PAGE_SIZE) as i64)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
+    }
+
+    Ok(songs)
+}
+
+/// The most recently added songs, newest first.
+#[tauri::command]
+pub async fn get_recently_added(
+    music_db: State<'_, MusicDatabase>,
+    limit: Option<usize>,
+) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s ORDER BY s.added_at DESC, s.id LIMIT ?",
+        SONG_COLUMNS
+    ))
+    .bind(limit.unwrap_or(DEFAULT_PLAYED_LIMIT).min(MAX_PAGE_SIZE) as i64)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
+    }
+
+    Ok(songs)
+}
+
+#[tauri::command]
+pub async fn clear_history(music_db: State<'_, MusicDatabase>) -> Result<(), AppError> {
     sqlx::query("DELETE FROM history")
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
+/// The most recently played songs, each once with its latest play, so
+/// replaying a song doesn't push everything else out.
 #[tauri::command]
-pub async fn get_history(music_db: State<'_, MusicDatabase>) -> Result<Vec<History>, String> {
+pub async fn get_history(
+    music_db: State<'_, MusicDatabase>,
+    limit: Option<usize>,
+) -> Result<Vec<History>, AppError> {
+    // SQLite takes the other columns from the row MAX() picked.
     let rows = sqlx::query(
-        "SELECT id, date_played, song_id FROM history ORDER BY date_played DESC LIMIT 5",
+        "SELECT id, MAX(date_played) AS date_played, song_id FROM history
+         GROUP BY song_id
+         ORDER BY date_played DESC LIMIT ?",
     )
+    .bind(limit.unwrap_or(DEFAULT_HISTORY_LIMIT).min(MAX_PAGE_SIZE) as i64)
     .fetch_all(&music_db.pool)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
-    let mut history = Vec::new();
-    for row in rows {
-        let song = get_song(music_db.clone(), row.get("song_id"))
-            .await?
-            .unwrap();
-        history.push(History {
-            id: row.get("id"),
-            date_played: row.get::<String, _>("date_played").parse().unwrap(),
-            song,
+    history_from_rows(music_db, rows).await
+}
+
+/// Every play, newest first, including repeats of the same song.
+#[tauri::command]
+pub async fn get_full_history(
+    music_db: State<'_, MusicDatabase>,
+    limit: Option<usize>,
+) -> Result<Vec<History>, AppError> {
+    let rows = sqlx::query(
+        "SELECT id, date_played, song_id FROM history ORDER BY date_played DESC LIMIT ?",
+    )
+    .bind(limit.map_or(-1, |limit| limit as i64))
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    history_from_rows(music_db, rows).await
+}
+
+/// Plays from `from_ms` up to, but not including, `to_ms` (milliseconds since
+/// the epoch), newest first.
+#[tauri::command]
+pub async fn get_history_range(
+    music_db: State<'_, MusicDatabase>,
+    from_ms: i64,
+    to_ms: i64,
+) -> Result<Vec<History>, AppError> {
+    let rows = sqlx::query(
+        "SELECT id, date_played, song_id FROM history
+         WHERE date_played >= ? AND date_played < ?
+         ORDER BY date_played DESC",
+    )
+    .bind(millis_to_date(from_ms)?.to_rfc3339())
+    .bind(millis_to_date(to_ms)?.to_rfc3339())
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    history_from_rows(music_db, rows).await
+}
+
+/// Listening time, top artists and top songs of the current day, week or
+/// month, counting every play with the full length of the song.
+#[tauri::command]
+pub async fn get_listening_stats(
+    music_db: State<'_, MusicDatabase>,
+    period: StatsPeriod,
+) -> Result<ListeningStats, AppError> {
+    let now = Local::now();
+    let from = period_start(period, now);
+    let to = now.with_timezone(&Utc);
+    let range = (from.to_rfc3339(), to.to_rfc3339());
+
+    let totals = sqlx::query(&format!(
+        "SELECT COUNT(*) AS plays, COALESCE(SUM(s.duration), 0) AS seconds {}",
+        HISTORY_IN_RANGE
+    ))
+    .bind(&range.0)
+    .bind(&range.1)
+    .fetch_one(&music_db.pool)
+    .await?;
+
+    let artists = sqlx::query(&format!(
+        "SELECT {0} AS artist, COUNT(*) AS count {1}
+         GROUP BY {0} COLLATE NOCASE
+         ORDER BY count DESC, artist LIMIT ?",
+        ARTIST_NAME, HISTORY_IN_RANGE
+    ))
+    .bind(&range.0)
+    .bind(&range.1)
+    .bind(TOP_STATS_LIMIT)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let songs = sqlx::query(&format!(
+        "SELECT {}, COUNT(*) AS plays {}
+         GROUP BY s.id
+         ORDER BY plays DESC, s.title LIMIT ?",
+        SONG_COLUMNS, HISTORY_IN_RANGE
+    ))
+    .bind(&range.0)
+    .bind(&range.1)
+    .bind(TOP_STATS_LIMIT)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut top_songs = Vec::new();
+    for row in songs {
+        top_songs.push(SongPlays {
+            song: music_db.song_from_row(&row)?,
+            plays: row.get("plays"),
         });
     }
 
-    Ok(history)
+    Ok(ListeningStats {
+        from,
+        to,
+        plays: totals.get("plays"),
+        total_seconds: totals.get("seconds"),
+        top_artists: artists
+            .iter()
+            .map(|row| ArtistCount {
+                artist: row.get("artist"),
+                count: row.get("count"),
+            })
+            .collect(),
+        top_songs,
+    })
 }
 
 #[tauri::command]
 pub async fn get_playlist(
     music_db: State<'_, MusicDatabase>,
     id: String,
-) -> Result<Option<Playlist>, String> {
-    let row = sqlx::query("SELECT id, name, date_created FROM playlists WHERE id = ?")
+) -> Result<Option<Playlist>, AppError> {
+    let row = sqlx::query("SELECT id, name, date_created, cover FROM playlists WHERE id = ?")
         .bind(id)
         .fetch_optional(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     if let Some(row) = row {
         let songs = get_songs_in_playlist(music_db.clone(), row.get("id")).await?;
         Ok(Some(Playlist {
             id: row.get("id"),
             name: row.get("name"),
-            date_created: row.get::<String, _>("date_created").parse().unwrap(),
+            date_created: parse_date(&row, "date_created")?,
             songs,
+            cover: music_db.playlist_cover(&row),
         }))
     } else {
         Ok(None)
@@ -164,11 +621,10 @@ pub async fn get_playlist(
 }
 
 #[tauri::command]
-pub async fn get_playlists(music_db: State<'_, MusicDatabase>) -> Result<Vec<Playlist>, String> {
-    let rows = sqlx::query("SELECT id, name, date_created FROM playlists")
+pub async fn get_playlists(music_db: State<'_, MusicDatabase>) -> Result<Vec<Playlist>, AppError> {
+    let rows = sqlx::query("SELECT id, name, date_created, cover FROM playlists")
         .fetch_all(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     let mut playlists = Vec::new();
     for row in rows {
@@ -176,66 +632,195 @@ pub async fn get_playlists(music_db: State<'_, MusicDatabase>) -> Result<Vec<Pla
         playlists.push(Playlist {
             id: row.get("id"),
             name: row.get("name"),
-            date_created: row.get::<String, _>("date_created").parse().unwrap(),
+            date_created: parse_date(&row, "date_created")?,
             songs,
+            cover: music_db.playlist_cover(&row),
         });
     }
 
     Ok(playlists)
 }
 
+#[tauri::command]
+pub async fn rename_playlist(
+    music_db: State<'_, MusicDatabase>,
+    playlist_id: String,
+    name: String,
+) -> Result<(), AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Playlist name cannot be empty".to_string(),
+        ));
+    }
+
+    let result = sqlx::query("UPDATE playlists SET name = ? WHERE id = ?")
+        .bind(name)
+        .bind(&playlist_id)
+        .execute(&music_db.pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "Playlist {} does not exist",
+            playlist_id
+        )));
+    }
+    Ok(())
+}
+
+/// Copies the image into the app data folder and makes it the playlist's
+/// cover, replacing the previous one.
+#[tauri::command]
+pub async fn set_playlist_cover(
+    app: AppHandle,
+    music_db: State<'_, MusicDatabase>,
+    playlist_id: String,
+    image: ImageSource,
+) -> Result<(), AppError> {
+    let data = match image {
+        ImageSource::Path(path) => {
+            fs::read(&path).map_err(|e| AppError::from(e).with_context(&path))?
+        }
+        ImageSource::Bytes(bytes) => bytes,
+    };
+    if data.is_empty() {
+        return Err(AppError::InvalidInput("Cover image is empty".to_string()));
+    }
+
+    let covers_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Io(e.to_string()))?
+        .join("playlist_covers");
+    fs::create_dir_all(&covers_dir)?;
+    let cover_path = covers_dir.join(&playlist_id);
+
+    let result = sqlx::query("UPDATE playlists SET cover = ? WHERE id = ?")
+        .bind(cover_path.to_string_lossy().into_owned())
+        .bind(&playlist_id)
+        .execute(&music_db.pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "Playlist {} does not exist",
+            playlist_id
+        )));
+    }
+
+    fs::write(&cover_path, data)?;
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn get_song(
     music_db: State<'_, MusicDatabase>,
     id: String,
-) -> Result<Option<Song>, String> {
-    let row = sqlx::query(
-        "SELECT id, title, artist, album, cover, date_added, duration FROM songs WHERE id = ?",
-    )
+) -> Result<Option<Song>, AppError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM songs s WHERE s.id = ?",
+        SONG_COLUMNS
+    ))
     .bind(id)
     .fetch_optional(&music_db.pool)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     if let Some(row) = row {
-        let mut song = Song {
-            id: row.get("id"),
-            title: row.get("title"),
-            artist: row.get("artist"),
-            album: row.get("album"),
-            cover: row.get("cover"),
-            date_added: row.get::<String, _>("date_added").parse().unwrap(),
-            duration: row.get("duration"),
-        };
-        song.cover = music_db.get_song_cover(&song.id);
-        Ok(Some(song))
+        Ok(Some(music_db.song_from_row(&row)?))
     } else {
         Ok(None)
     }
 }
 
 #[tauri::command]
-pub async fn get_songs(music_db: State<'_, MusicDatabase>) -> Result<Vec<Song>, String> {
-    let rows = sqlx::query(
-        "SELECT id, title, artist, album, cover, date_added, duration FROM songs ORDER BY title",
-    )
+pub async fn get_songs(music_db: State<'_, MusicDatabase>) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s ORDER BY s.title",
+        SONG_COLUMNS
+    ))
     .fetch_all(&music_db.pool)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     let mut songs = Vec::new();
     for row in rows {
-        let mut song = Song {
-            id: row.get("id"),
-            title: row.get("title"),
-            artist: row.get("artist"),
-            album: row.get("album"),
-            cover: row.get("cover"),
-            date_added: row.get::<String, _>("date_added").parse().unwrap(),
-            duration: row.get("duration"),
-        };
-        song.cover = music_db.get_song_cover(&song.id);
-        songs.push(song);
+        songs.push(music_db.song_from_row(&row)?);
+    }
+
+    Ok(songs)
+}
+
+/// Returns one page of the library together with the total number of songs.
+#[tauri::command]
+pub async fn get_songs_paged(
+    music_db: State<'_, MusicDatabase>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    sort_by: Option<SongSort>,
+) -> Result<SongPage, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM songs")
+        .fetch_one(&music_db.pool)
+        .await?;
+
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s ORDER BY {} LIMIT ? OFFSET ?",
+        SONG_COLUMNS,
+        sort_by.unwrap_or_default().order_by()
+    ))
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
+    }
+
+    Ok(SongPage { songs, total })
+}
+
+/// Case-insensitive search over title, artist and album. Exact title matches
+/// come first, then titles starting with the query, then everything else.
+#[tauri::command]
+pub async fn search_songs(
+    music_db: State<'_, MusicDatabase>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<Song>, AppError> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).min(MAX_SEARCH_LIMIT);
+    let escaped = escape_like(query);
+
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s
+         WHERE s.title LIKE ?1 ESCAPE '\\'
+            OR s.artist LIKE ?1 ESCAPE '\\'
+            OR s.album LIKE ?1 ESCAPE '\\'
+         ORDER BY
+            CASE
+                WHEN s.title = ?2 COLLATE NOCASE THEN 0
+                WHEN s.title LIKE ?3 ESCAPE '\\' THEN 1
+                ELSE 2
+            END,
+            s.title COLLATE NOCASE, s.id
+         LIMIT ?4",
+        SONG_COLUMNS
+    ))
+    .bind(format!("%{}%", escaped))
+    .bind(query)
+    .bind(format!("{}%", escaped))
+    .bind(limit as i64)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
     }
 
     Ok(songs)
@@ -245,12 +830,11 @@ pub async fn get_songs(music_db: State<'_, MusicDatabase>) -> Result<Vec<Song>,
 pub async fn remove_song(
     music_db: State<'_, MusicDatabase>,
     song_id: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("DELETE FROM songs WHERE id = ?")
         .bind(song_id)
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
@@ -258,12 +842,11 @@ pub async fn remove_song(
 pub async fn remove_song_from_history(
     music_db: State<'_, MusicDatabase>,
     song_id: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("DELETE FROM history WHERE song_id = ?")
         .bind(song_id)
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
@@ -272,13 +855,12 @@ pub async fn remove_song_from_playlist(
     music_db: State<'_, MusicDatabase>,
     playlist_id: String,
     song_id: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("DELETE FROM playlist_songs WHERE playlist_id = ? AND song_id = ?")
         .bind(playlist_id)
         .bind(song_id)
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
@@ -286,12 +868,17 @@ pub async fn remove_song_from_playlist(
 pub async fn remove_playlist(
     music_db: State<'_, MusicDatabase>,
     playlist_id: String,
-) -> Result<(), String> {
-    sqlx::query("DELETE FROM playlists WHERE id = ?")
-        .bind(playlist_id)
-        .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+) -> Result<(), AppError> {
+    let cover: Option<String> = sqlx::query_scalar::<_, Option<String>>(
+        "DELETE FROM playlists WHERE id = ? RETURNING cover",
+    )
+    .bind(playlist_id)
+    .fetch_optional(&music_db.pool)
+    .await?
+    .flatten();
+    if let Some(cover) = cover {
+        let _ = fs::remove_file(cover);
+    }
     Ok(())
 }
 
@@ -299,17 +886,16 @@ pub async fn remove_playlist(
 pub async fn remove_album(
     music_db: State<'_, MusicDatabase>,
     album_id: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     sqlx::query("DELETE FROM albums WHERE id = ?")
         .bind(album_id)
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn add_album(music_db: State<'_, MusicDatabase>, album: Album) -> Result<(), String> {
+pub async fn add_album(music_db: State<'_, MusicDatabase>, album: Album) -> Result<(), AppError> {
     sqlx::query("INSERT INTO albums (id, name, artist, cover, date_added) VALUES (?, ?, ?, ?, ?)")
         .bind(album.id)
         .bind(album.name)
@@ -317,8 +903,7 @@ pub async fn add_album(music_db: State<'_, MusicDatabase>, album: Album) -> Resu
         .bind(album.cover)
         .bind(album.date_added.to_rfc3339())
         .execute(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
     Ok(())
 }
 
@@ -326,12 +911,11 @@ pub async fn add_album(music_db: State<'_, MusicDatabase>, album: Album) -> Resu
 pub async fn get_album(
     music_db: State<'_, MusicDatabase>,
     id: String,
-) -> Result<Option<Album>, String> {
+) -> Result<Option<Album>, AppError> {
     let row = sqlx::query("SELECT id, name, artist, cover, date_added FROM albums WHERE id = ?")
         .bind(id)
         .fetch_optional(&music_db.pool)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     if let Some(row) = row {
         let songs = get_songs_in_album(music_db.clone(), row.get("id")).await?;
@@ -340,7 +924,7 @@ pub async fn get_album(
             name: row.get("name"),
             artist: row.get("artist"),
             cover: row.get("cover"),
-            date_added: row.get::<String, _>("date_added").parse().unwrap(),
+            date_added: parse_date(&row, "date_added")?,
             songs,
         }))
     } else {
@@ -348,34 +932,621 @@ pub async fn get_album(
     }
 }
 
+/// Every artist in the library with their number of songs, sorted by name
+/// with a leading "The" ignored.
+#[tauri::command]
+pub async fn get_artists(music_db: State<'_, MusicDatabase>) -> Result<Vec<ArtistCount>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {0} AS artist, COUNT(*) AS count FROM songs s GROUP BY {0} COLLATE NOCASE",
+        ARTIST_NAME
+    ))
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut artists: Vec<ArtistCount> = rows
+        .iter()
+        .map(|row| ArtistCount {
+            artist: row.get("artist"),
+            count: row.get("count"),
+        })
+        .collect();
+    artists.sort_by_cached_key(|a| artist_sort_key(&a.artist));
+    Ok(artists)
+}
+
+/// The albums of an artist as returned by `get_artists`, by name.
+#[tauri::command]
+pub async fn get_albums_by_artist(
+    music_db: State<'_, MusicDatabase>,
+    artist: String,
+) -> Result<Vec<AlbumSummary>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {0} AS artist, {1} AS album, COUNT(*) AS count, MIN(s.id) AS first_id
+         FROM songs s
+         WHERE {0} = ? COLLATE NOCASE
+         GROUP BY {1} COLLATE NOCASE
+         ORDER BY {1} COLLATE NOCASE",
+        ARTIST_NAME, ALBUM_NAME
+    ))
+    .bind(artist.trim())
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let artist: String = row.get("artist");
+            let name: String = row.get("album");
+            AlbumSummary {
+                id: album_key(&artist, &name),
+                cover: music_db.get_song_cover(&row.get::<String, _>("first_id")),
+                song_count: row.get("count"),
+                name,
+                artist,
+            }
+        })
+        .collect())
+}
+
+/// Songs of an album from `get_albums_by_artist` or one added with
+/// `add_album`.
+#[tauri::command]
+pub async fn get_songs_by_album(
+    music_db: State<'_, MusicDatabase>,
+    album_id: String,
+) -> Result<Vec<Song>, AppError> {
+    let songs = get_songs_in_album(music_db.clone(), album_id.clone()).await?;
+    if !songs.is_empty() {
+        return Ok(songs);
+    }
+
+    let albums = sqlx::query(&format!(
+        "SELECT DISTINCT {} AS artist, {} AS album FROM songs s",
+        ARTIST_NAME, ALBUM_NAME
+    ))
+    .fetch_all(&music_db.pool)
+    .await?;
+    let (artist, album) = albums
+        .iter()
+        .map(|row| {
+            (
+                row.get::<String, _>("artist"),
+                row.get::<String, _>("album"),
+            )
+        })
+        .find(|(artist, album)| album_key(artist, album) == album_id)
+        .ok_or_else(|| AppError::NotFound(format!("Album {} does not exist", album_id)))?;
+
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s
+         WHERE {} = ? COLLATE NOCASE AND {} = ? COLLATE NOCASE
+         ORDER BY s.title",
+        SONG_COLUMNS, ARTIST_NAME, ALBUM_NAME
+    ))
+    .bind(artist)
+    .bind(album)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
+    }
+    Ok(songs)
+}
+
+#[tauri::command]
+pub async fn set_album_art(
+    music_db: State<'_, MusicDatabase>,
+    album_id: String,
+    bytes: Vec<u8>,
+) -> Result<(), AppError> {
+    if bytes.is_empty() {
+        return Err(AppError::InvalidInput("Album art is empty".to_string()));
+    }
+    let exists: Option<String> = sqlx::query_scalar("SELECT id FROM albums WHERE id = ?")
+        .bind(&album_id)
+        .fetch_optional(&music_db.pool)
+        .await?;
+    if exists.is_none() {
+        return Err(AppError::NotFound(format!(
+            "Album {} does not exist",
+            album_id
+        )));
+    }
+    music_db.store_album_art(&album_id, &bytes).await
+}
+
+/// Returns the base64 encoded art for a song. Album art is preferred; when
+/// the album has none yet it is extracted from the song file and cached for
+/// the album. Falls back to the song's own cover.
+#[tauri::command]
+pub async fn get_album_art(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+) -> Result<Option<String>, AppError> {
+    let album = sqlx::query(
+        "SELECT a.id, a.album_art FROM albums a
+         JOIN album_songs s ON s.album_id = a.id
+         WHERE s.song_id = ?
+         LIMIT 1",
+    )
+    .bind(&song_id)
+    .fetch_optional(&music_db.pool)
+    .await?;
+
+    let album_id = album.as_ref().map(|row| row.get::<String, _>("id"));
+    let cached = album
+        .and_then(|row| row.get::<Option<String>, _>("album_art"))
+        .and_then(|path| fs::read(path).ok());
+    if let Some(data) = cached {
+        return Ok(Some(BASE64_STANDARD.encode(data)));
+    }
+
+    if let Ok(file) = music_db.song_file(&song_id).await {
+        let embedded = tokio::task::spawn_blocking(move || metadata::read_metadata(&file))
+            .await
+            .map_err(|e| AppError::Io(e.to_string()))?
+            .ok()
+            .and_then(|metadata| metadata.cover);
+        if let Some(data) = embedded {
+            if let Some(album_id) = album_id {
+                music_db.store_album_art(&album_id, &data).await?;
+            }
+            return Ok(Some(BASE64_STANDARD.encode(data)));
+        }
+    }
+
+    let cover = music_db.get_song_cover(&song_id);
+    Ok((!cover.is_empty()).then_some(cover))
+}
+
+#[tauri::command]
+pub async fn queue_filtered(
+    music_db: State<'_, MusicDatabase>,
+    settings_db: State<'_, SettingsDatabase>,
+    filter: SongFilter,
+    sort_by: Option<SongSort>,
+    limit: Option<usize>,
+) -> Result<usize, AppError> {
+    let limit = limit.unwrap_or(MAX_QUEUE_FILTERED).min(MAX_QUEUE_FILTERED);
+    let (clause, params) = song_filter_clause(&filter);
+    let sql = format!(
+        "SELECT s.id FROM songs s {} ORDER BY {}",
+        clause,
+        sort_by.unwrap_or_default().order_by()
+    );
+
+    let mut queue = settings_db
+        .get_setting("queue")
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))
+        .and_then(|s| parse_queue(&s).map_err(|e| AppError::Db(e.to_string())))?;
+
+    let mut query = sqlx::query_scalar::<_, String>(&sql);
+    for param in &params {
+        query = query.bind(param);
+    }
+    let mut ids = query.fetch(&music_db.pool);
+
+    let mut queued = 0;
+    while queued < limit {
+        match ids.try_next().await? {
+            Some(id) => {
+                queue.push(id);
+                queued += 1;
+            }
+            None => break,
+        }
+    }
+    drop(ids);
+
+    settings_db.update_setting("queue", queue).await?;
+    Ok(queued)
+}
+
+/// Loads the songs of the saved `queue_snapshot` in their queued order. Songs
+/// that have been deleted since are left out; if the current one is among
+/// them, `current_index` is -1 and the position is dropped.
+#[tauri::command]
+pub async fn restore_queue(
+    music_db: State<'_, MusicDatabase>,
+    settings_db: State<'_, SettingsDatabase>,
+) -> Result<RestoredQueue, AppError> {
+    let snapshot: QueueSnapshot = settings_db
+        .get_setting("queue_snapshot")
+        .await
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+
+    let found = music_db.find_songs(&snapshot.song_ids).await?;
+
+    let mut songs = Vec::with_capacity(snapshot.song_ids.len());
+    let mut current_index = -1;
+    for (index, id) in snapshot.song_ids.iter().enumerate() {
+        let Some(song) = found.get(id) else {
+            continue;
+        };
+        if index as i64 == snapshot.current_index {
+            current_index = songs.len() as i64;
+        }
+        songs.push(song.clone());
+    }
+
+    Ok(RestoredQueue {
+        songs,
+        current_index,
+        position: if current_index >= 0 {
+            snapshot.position
+        } else {
+            0.0
+        },
+    })
+}
+
+#[tauri::command]
+pub async fn get_genres(music_db: State<'_, MusicDatabase>) -> Result<Vec<GenreCount>, AppError> {
+    let rows = sqlx::query("SELECT genre FROM songs WHERE genre IS NOT NULL AND genre != ''")
+        .fetch_all(&music_db.pool)
+        .await?;
+
+    let mut counts: HashMap<String, GenreCount> = HashMap::new();
+    for row in rows {
+        for genre in split_genres(&row.get::<String, _>("genre")) {
+            counts
+                .entry(genre.to_lowercase())
+                .or_insert_with(|| GenreCount { genre, count: 0 })
+                .count += 1;
+        }
+    }
+
+    let mut genres: Vec<GenreCount> = counts.into_values().collect();
+    genres.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.genre.cmp(&b.genre)));
+    Ok(genres)
+}
+
+#[tauri::command]
+pub async fn get_songs_by_genre(
+    music_db: State<'_, MusicDatabase>,
+    genre: String,
+) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s WHERE s.genre LIKE ? ESCAPE '\\' ORDER BY s.title",
+        SONG_COLUMNS
+    ))
+    .bind(format!("%{}%", escape_like(genre.trim())))
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        let song = music_db.song_from_row(&row)?;
+        let matches = song.genre.as_deref().is_some_and(|g| {
+            split_genres(g)
+                .iter()
+                .any(|g| g.eq_ignore_ascii_case(genre.trim()))
+        });
+        if matches {
+            songs.push(song);
+        }
+    }
+
+    Ok(songs)
+}
+
+#[tauri::command]
+pub async fn update_song_duration(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+    duration: i64,
+) -> Result<(), AppError> {
+    sqlx::query("UPDATE songs SET duration = ? WHERE id = ?")
+        .bind(duration)
+        .bind(song_id)
+        .execute(&music_db.pool)
+        .await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_song_bpm(
+    music_db: State<'_, MusicDatabase>,
+    song_id: String,
+    bpm: f64,
+) -> Result<(), AppError> {
+    sqlx::query("UPDATE songs SET bpm = ? WHERE id = ?")
+        .bind(bpm)
+        .bind(song_id)
+        .execute(&music_db.pool)
+        .await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_songs_without_bpm(
+    music_db: State<'_, MusicDatabase>,
+) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s WHERE s.bpm IS NULL ORDER BY s.title",
+        SONG_COLUMNS
+    ))
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
+    }
+
+    Ok(songs)
+}
+
+#[tauri::command]
+pub async fn get_songs_by_bpm_range(
+    music_db: State<'_, MusicDatabase>,
+    min: f64,
+    max: f64,
+) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM songs s WHERE s.bpm BETWEEN ? AND ? ORDER BY s.bpm",
+        SONG_COLUMNS
+    ))
+    .bind(min)
+    .bind(max)
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        songs.push(music_db.song_from_row(&row)?);
+    }
+
+    Ok(songs)
+}
+
+/// Recreates song rows from the audio files in `Songs/`, using each file name
+/// as the song id. Songs that are already in the library are left untouched.
+#[tauri::command]
+pub async fn rebuild_library_from_files(
+    music_db: State<'_, MusicDatabase>,
+) -> Result<LibraryRebuildReport, AppError> {
+    let songs_dir = get_music_path().join("Songs");
+    let entries = fs::read_dir(&songs_dir)?;
+    let mut report = LibraryRebuildReport::default();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_audio = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext));
+        if !is_audio {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        let file = path.clone();
+        let metadata = match tokio::task::spawn_blocking(move || metadata::read_metadata(&file))
+            .await
+            .map_err(|e| AppError::Io(e.to_string()))?
+        {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                report.failed.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+
+        let date_added: DateTime<Utc> = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .map(DateTime::from)
+            .unwrap_or_else(|_| Utc::now());
+
+        if music_db
+            .insert_imported_song(id, None, metadata, date_added)
+            .await?
+        {
+            report.recovered += 1;
+        } else {
+            report.existing += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Lists playlist entries whose song no longer exists in the library.
+#[tauri::command]
+pub async fn validate_playlists(
+    music_db: State<'_, MusicDatabase>,
+) -> Result<Vec<BrokenPlaylist>, AppError> {
+    let rows = sqlx::query(
+        "SELECT ps.playlist_id, ps.song_id FROM playlist_songs ps
+         LEFT JOIN songs s ON s.id = ps.song_id
+         WHERE s.id IS NULL
+         ORDER BY ps.playlist_id",
+    )
+    .fetch_all(&music_db.pool)
+    .await?;
+
+    let mut broken: Vec<BrokenPlaylist> = Vec::new();
+    for row in rows {
+        let playlist_id: String = row.get("playlist_id");
+        let song_id: String = row.get("song_id");
+        match broken.last_mut() {
+            Some(last) if last.playlist_id == playlist_id => last.missing_song_ids.push(song_id),
+            _ => broken.push(BrokenPlaylist {
+                playlist_id,
+                missing_song_ids: vec![song_id],
+            }),
+        }
+    }
+
+    Ok(broken)
+}
+
+#[tauri::command]
+pub async fn prune_playlist_broken_entries(
+    music_db: State<'_, MusicDatabase>,
+    playlist_id: String,
+) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        "DELETE FROM playlist_songs
+         WHERE playlist_id = ? AND song_id NOT IN (SELECT id FROM songs)",
+    )
+    .bind(playlist_id)
+    .execute(&music_db.pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Writes the playlist as an extended M3U with absolute paths to the song
+/// files. Songs without a local file are left out and reported.
+#[tauri::command]
+pub async fn export_playlist_m3u(
+    music_db: State<'_, MusicDatabase>,
+    playlist_id: String,
+    path: String,
+) -> Result<M3uExportReport, AppError> {
+    let playlist = get_playlist(music_db.clone(), playlist_id.clone())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Playlist {} does not exist", playlist_id)))?;
+
+    let mut report = M3uExportReport::default();
+    let mut m3u = format!("#EXTM3U\n#PLAYLIST:{}\n", playlist.name);
+    for song in &playlist.songs {
+        match music_db.song_file(&song.id).await {
+            Ok(file) => {
+                m3u.push_str(&format!(
+                    "#EXTINF:{},{} - {}\n{}\n",
+                    song.duration,
+                    song.artist,
+                    song.title,
+                    file.display()
+                ));
+                report.written += 1;
+            }
+            Err(e) => report.skipped.push(format!("{}: {}", song.title, e)),
+        }
+    }
+
+    fs::write(&path, m3u).map_err(|e| AppError::from(e).with_context(&path))?;
+    Ok(report)
+}
+
+/// Creates a playlist from an M3U file. Entries that point at a file in
+/// `Songs/` reuse that song, anything else is indexed in place like a folder
+/// scan would. Missing or unreadable files are skipped and reported.
+#[tauri::command]
+pub async fn import_playlist_m3u(
+    music_db: State<'_, MusicDatabase>,
+    path: String,
+) -> Result<M3uImportReport, AppError> {
+    let m3u_path = PathBuf::from(&path);
+    let content = fs::read(&m3u_path).map_err(|e| AppError::from(e).with_context(&path))?;
+    let content = String::from_utf8_lossy(&content);
+    let base_dir = m3u_path.parent().unwrap_or(Path::new(""));
+    let songs_dir = get_music_path().join("Songs");
+
+    let mut name = m3u_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Imported Playlist".to_string());
+    let mut report = M3uImportReport::default();
+    let mut song_ids: Vec<String> = Vec::new();
+
+    for line in content
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+    {
+        if let Some(playlist_name) = line.strip_prefix("#PLAYLIST:") {
+            if !playlist_name.trim().is_empty() {
+                name = playlist_name.trim().to_string();
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let file = if line.starts_with("file://") {
+            let Some(file) = reqwest::Url::parse(line)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+            else {
+                report.skipped.push(format!("{}: not a local file", line));
+                continue;
+            };
+            file
+        } else {
+            base_dir.join(line)
+        };
+        if !file.is_file() {
+            report.skipped.push(format!("{}: file not found", line));
+            continue;
+        }
+
+        if file.parent() == Some(songs_dir.as_path()) {
+            if let Some(id) = file.file_stem().and_then(|stem| stem.to_str()) {
+                if get_song(music_db.clone(), id.to_string()).await?.is_some() {
+                    song_ids.push(id.to_string());
+                    continue;
+                }
+            }
+        }
+
+        match import_file(&music_db, &file, None).await {
+            Ok(_) => song_ids.push(path_id(&file)),
+            Err(e) => report.skipped.push(format!("{}: {}", line, e)),
+        }
+    }
+
+    report.playlist_id = format!("m3u-{}", Utc::now().timestamp_millis());
+    let mut tx = music_db.pool.begin().await?;
+    sqlx::query("INSERT INTO playlists (id, name, date_created) VALUES (?, ?, ?)")
+        .bind(&report.playlist_id)
+        .bind(&name)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+    for song_id in &song_ids {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO playlist_songs (playlist_id, song_id) VALUES (?, ?)",
+        )
+        .bind(&report.playlist_id)
+        .bind(song_id)
+        .execute(&mut *tx)
+        .await?;
+        report.added += result.rows_affected() as usize;
+    }
+    tx.commit().await?;
+
+    Ok(report)
+}
+
 async fn get_songs_in_playlist(
     music_db: State<'_, MusicDatabase>,
     playlist_id: String,
-) -> Result<Vec<Song>, String> {
-    let rows = sqlx::query(
-        "SELECT s.id, s.title, s.artist, s.album, s.cover, s.date_added, s.duration
+) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {}
          FROM songs s
          JOIN playlist_songs ps ON s.id = ps.song_id
          WHERE ps.playlist_id = ?",
-    )
+        SONG_COLUMNS
+    ))
     .bind(playlist_id)
     .fetch_all(&music_db.pool)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     let mut songs = Vec::new();
     for row in rows {
-        let mut song = Song {
-            id: row.get("id"),
-            title: row.get("title"),
-            artist: row.get("artist"),
-            album: row.get("album"),
-            cover: row.get("cover"),
-            date_added: row.get::<String, _>("date_added").parse().unwrap(),
-            duration: row.get("duration"),
-        };
-        song.cover = music_db.get_song_cover(&song.id);
-        songs.push(song);
+        songs.push(music_db.song_from_row(&row)?);
     }
 
     Ok(songs)
@@ -384,32 +1555,127 @@ async fn get_songs_in_playlist(
 async fn get_songs_in_album(
     music_db: State<'_, MusicDatabase>,
     album_id: String,
-) -> Result<Vec<Song>, String> {
-    let rows = sqlx::query(
-        "SELECT s.id, s.title, s.artist, s.album, s.cover, s.date_added, s.duration
+) -> Result<Vec<Song>, AppError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {}
          FROM songs s
-         JOIN album_songs as ON s.id = as.song_id
-         WHERE as.album_id = ?",
-    )
+         JOIN album_songs a ON s.id = a.song_id
+         WHERE a.album_id = ?",
+        SONG_COLUMNS
+    ))
     .bind(album_id)
     .fetch_all(&music_db.pool)
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
     let mut songs = Vec::new();
     for row in rows {
-        let mut song = Song {
-            id: row.get("id"),
-            title: row.get("title"),
-            artist: row.get("artist"),
-            album: row.get("album"),
-            cover: row.get("cover"),
-            date_added: row.get::<String, _>("date_added").parse().unwrap(),
-            duration: row.get("duration"),
-        };
-        song.cover = music_db.get_song_cover(&song.id);
-        songs.push(song);
+        songs.push(music_db.song_from_row(&row)?);
     }
 
     Ok(songs)
 }
+
+async fn history_from_rows(
+    music_db: State<'_, MusicDatabase>,
+    rows: Vec<SqliteRow>,
+) -> Result<Vec<History>, AppError> {
+    let mut history = Vec::new();
+    for row in rows {
+        let Some(song) = get_song(music_db.clone(), row.get("song_id")).await? else {
+            continue;
+        };
+        history.push(History {
+            id: row.get("id"),
+            date_played: parse_date(&row, "date_played")?,
+            song,
+        });
+    }
+    Ok(history)
+}
+
+fn millis_to_date(ms: i64) -> Result<DateTime<Utc>, AppError> {
+    DateTime::from_timestamp_millis(ms)
+        .ok_or_else(|| AppError::InvalidInput(format!("Invalid timestamp: {}", ms)))
+}
+
+/// Local midnight at the start of the day, week or month `now` falls in.
+fn period_start(period: StatsPeriod, now: DateTime<Local>) -> DateTime<Utc> {
+    let today = now.date_naive();
+    let first_day = match period {
+        StatsPeriod::Day => today,
+        StatsPeriod::Week => today - Days::new(today.weekday().num_days_from_monday() as u64),
+        StatsPeriod::Month => today.with_day(1).unwrap_or(today),
+    };
+    // Midnight can be skipped by a daylight saving change.
+    Local
+        .from_local_datetime(&first_day.and_time(NaiveTime::MIN))
+        .earliest()
+        .unwrap_or(now)
+        .with_timezone(&Utc)
+}
+
+fn parse_date(row: &SqliteRow, column: &str) -> Result<DateTime<Utc>, AppError> {
+    row.get::<String, _>(column)
+        .parse()
+        .map_err(|e| AppError::Db(format!("Invalid {}: {}", column, e)))
+}
+
+fn song_filter_clause(filter: &SongFilter) -> (String, Vec<String>) {
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+
+    if let Some(query) = filter
+        .query
+        .as_deref()
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+    {
+        conditions.push(
+            "(title LIKE ? ESCAPE '\\' OR artist LIKE ? ESCAPE '\\' OR album LIKE ? ESCAPE '\\')",
+        );
+        let pattern = format!("%{}%", escape_like(query));
+        params.extend([pattern.clone(), pattern.clone(), pattern]);
+    }
+    if let Some(artist) = &filter.artist {
+        conditions.push("artist = ?");
+        params.push(artist.clone());
+    }
+    if let Some(album) = &filter.album {
+        conditions.push("album = ?");
+        params.push(album.clone());
+    }
+
+    if conditions.is_empty() {
+        (String::new(), params)
+    } else {
+        (format!("WHERE {}", conditions.join(" AND ")), params)
+    }
+}
+
+fn escape_like(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Sort key for artist names, ignoring case and a leading "The ".
+fn artist_sort_key(artist: &str) -> String {
+    let lower = artist.trim().to_lowercase();
+    match lower.strip_prefix("the ") {
+        Some(rest) if !rest.trim().is_empty() => rest.trim_start().to_string(),
+        _ => lower,
+    }
+}
+
+/// Stable id of an album grouped from song tags, matching the case
+/// insensitive grouping of `get_albums_by_artist`.
+fn album_key(artist: &str, album: &str) -> String {
+    let hash = format!("{}\0{}", artist, album)
+        .to_ascii_lowercase()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("album-{:016x}", hash)
+}
@@ -1,8 +1,17 @@
 use anyhow::{Ok, Result};
 use serde::Serialize;
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
 use sqlx::SqlitePool;
-use crate::db::types::{Settings, Song, EQSettings};
+use std::collections::HashMap;
+use crate::api::commands::{ping_urls_helper, set_download_limit, DEFAULT_PING_TIMEOUT};
+use crate::api::downloads::DownloadManager;
+use crate::api::{discord_rpc, media_controls, media_keys, tray};
+use crate::db::music::MusicDatabase;
+use crate::utils::{logger, scrobble};
+use crate::db::types::{Backend, BackendStatus, EqPreset, MediaKeyBindings, NormalizationMode, PlayerState, QueueSnapshot, RepeatMode, Settings, Song, EQSettings};
+
+const EQ_FREQUENCIES: [u32; 10] = [32, 64, 125, 250, 500, 1000, 2000, 4000, 8000, 16000];
+const EQ_MAX_GAIN: f64 = 12.0;
 
 pub struct SettingsDatabase {
     pub pool: SqlitePool,
@@ -51,33 +60,565 @@ impl SettingsDatabase {
 
     async fn initialize_default_settings(&self, settings: &Settings) -> Result<()> {
         let settings_map = [
+            ("active_backend", settings.active_backend.clone()),
             ("api_url", settings.api_url.clone()),
+            ("auto_select_api", settings.auto_select_api.to_string()),
+            ("backends", serde_json::to_string(&settings.backends)?),
+            ("balance", settings.balance.to_string()),
+            ("close_to_tray", settings.close_to_tray.to_string()),
+            ("crossfade_ms", settings.crossfade_ms.to_string()),
+            ("crossfade_on_skip", settings.crossfade_on_skip.to_string()),
             ("current_song", serde_json::to_string(&settings.current_song)?),
+            ("discord_rpc_enabled", settings.discord_rpc_enabled.to_string()),
+            ("download_bandwidth_limit_kbps", settings.download_bandwidth_limit_kbps.to_string()),
             ("eq", serde_json::to_string(&settings.eq)?),
+            ("fade_duration_ms", settings.fade_duration_ms.to_string()),
+            ("gapless", settings.gapless.to_string()),
+            ("keep_streamed", settings.keep_streamed.to_string()),
+            ("lastfm_enabled", settings.lastfm_enabled.to_string()),
+            ("lastfm_session_key", settings.lastfm_session_key.clone()),
+            ("lastfm_username", settings.lastfm_username.clone()),
+            ("level_meter", settings.level_meter.to_string()),
+            ("log_level", settings.log_level.clone()),
+            ("log_max_size_mb", settings.log_max_size_mb.to_string()),
+            ("log_retention", settings.log_retention.to_string()),
             ("lossless", settings.lossless.to_string()),
             ("loop", settings.r#loop.to_string()),
+            ("lyrics_api_url", settings.lyrics_api_url.clone()),
+            ("max_audio_memory_mb", settings.max_audio_memory_mb.to_string()),
+            ("max_concurrent_downloads", settings.max_concurrent_downloads.to_string()),
+            ("media_keys", serde_json::to_string(&settings.media_keys)?),
+            ("min_silence_ms", settings.min_silence_ms.to_string()),
+            ("mono", settings.mono.to_string()),
             ("muted", settings.muted.to_string()),
+            ("normalization", settings.normalization.as_str().to_string()),
+            ("output_device", settings.output_device.clone()),
+            ("output_device_id", settings.output_device_id.clone()),
+            ("playback_speed", settings.playback_speed.to_string()),
             ("queue", serde_json::to_string(&settings.queue)?),
+            ("queue_pins", serde_json::to_string(&settings.queue_pins)?),
+            ("queue_snapshot", serde_json::to_string(&settings.queue_snapshot)?),
+            ("repeat_mode", settings.repeat_mode.as_str().to_string()),
+            ("resume_on_launch", settings.resume_on_launch.to_string()),
+            ("romanize_lyrics", settings.romanize_lyrics.to_string()),
             ("shuffle", settings.shuffle.to_string()),
+            ("silence_threshold_db", settings.silence_threshold_db.to_string()),
+            ("skip_silence", settings.skip_silence.to_string()),
+            ("smart_resume", settings.smart_resume.to_string()),
+            ("smart_resume_ramp_ms", settings.smart_resume_ramp_ms.to_string()),
             ("streaming", settings.streaming.to_string()),
             ("volume", settings.volume.to_string()),
         ];
 
+        // Only fill in missing keys so a setting added in a newer version
+        // doesn't reset everything the user already configured.
         for (key, value) in settings_map {
-            self.update_setting(key, value).await?;
+            sqlx::query("INSERT OR IGNORE INTO settings (key, value) VALUES (?, ?)")
+                .bind(key)
+                .bind(value)
+                .execute(&self.pool)
+                .await?;
         }
         Ok(())
     }
 }
 
+impl SettingsDatabase {
+    async fn get_backends(&self) -> Result<Vec<Backend>> {
+        let backends = self.get_setting("backends").await?;
+        Ok(serde_json::from_str(&backends)?)
+    }
+
+    /// Every setting in one query, with defaults for keys that haven't been
+    /// stored yet.
+    pub async fn get_all(&self) -> Result<Settings> {
+        let rows: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM settings")
+            .fetch_all(&self.pool)
+            .await?;
+        let stored: HashMap<String, String> = rows.into_iter().collect();
+
+        let defaults = serde_json::to_value(Settings::default())?
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        let mut merged = serde_json::Map::new();
+        for (key, default) in defaults {
+            // Strings are stored as they are, everything else as JSON.
+            let value = match stored.get(&key) {
+                Some(raw) if key == "queue" => serde_json::to_value(parse_queue(raw)?)?,
+                Some(raw) if default.is_string() => serde_json::Value::String(raw.clone()),
+                Some(raw) => serde_json::from_str(raw)
+                    .unwrap_or_else(|_| serde_json::Value::String(raw.clone())),
+                None => default,
+            };
+            merged.insert(key, value);
+        }
+        Ok(serde_json::from_value(serde_json::Value::Object(merged))?)
+    }
+}
+
+#[tauri::command]
+pub async fn get_all_settings(
+    settings_db: State<'_, SettingsDatabase>,
+) -> Result<Settings, String> {
+    settings_db.get_all().await.map_err(|e| e.to_string())
+}
+
+/// Stores every setting through its own setter, so values are validated and
+/// take effect the same way as when they are changed one at a time. A value
+/// that is rejected doesn't stop the others; the errors are returned together.
+#[tauri::command]
+pub async fn set_all_settings(
+    app: AppHandle,
+    settings_db: State<'_, SettingsDatabase>,
+    settings: Settings,
+) -> Result<(), String> {
+    apply_settings(app, settings_db, settings, &[]).await
+}
+
+/// `set_all_settings`, leaving the settings named in `skip` as they are.
+pub(crate) async fn apply_settings(
+    app: AppHandle,
+    settings_db: State<'_, SettingsDatabase>,
+    settings: Settings,
+    skip: &[&str],
+) -> Result<(), String> {
+    let mut errors = Vec::new();
+    macro_rules! apply {
+        ($key:literal, $result:expr) => {
+            if !skip.contains(&$key) {
+                if let Err(e) = $result {
+                    errors.push(format!("{}: {}", $key, e));
+                }
+            }
+        };
+    }
+
+    // Backends and the Last.fm session have no setters of their own.
+    apply!(
+        "backends",
+        settings_db
+            .update_setting("backends", &settings.backends)
+            .await
+            .map_err(|e| e.to_string())
+    );
+    apply!(
+        "active_backend",
+        set_active_backend(settings_db.clone(), settings.active_backend).await
+    );
+    apply!(
+        "api_url",
+        set_api_url(settings_db.clone(), settings.api_url).await
+    );
+    apply!(
+        "auto_select_api",
+        set_auto_select_api(settings_db.clone(), settings.auto_select_api).await
+    );
+    apply!(
+        "balance",
+        set_balance(settings_db.clone(), settings.balance).await
+    );
+    apply!(
+        "close_to_tray",
+        set_close_to_tray(settings_db.clone(), settings.close_to_tray).await
+    );
+    apply!(
+        "crossfade_ms",
+        set_crossfade_ms(settings_db.clone(), settings.crossfade_ms).await
+    );
+    apply!(
+        "crossfade_on_skip",
+        set_crossfade_on_skip(settings_db.clone(), settings.crossfade_on_skip).await
+    );
+    apply!(
+        "current_song",
+        set_current_song(settings_db.clone(), settings.current_song).await
+    );
+    apply!(
+        "discord_rpc_enabled",
+        set_discord_rpc_enabled(settings_db.clone(), settings.discord_rpc_enabled).await
+    );
+    apply!(
+        "download_bandwidth_limit_kbps",
+        set_download_bandwidth_limit_kbps(
+            settings_db.clone(),
+            settings.download_bandwidth_limit_kbps
+        )
+        .await
+    );
+    apply!("eq", set_eq(settings_db.clone(), settings.eq).await);
+    apply!(
+        "fade_duration_ms",
+        set_fade_duration_ms(settings_db.clone(), settings.fade_duration_ms).await
+    );
+    apply!(
+        "gapless",
+        set_gapless(settings_db.clone(), settings.gapless).await
+    );
+    apply!(
+        "keep_streamed",
+        set_keep_streamed(settings_db.clone(), settings.keep_streamed).await
+    );
+    apply!(
+        "lastfm_session_key",
+        settings_db
+            .update_setting("lastfm_session_key", settings.lastfm_session_key)
+            .await
+            .map_err(|e| e.to_string())
+    );
+    apply!(
+        "lastfm_username",
+        settings_db
+            .update_setting("lastfm_username", settings.lastfm_username)
+            .await
+            .map_err(|e| e.to_string())
+    );
+    apply!(
+        "lastfm_enabled",
+        set_lastfm_enabled(settings_db.clone(), settings.lastfm_enabled).await
+    );
+    apply!(
+        "level_meter",
+        set_level_meter(settings_db.clone(), settings.level_meter).await
+    );
+    apply!(
+        "log_level",
+        set_log_level(settings_db.clone(), settings.log_level).await
+    );
+    apply!(
+        "log_max_size_mb",
+        set_log_max_size_mb(settings_db.clone(), settings.log_max_size_mb).await
+    );
+    apply!(
+        "log_retention",
+        set_log_retention(settings_db.clone(), settings.log_retention).await
+    );
+    apply!(
+        "lossless",
+        set_lossless(settings_db.clone(), settings.lossless).await
+    );
+    apply!("loop", set_loop(settings_db.clone(), settings.r#loop).await);
+    apply!(
+        "lyrics_api_url",
+        set_lyrics_api_url(settings_db.clone(), settings.lyrics_api_url).await
+    );
+    apply!(
+        "max_audio_memory_mb",
+        set_max_audio_memory_mb(settings_db.clone(), settings.max_audio_memory_mb).await
+    );
+    apply!(
+        "max_concurrent_downloads",
+        set_max_concurrent_downloads(
+            app.clone(),
+            settings_db.clone(),
+            settings.max_concurrent_downloads
+        )
+        .await
+    );
+    apply!(
+        "media_keys",
+        set_media_keys(app.clone(), settings_db.clone(), settings.media_keys).await
+    );
+    apply!(
+        "min_silence_ms",
+        set_min_silence_ms(settings_db.clone(), settings.min_silence_ms).await
+    );
+    apply!("mono", set_mono(settings_db.clone(), settings.mono).await);
+    apply!(
+        "muted",
+        set_muted(settings_db.clone(), settings.muted).await
+    );
+    apply!(
+        "normalization",
+        set_normalization(settings_db.clone(), settings.normalization).await
+    );
+    apply!(
+        "output_device",
+        set_output_device(settings_db.clone(), settings.output_device).await
+    );
+    apply!(
+        "output_device_id",
+        set_output_device_id(settings_db.clone(), settings.output_device_id).await
+    );
+    apply!(
+        "playback_speed",
+        set_playback_speed(settings_db.clone(), settings.playback_speed).await
+    );
+    apply!(
+        "queue",
+        set_queue(settings_db.clone(), settings.queue).await
+    );
+    apply!(
+        "queue_pins",
+        settings_db
+            .update_setting("queue_pins", settings.queue_pins)
+            .await
+            .map_err(|e| e.to_string())
+    );
+    apply!(
+        "queue_snapshot",
+        set_queue_snapshot(settings_db.clone(), settings.queue_snapshot).await
+    );
+    apply!(
+        "repeat_mode",
+        set_repeat_mode(settings_db.clone(), settings.repeat_mode).await
+    );
+    apply!(
+        "resume_on_launch",
+        set_resume_on_launch(settings_db.clone(), settings.resume_on_launch).await
+    );
+    apply!(
+        "romanize_lyrics",
+        set_romanize_lyrics(settings_db.clone(), settings.romanize_lyrics).await
+    );
+    apply!(
+        "shuffle",
+        set_shuffle(settings_db.clone(), settings.shuffle).await
+    );
+    apply!(
+        "silence_threshold_db",
+        set_silence_threshold_db(settings_db.clone(), settings.silence_threshold_db).await
+    );
+    apply!(
+        "skip_silence",
+        set_skip_silence(settings_db.clone(), settings.skip_silence).await
+    );
+    apply!(
+        "smart_resume",
+        set_smart_resume(settings_db.clone(), settings.smart_resume).await
+    );
+    apply!(
+        "smart_resume_ramp_ms",
+        set_smart_resume_ramp_ms(settings_db.clone(), settings.smart_resume_ramp_ms).await
+    );
+    apply!(
+        "streaming",
+        set_streaming(settings_db.clone(), settings.streaming).await
+    );
+    apply!(
+        "volume",
+        set_volume(settings_db.clone(), settings.volume).await
+    );
+
+    if errors.is_empty() {
+        Result::<(), String>::Ok(())
+    } else {
+        Err(format!(
+            "Some settings were not applied: {}",
+            errors.join("; ")
+        ))
+    }
+}
+
+#[tauri::command]
+pub async fn add_backend(
+    settings_db: State<'_, SettingsDatabase>,
+    name: String,
+    url: String,
+) -> Result<(), String> {
+    let name = name.trim().to_string();
+    let url = url.trim().trim_end_matches('/').to_string();
+    if name.is_empty() || url.is_empty() {
+        return Err("Backend name and url must not be empty".to_string());
+    }
+
+    let mut backends = settings_db.get_backends().await.map_err(|e| e.to_string())?;
+    match backends.iter_mut().find(|b| b.name == name) {
+        Some(backend) => backend.url = url,
+        None => backends.push(Backend { name, url }),
+    }
+    settings_db
+        .update_setting("backends", backends)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_backend(settings_db: State<'_, SettingsDatabase>, name: String) -> Result<(), String> {
+    let mut backends = settings_db.get_backends().await.map_err(|e| e.to_string())?;
+    backends.retain(|b| b.name != name);
+    settings_db
+        .update_setting("backends", backends)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let active = settings_db.get_setting("active_backend").await.map_err(|e| e.to_string())?;
+    if active == name {
+        settings_db
+            .update_setting("active_backend", "")
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn list_backends(settings_db: State<'_, SettingsDatabase>) -> Result<Vec<Backend>, String> {
+    settings_db.get_backends().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn ping_backends(settings_db: State<'_, SettingsDatabase>) -> Result<Vec<BackendStatus>, String> {
+    let backends = settings_db.get_backends().await.map_err(|e| e.to_string())?;
+    let urls: Vec<String> = backends.iter().map(|b| b.url.clone()).collect();
+    let results = ping_urls_helper(&urls, DEFAULT_PING_TIMEOUT).await;
+
+    Result::<Vec<BackendStatus>, String>::Ok(backends
+        .into_iter()
+        .map(|backend| {
+            let latency_ms = results
+                .iter()
+                .find(|result| result.url == backend.url)
+                .and_then(|result| result.latency_ms)
+                .map(u128::from);
+            BackendStatus { name: backend.name, url: backend.url, latency_ms }
+        })
+        .collect())
+}
+
+/// Pings the default API, the stored URL and every configured backend and
+/// stores the fastest one that responds as the API URL. The stored URL is
+/// kept when none respond. Returns the API URL in use afterwards.
+async fn select_fastest_api(settings_db: &SettingsDatabase) -> Result<String> {
+    let current = settings_db.get_setting("api_url").await?;
+    let mut urls = vec![Settings::default().api_url, current.clone()];
+    urls.extend(settings_db.get_backends().await?.into_iter().map(|b| b.url));
+    urls.sort();
+    urls.dedup();
+
+    let results = ping_urls_helper(&urls, DEFAULT_PING_TIMEOUT).await;
+    let reachable = results.iter().filter(|result| result.reachable).count();
+    let Some(fastest) = results.iter().find(|result| result.reachable) else {
+        log::warn!("None of {} API mirrors responded, keeping {}", urls.len(), current);
+        return Ok(current);
+    };
+
+    log::info!(
+        "Selected API mirror {}: fastest of {} reachable out of {} ({} ms)",
+        fastest.url,
+        reachable,
+        urls.len(),
+        fastest.latency_ms.unwrap_or_default()
+    );
+    if fastest.url != current {
+        settings_db.update_setting("api_url", &fastest.url).await?;
+    }
+    Ok(fastest.url.clone())
+}
+
+/// Picks the fastest API mirror at startup when `auto_select_api` is on.
+/// An active backend chosen by the user is left alone.
+pub fn auto_select_api(app: &tauri::App) {
+    let app_handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let settings_db = app_handle.state::<SettingsDatabase>();
+        let enabled = settings_db
+            .get_setting("auto_select_api")
+            .await
+            .is_ok_and(|value| value == "true");
+        if !enabled {
+            return;
+        }
+        let active = settings_db.get_setting("active_backend").await.unwrap_or_default();
+        if !active.is_empty() {
+            log::info!("Not selecting an API mirror, backend {} is active", active);
+            return;
+        }
+        if let Err(e) = select_fastest_api(&settings_db).await {
+            log::warn!("Failed to select an API mirror: {}", e);
+        }
+    });
+}
+
+/// Re-runs the mirror selection on request, regardless of `auto_select_api`.
+#[tauri::command]
+pub async fn refresh_api_url(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
+    select_fastest_api(&settings_db).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_active_backend(settings_db: State<'_, SettingsDatabase>, name: String) -> Result<(), String> {
+    let backends = settings_db.get_backends().await.map_err(|e| e.to_string())?;
+    if !name.is_empty() && !backends.iter().any(|b| b.name == name) {
+        return Err(format!("Unknown backend: {}", name));
+    }
+    settings_db
+        .update_setting("active_backend", name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_active_backend(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
+    settings_db
+        .get_setting("active_backend")
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the url of the active backend, or the plain `api_url` setting when
+/// no backend is selected.
 #[tauri::command]
 pub async fn get_api_url(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
+    let active = settings_db.get_setting("active_backend").await.map_err(|e| e.to_string())?;
+    if !active.is_empty() {
+        let backends = settings_db.get_backends().await.map_err(|e| e.to_string())?;
+        if let Some(backend) = backends.into_iter().find(|b| b.name == active) {
+            return Result::<String, String>::Ok(backend.url);
+        }
+    }
+
     settings_db
         .get_setting("api_url")
         .await
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_auto_select_api(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("auto_select_api")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_balance(settings_db: State<'_, SettingsDatabase>) -> Result<f64, String> {
+    settings_db
+        .get_setting("balance")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_close_to_tray(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("close_to_tray")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_crossfade_ms(settings_db: State<'_, SettingsDatabase>) -> Result<u32, String> {
+    settings_db
+        .get_setting("crossfade_ms")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_crossfade_on_skip(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("crossfade_on_skip")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
 #[tauri::command]
 pub async fn get_current_song(settings_db: State<'_, SettingsDatabase>) -> Result<Option<Song>, String> {
     settings_db
@@ -87,6 +628,24 @@ pub async fn get_current_song(settings_db: State<'_, SettingsDatabase>) -> Resul
         .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
+#[tauri::command]
+pub async fn get_discord_rpc_enabled(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("discord_rpc_enabled")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_download_bandwidth_limit_kbps(settings_db: State<'_, SettingsDatabase>) -> Result<u32, String> {
+    settings_db
+        .get_setting("download_bandwidth_limit_kbps")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
 #[tauri::command]
 pub async fn get_eq(settings_db: State<'_, SettingsDatabase>) -> Result<EQSettings, String> {
     settings_db
@@ -97,171 +656,1232 @@ pub async fn get_eq(settings_db: State<'_, SettingsDatabase>) -> Result<EQSettin
 }
 
 #[tauri::command]
-pub async fn get_lossless(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+pub async fn get_fade_duration_ms(settings_db: State<'_, SettingsDatabase>) -> Result<u32, String> {
     settings_db
-        .get_setting("lossless")
+        .get_setting("fade_duration_ms")
         .await
         .map_err(|e| e.to_string())
         .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn get_loop(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+pub async fn get_gapless(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
     settings_db
-        .get_setting("loop")
+        .get_setting("gapless")
         .await
         .map_err(|e| e.to_string())
         .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn get_muted(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+pub async fn get_keep_streamed(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
     settings_db
-        .get_setting("muted")
+        .get_setting("keep_streamed")
         .await
         .map_err(|e| e.to_string())
         .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn get_queue(settings_db: State<'_, SettingsDatabase>) -> Result<Vec<Song>, String> {
+pub async fn get_lastfm_enabled(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
     settings_db
-        .get_setting("queue")
+        .get_setting("lastfm_enabled")
         .await
         .map_err(|e| e.to_string())
         .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn get_shuffle(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+pub async fn get_lastfm_username(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
     settings_db
-        .get_setting("shuffle")
+        .get_setting("lastfm_username")
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_level_meter(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("level_meter")
         .await
         .map_err(|e| e.to_string())
         .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn get_streaming(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+pub async fn get_log_level(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
     settings_db
-        .get_setting("streaming")
+        .get_setting("log_level")
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_log_max_size_mb(settings_db: State<'_, SettingsDatabase>) -> Result<u64, String> {
+    settings_db
+        .get_setting("log_max_size_mb")
         .await
         .map_err(|e| e.to_string())
         .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn get_volume(settings_db: State<'_, SettingsDatabase>) -> Result<f64, String> {
-    let value = settings_db.get_setting("volume").await
-        .map_err(|e| e.to_string())?;
-    serde_json::from_str(&value)
+pub async fn get_log_retention(settings_db: State<'_, SettingsDatabase>) -> Result<usize, String> {
+    settings_db
+        .get_setting("log_retention")
+        .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn set_api_url(settings_db: State<'_, SettingsDatabase>, api_url: String) -> Result<(), String> {
+pub async fn get_lossless(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
     settings_db
-        .update_setting("api_url", api_url)
+        .get_setting("lossless")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn set_current_song(
-    settings_db: State<'_, SettingsDatabase>,
-    current_song: Option<Song>,
-) -> Result<(), String> {
+pub async fn get_loop(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
     settings_db
-        .update_setting("current_song", current_song)
+        .get_setting("loop")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn set_eq(settings_db: State<'_, SettingsDatabase>, eq: EQSettings) -> Result<(), String> {
+pub async fn get_lyrics_api_url(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
     settings_db
-        .update_setting("eq", eq)
+        .get_setting("lyrics_api_url")
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn set_lossless(settings_db: State<'_, SettingsDatabase>, lossless: bool) -> Result<(), String> {
+pub async fn get_max_audio_memory_mb(settings_db: State<'_, SettingsDatabase>) -> Result<u32, String> {
     settings_db
-        .update_setting("lossless", lossless)
+        .get_setting("max_audio_memory_mb")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn set_loop(settings_db: State<'_, SettingsDatabase>, r#loop: bool) -> Result<(), String> {
+pub async fn get_max_concurrent_downloads(settings_db: State<'_, SettingsDatabase>) -> Result<u32, String> {
     settings_db
-        .update_setting("loop", r#loop)
+        .get_setting("max_concurrent_downloads")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn set_muted(settings_db: State<'_, SettingsDatabase>, muted: bool) -> Result<(), String> {
+pub async fn get_media_keys(settings_db: State<'_, SettingsDatabase>) -> Result<MediaKeyBindings, String> {
     settings_db
-        .update_setting("muted", muted)
+        .get_setting("media_keys")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn set_queue(settings_db: State<'_, SettingsDatabase>, queue: Vec<Song>) -> Result<(), String> {
+pub async fn get_min_silence_ms(settings_db: State<'_, SettingsDatabase>) -> Result<u32, String> {
     settings_db
-        .update_setting("queue", queue)
+        .get_setting("min_silence_ms")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn set_shuffle(settings_db: State<'_, SettingsDatabase>, shuffle: bool) -> Result<(), String> {
+pub async fn get_mono(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
     settings_db
-        .update_setting("shuffle", shuffle)
+        .get_setting("mono")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
 }
 
 #[tauri::command]
-pub async fn set_streaming(settings_db: State<'_, SettingsDatabase>, streaming: bool) -> Result<(), String> {
+pub async fn get_muted(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
     settings_db
-        .update_setting("streaming", streaming)
+        .get_setting("muted")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+impl SettingsDatabase {
+    async fn get_queue_ids(&self) -> Result<Vec<String>> {
+        let queue = self.get_setting("queue").await?;
+        Ok(parse_queue(&queue)?)
+    }
+
+    async fn get_queue_pins(&self) -> Result<Vec<String>> {
+        let pins = self.get_setting("queue_pins").await?;
+        Ok(serde_json::from_str(&pins)?)
+    }
+}
+
+/// The id of the entry at `index` in `get_queue`, which leaves out deleted
+/// songs and repeated ids.
+async fn queue_item_id(
+    music_db: &MusicDatabase,
+    settings_db: &SettingsDatabase,
+    index: usize,
+) -> Result<String, String> {
+    let ids = settings_db.get_queue_ids().await.map_err(|e| e.to_string())?;
+    let mut found = music_db.find_songs(&ids).await.map_err(|e| e.to_string())?;
+    ids.into_iter()
+        .filter(|id| found.remove(id).is_some())
+        .nth(index)
+        .ok_or_else(|| format!("No queue entry at index {}", index))
 }
 
 #[tauri::command]
-pub async fn set_volume(settings_db: State<'_, SettingsDatabase>, volume: f64) -> Result<(), String> {
-    let clamped_volume = volume.max(0.0).min(1.0);
+pub async fn get_normalization(settings_db: State<'_, SettingsDatabase>) -> Result<NormalizationMode, String> {
     settings_db
-        .update_setting("volume", clamped_volume)
+        .get_setting("normalization")
         .await
         .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_value(serde_json::Value::String(s)).map_err(|e| e.to_string()))
 }
 
-pub async fn initialize_settings(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
-    let default_settings = Settings::default();
+#[tauri::command]
+pub async fn get_output_device(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
+    settings_db
+        .get_setting("output_device")
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    sqlx::query(
-        "INSERT INTO settings (api_url, current_song, eq, lossless, loop, muted, queue, shuffle, streaming, volume) 
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+#[tauri::command]
+pub async fn get_output_device_id(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
+    settings_db
+        .get_setting("output_device_id")
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_playback_speed(settings_db: State<'_, SettingsDatabase>) -> Result<f64, String> {
+    settings_db
+        .get_setting("playback_speed")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+/// The upcoming songs, looked up from the ids in the `queue` setting. Songs
+/// that have been deleted since they were queued are left out.
+#[tauri::command]
+pub async fn get_queue(
+    music_db: State<'_, MusicDatabase>,
+    settings_db: State<'_, SettingsDatabase>,
+) -> Result<Vec<Song>, String> {
+    let ids = settings_db.get_queue_ids().await.map_err(|e| e.to_string())?;
+    let pins = settings_db.get_queue_pins().await.map_err(|e| e.to_string())?;
+    let mut found = music_db.find_songs(&ids).await.map_err(|e| e.to_string())?;
+    Result::<Vec<Song>, String>::Ok(
+        ids.iter()
+            .filter_map(|id| found.remove(id))
+            .map(|mut song| {
+                song.pinned = pins.contains(&song.id);
+                song
+            })
+            .collect(),
     )
-    .bind(&default_settings.api_url)
-    .bind(serde_json::to_string(&default_settings.current_song).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?)
-    .bind(serde_json::to_string(&default_settings.eq).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?)
-    .bind(default_settings.lossless)
-    .bind(default_settings.r#loop)
-    .bind(default_settings.muted)
-    .bind(serde_json::to_string(&default_settings.queue).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?)
-    .bind(default_settings.shuffle)
-    .bind(default_settings.streaming)
-    .bind(default_settings.volume)
-    .execute(pool)
-    .await
-    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+}
 
-    Result::<(), Box<dyn std::error::Error>>::Ok(())
+/// The ids in the `queue` setting. Older versions stored whole songs there,
+/// of which only the ids are kept.
+pub(crate) fn parse_queue(raw: &str) -> Result<Vec<String>, serde_json::Error> {
+    let entries: Vec<serde_json::Value> = serde_json::from_str(raw)?;
+    Result::<Vec<String>, serde_json::Error>::Ok(
+        entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                serde_json::Value::String(id) => Some(id),
+                serde_json::Value::Object(song) => song.get("id")?.as_str().map(str::to_string),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+#[tauri::command]
+pub async fn get_queue_snapshot(settings_db: State<'_, SettingsDatabase>) -> Result<QueueSnapshot, String> {
+    settings_db
+        .get_setting("queue_snapshot")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+/// The settings-backed player state and the playback position last reported
+/// by the player, in one call. The current song is the one playing, or the
+/// `current_song` setting before anything has played.
+#[tauri::command]
+pub async fn get_player_state(settings_db: State<'_, SettingsDatabase>) -> Result<PlayerState, String> {
+    let now_playing = media_controls::current();
+    // The stored current song is only what will play next, nothing is
+    // playing until the player reports a song.
+    let is_playing = now_playing.song.is_some() && !now_playing.paused;
+    let current_song = match now_playing.song {
+        Some(song) => Some(song),
+        None => get_current_song(settings_db.clone()).await?,
+    };
+
+    Result::<PlayerState, String>::Ok(PlayerState {
+        volume: get_volume(settings_db.clone()).await?,
+        muted: get_muted(settings_db.clone()).await?,
+        looping: get_loop(settings_db.clone()).await?,
+        repeat_mode: get_repeat_mode(settings_db.clone()).await?,
+        shuffle: get_shuffle(settings_db).await?,
+        is_playing,
+        current_song,
+        progress: now_playing.position,
+        duration: now_playing.duration,
+    })
+}
+
+#[tauri::command]
+pub async fn get_repeat_mode(settings_db: State<'_, SettingsDatabase>) -> Result<RepeatMode, String> {
+    settings_db
+        .get_setting("repeat_mode")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_value(serde_json::Value::String(s)).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_resume_on_launch(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("resume_on_launch")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_romanize_lyrics(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("romanize_lyrics")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_shuffle(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("shuffle")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_silence_threshold_db(settings_db: State<'_, SettingsDatabase>) -> Result<f64, String> {
+    settings_db
+        .get_setting("silence_threshold_db")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_skip_silence(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("skip_silence")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_smart_resume(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("smart_resume")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_smart_resume_ramp_ms(settings_db: State<'_, SettingsDatabase>) -> Result<u32, String> {
+    settings_db
+        .get_setting("smart_resume_ramp_ms")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_streaming(settings_db: State<'_, SettingsDatabase>) -> Result<bool, String> {
+    settings_db
+        .get_setting("streaming")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+}
+
+#[tauri::command]
+pub async fn get_volume(settings_db: State<'_, SettingsDatabase>) -> Result<f64, String> {
+    stored_volume(&settings_db).await
+}
+
+/// The stored volume, clamped in case it was written before `set_volume`
+/// validated its input.
+async fn stored_volume(settings_db: &SettingsDatabase) -> Result<f64, String> {
+    let value = settings_db.get_setting("volume").await
+        .map_err(|e| e.to_string())?;
+    let volume: f64 = serde_json::from_str(&value)
+        .map_err(|e| e.to_string())?;
+    Result::<f64, String>::Ok(if volume.is_finite() { volume.clamp(0.0, 1.0) } else { 0.0 })
+}
+
+#[tauri::command]
+pub async fn set_api_url(settings_db: State<'_, SettingsDatabase>, api_url: String) -> Result<(), String> {
+    settings_db
+        .update_setting("api_url", api_url)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_auto_select_api(settings_db: State<'_, SettingsDatabase>, auto_select_api: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("auto_select_api", auto_select_api)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_balance(settings_db: State<'_, SettingsDatabase>, balance: f64) -> Result<(), String> {
+    // -1.0 is full left, 1.0 full right.
+    let clamped_balance = if balance.is_finite() { balance.clamp(-1.0, 1.0) } else { 0.0 };
+    settings_db
+        .update_setting("balance", clamped_balance)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_close_to_tray(settings_db: State<'_, SettingsDatabase>, close_to_tray: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("close_to_tray", close_to_tray)
+        .await
+        .map_err(|e| e.to_string())?;
+    tray::set_close_to_tray(close_to_tray);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_crossfade_ms(settings_db: State<'_, SettingsDatabase>, crossfade_ms: u32) -> Result<(), String> {
+    settings_db
+        .update_setting("crossfade_ms", crossfade_ms)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_crossfade_on_skip(settings_db: State<'_, SettingsDatabase>, crossfade_on_skip: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("crossfade_on_skip", crossfade_on_skip)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_current_song(
+    settings_db: State<'_, SettingsDatabase>,
+    current_song: Option<Song>,
+) -> Result<(), String> {
+    settings_db
+        .update_setting("current_song", current_song)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_discord_rpc_enabled(settings_db: State<'_, SettingsDatabase>, discord_rpc_enabled: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("discord_rpc_enabled", discord_rpc_enabled)
+        .await
+        .map_err(|e| e.to_string())?;
+    discord_rpc::set_discord_enabled(discord_rpc_enabled);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_download_bandwidth_limit_kbps(settings_db: State<'_, SettingsDatabase>, download_bandwidth_limit_kbps: u32) -> Result<(), String> {
+    settings_db
+        .update_setting("download_bandwidth_limit_kbps", download_bandwidth_limit_kbps)
+        .await
+        .map_err(|e| e.to_string())?;
+    set_download_limit(download_bandwidth_limit_kbps);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_eq(settings_db: State<'_, SettingsDatabase>, eq: EQSettings) -> Result<(), String> {
+    store_eq(&settings_db, eq).await.map(|_| ())
+}
+
+async fn store_eq(settings_db: &SettingsDatabase, mut eq: EQSettings) -> Result<EQSettings, String> {
+    eq.preamp_db = if eq.preamp_db.is_finite() {
+        eq.preamp_db.clamp(-EQ_MAX_GAIN, EQ_MAX_GAIN)
+    } else {
+        0.0
+    };
+    settings_db
+        .update_setting("eq", &eq)
+        .await
+        .map_err(|e| e.to_string())?;
+    Result::<EQSettings, String>::Ok(eq)
+}
+
+/// Parses an AutoEQ-style `GraphicEQ: 20 -1.2; 21 -1.1; ...` curve and fits it
+/// onto the player's bands, interpolating on a log frequency scale. A
+/// `Preamp: -6.2 dB` line sets the preamp, and every band is enabled since
+/// the curve covers all of them.
+#[tauri::command]
+pub async fn import_eq_curve(
+    app: AppHandle,
+    settings_db: State<'_, SettingsDatabase>,
+    text: String,
+) -> Result<EQSettings, String> {
+    let curve = parse_eq_curve(&text)?;
+    let mut eq: EQSettings = settings_db
+        .get_setting("eq")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))?;
+
+    eq.values = EQ_FREQUENCIES
+        .iter()
+        .map(|&freq| {
+            let gain = interpolate_gain(&curve.points, freq).clamp(-EQ_MAX_GAIN, EQ_MAX_GAIN);
+            (freq.to_string(), format!("{:.1}", gain))
+        })
+        .collect();
+    eq.band_enabled = vec![true; EQ_FREQUENCIES.len()];
+    if let Some(preamp_db) = curve.preamp_db {
+        eq.preamp_db = preamp_db;
+    }
+
+    let eq = store_eq(&settings_db, eq).await?;
+    app.emit("eq-change", &eq).map_err(|e| e.to_string())?;
+
+    Result::<EQSettings, String>::Ok(eq)
+}
+
+/// The current EQ as a `Preamp:` line and a `GraphicEQ:` curve, which
+/// `import_eq_curve` reads back to the same sound.
+#[tauri::command]
+pub async fn export_eq_curve(settings_db: State<'_, SettingsDatabase>) -> Result<String, String> {
+    let eq: EQSettings = settings_db
+        .get_setting("eq")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))?;
+
+    Result::<String, String>::Ok(format_eq_curve(&eq))
+}
+
+/// Disabled bands are written as flat, since that is how they sound.
+fn format_eq_curve(eq: &EQSettings) -> String {
+    let bands: Vec<String> = EQ_FREQUENCIES
+        .iter()
+        .enumerate()
+        .map(|(index, freq)| {
+            let enabled = eq.band_enabled.get(index).copied().unwrap_or(true);
+            let gain = eq
+                .values
+                .get(&freq.to_string())
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|_| enabled)
+                .unwrap_or(0.0);
+            format!("{} {:.1}", freq, gain)
+        })
+        .collect();
+
+    format!("Preamp: {:.1} dB\nGraphicEQ: {}", eq.preamp_db, bands.join("; "))
+}
+
+/// Built-in presets first, then the user's, each sorted by name.
+#[tauri::command]
+pub async fn get_eq_presets(settings_db: State<'_, SettingsDatabase>) -> Result<Vec<EqPreset>, String> {
+    let rows: Vec<(String, String, bool)> = sqlx::query_as(
+        "SELECT name, gains, builtin FROM eq_presets ORDER BY builtin DESC, name COLLATE NOCASE"
+    )
+    .fetch_all(&settings_db.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    rows.into_iter()
+        .map(|(name, gains, builtin)| {
+            let values = serde_json::from_str(&gains)
+                .map_err(|e| format!("Invalid gains in EQ preset {}: {}", name, e))?;
+            Result::<EqPreset, String>::Ok(EqPreset { name, values, builtin })
+        })
+        .collect()
+}
+
+/// Creates a preset or overwrites one of the user's. Gains are clamped to
+/// what the equalizer supports.
+#[tauri::command]
+pub async fn save_eq_preset(
+    settings_db: State<'_, SettingsDatabase>,
+    name: String,
+    values: HashMap<String, String>,
+) -> Result<EqPreset, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("EQ preset name can't be empty".to_string());
+    }
+    let values = normalize_eq_values(&values)?;
+    let gains = serde_json::to_string(&values).map_err(|e| e.to_string())?;
+
+    let result = sqlx::query(
+        "INSERT INTO eq_presets (name, gains) VALUES (?, ?)
+         ON CONFLICT(name) DO UPDATE SET gains = excluded.gains WHERE builtin = 0"
+    )
+    .bind(&name)
+    .bind(gains)
+    .execute(&settings_db.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if result.rows_affected() == 0 {
+        return Err(format!("{} is a built-in EQ preset and can't be changed", name));
+    }
+
+    Result::<EqPreset, String>::Ok(EqPreset { name, values, builtin: false })
+}
+
+/// Sets the preset's gains as the current EQ, keeping the preamp and the
+/// enabled bands, and tells the player through `eq-change`.
+#[tauri::command]
+pub async fn apply_eq_preset(
+    app: AppHandle,
+    settings_db: State<'_, SettingsDatabase>,
+    name: String,
+) -> Result<EQSettings, String> {
+    let gains: Option<String> = sqlx::query_scalar("SELECT gains FROM eq_presets WHERE name = ?")
+        .bind(&name)
+        .fetch_optional(&settings_db.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let gains = gains.ok_or_else(|| format!("No EQ preset named {}", name))?;
+
+    let mut eq: EQSettings = settings_db
+        .get_setting("eq")
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))?;
+    eq.values = serde_json::from_str(&gains)
+        .map_err(|e| format!("Invalid gains in EQ preset {}: {}", name, e))?;
+
+    let eq = store_eq(&settings_db, eq).await?;
+    app.emit("eq-change", &eq).map_err(|e| e.to_string())?;
+
+    Result::<EQSettings, String>::Ok(eq)
+}
+
+#[tauri::command]
+pub async fn delete_eq_preset(settings_db: State<'_, SettingsDatabase>, name: String) -> Result<(), String> {
+    let builtin: Option<bool> = sqlx::query_scalar("SELECT builtin FROM eq_presets WHERE name = ?")
+        .bind(&name)
+        .fetch_optional(&settings_db.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    match builtin {
+        None => return Err(format!("No EQ preset named {}", name)),
+        Some(true) => return Err(format!("{} is a built-in EQ preset and can't be deleted", name)),
+        Some(false) => {}
+    }
+
+    sqlx::query("DELETE FROM eq_presets WHERE name = ? AND builtin = 0")
+        .bind(&name)
+        .execute(&settings_db.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Result::<(), String>::Ok(())
+}
+
+/// Checks that every key is one of the equalizer's bands and every gain a
+/// number, clamped to the supported range.
+fn normalize_eq_values(values: &HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+    let mut normalized = HashMap::new();
+    for (freq, gain) in values {
+        if !EQ_FREQUENCIES.iter().any(|band| band.to_string() == *freq) {
+            return Err(format!("{} Hz is not an EQ band", freq));
+        }
+        let parsed: f64 = gain
+            .replace(',', ".")
+            .parse()
+            .map_err(|_| format!("Invalid gain \"{}\" for {} Hz", gain, freq))?;
+        if !parsed.is_finite() {
+            return Err(format!("Invalid gain \"{}\" for {} Hz", gain, freq));
+        }
+        normalized.insert(freq.clone(), format!("{:.1}", parsed.clamp(-EQ_MAX_GAIN, EQ_MAX_GAIN)));
+    }
+    Result::<HashMap<String, String>, String>::Ok(normalized)
+}
+
+#[tauri::command]
+pub async fn set_fade_duration_ms(settings_db: State<'_, SettingsDatabase>, fade_duration_ms: u32) -> Result<(), String> {
+    settings_db
+        .update_setting("fade_duration_ms", fade_duration_ms)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_gapless(settings_db: State<'_, SettingsDatabase>, gapless: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("gapless", gapless)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_keep_streamed(settings_db: State<'_, SettingsDatabase>, keep_streamed: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("keep_streamed", keep_streamed)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_lastfm_enabled(settings_db: State<'_, SettingsDatabase>, lastfm_enabled: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("lastfm_enabled", lastfm_enabled)
+        .await
+        .map_err(|e| e.to_string())?;
+    scrobble::set_enabled(lastfm_enabled);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_level_meter(settings_db: State<'_, SettingsDatabase>, level_meter: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("level_meter", level_meter)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_log_level(settings_db: State<'_, SettingsDatabase>, level: String) -> Result<(), String> {
+    let filter = logger::parse_level(&level)?;
+    settings_db
+        .update_setting("log_level", filter.to_string().to_lowercase())
+        .await
+        .map_err(|e| e.to_string())?;
+    log::set_max_level(filter);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_log_max_size_mb(settings_db: State<'_, SettingsDatabase>, log_max_size_mb: u64) -> Result<(), String> {
+    let log_max_size_mb = log_max_size_mb.min(logger::MAX_LOG_SIZE_MB);
+    settings_db
+        .update_setting("log_max_size_mb", log_max_size_mb)
+        .await
+        .map_err(|e| e.to_string())?;
+    logger::set_max_size_mb(log_max_size_mb);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_log_retention(settings_db: State<'_, SettingsDatabase>, log_retention: usize) -> Result<(), String> {
+    settings_db
+        .update_setting("log_retention", log_retention)
+        .await
+        .map_err(|e| e.to_string())?;
+    logger::set_retention(log_retention);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_lossless(settings_db: State<'_, SettingsDatabase>, lossless: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("lossless", lossless)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Kept for older callers; `true` maps to repeating the current song.
+#[tauri::command]
+pub async fn set_loop(settings_db: State<'_, SettingsDatabase>, r#loop: bool) -> Result<(), String> {
+    let repeat_mode = if r#loop { RepeatMode::One } else { RepeatMode::Off };
+    set_repeat_mode(settings_db, repeat_mode).await
+}
+
+#[tauri::command]
+pub async fn set_lyrics_api_url(settings_db: State<'_, SettingsDatabase>, lyrics_api_url: String) -> Result<(), String> {
+    settings_db
+        .update_setting("lyrics_api_url", lyrics_api_url)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_max_audio_memory_mb(
+    settings_db: State<'_, SettingsDatabase>,
+    max_audio_memory_mb: u32,
+) -> Result<(), String> {
+    settings_db
+        .update_setting("max_audio_memory_mb", max_audio_memory_mb)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_max_concurrent_downloads(
+    app: AppHandle,
+    settings_db: State<'_, SettingsDatabase>,
+    max_concurrent_downloads: u32,
+) -> Result<(), String> {
+    let max_concurrent_downloads = max_concurrent_downloads.max(1);
+    settings_db
+        .update_setting("max_concurrent_downloads", max_concurrent_downloads)
+        .await
+        .map_err(|e| e.to_string())?;
+    app.state::<DownloadManager>()
+        .set_max_concurrent(&app, max_concurrent_downloads);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_media_keys(
+    app: AppHandle,
+    settings_db: State<'_, SettingsDatabase>,
+    media_keys: MediaKeyBindings,
+) -> Result<(), String> {
+    media_keys::validate(&media_keys)?;
+    settings_db
+        .update_setting("media_keys", &media_keys)
+        .await
+        .map_err(|e| e.to_string())?;
+    media_keys::apply(&app, &media_keys);
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_min_silence_ms(settings_db: State<'_, SettingsDatabase>, min_silence_ms: u32) -> Result<(), String> {
+    settings_db
+        .update_setting("min_silence_ms", min_silence_ms)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_mono(settings_db: State<'_, SettingsDatabase>, mono: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("mono", mono)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the stored volume, which is what unmuting should go back to.
+#[tauri::command]
+pub async fn set_muted(settings_db: State<'_, SettingsDatabase>, muted: bool) -> Result<f64, String> {
+    settings_db
+        .update_setting("muted", muted)
+        .await
+        .map_err(|e| e.to_string())?;
+    stored_volume(&settings_db).await
+}
+
+#[tauri::command]
+pub async fn set_normalization(settings_db: State<'_, SettingsDatabase>, normalization: NormalizationMode) -> Result<(), String> {
+    settings_db
+        .update_setting("normalization", normalization)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_output_device(settings_db: State<'_, SettingsDatabase>, output_device: String) -> Result<(), String> {
+    settings_db
+        .update_setting("output_device", output_device)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_output_device_id(settings_db: State<'_, SettingsDatabase>, output_device_id: String) -> Result<(), String> {
+    settings_db
+        .update_setting("output_device_id", output_device_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_playback_speed(settings_db: State<'_, SettingsDatabase>, playback_speed: f64) -> Result<(), String> {
+    let clamped_speed = playback_speed.max(0.5).min(2.0);
+    settings_db
+        .update_setting("playback_speed", clamped_speed)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_queue(settings_db: State<'_, SettingsDatabase>, queue: Vec<String>) -> Result<(), String> {
+    // Pins of songs that left the queue would otherwise pin them again the
+    // next time they get queued.
+    let mut pins = settings_db.get_queue_pins().await.map_err(|e| e.to_string())?;
+    pins.retain(|id| queue.contains(id));
+    settings_db
+        .update_setting("queue_pins", pins)
+        .await
+        .map_err(|e| e.to_string())?;
+    settings_db
+        .update_setting("queue", queue)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Pins the queue entry at `index`, in `get_queue` order, so `clear_queue`
+/// keeps it.
+#[tauri::command]
+pub async fn pin_queue_item(
+    music_db: State<'_, MusicDatabase>,
+    settings_db: State<'_, SettingsDatabase>,
+    index: usize,
+) -> Result<(), String> {
+    let id = queue_item_id(&music_db, &settings_db, index).await?;
+    let mut pins = settings_db.get_queue_pins().await.map_err(|e| e.to_string())?;
+    if !pins.contains(&id) {
+        pins.push(id);
+    }
+    settings_db
+        .update_setting("queue_pins", pins)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn unpin_queue_item(
+    music_db: State<'_, MusicDatabase>,
+    settings_db: State<'_, SettingsDatabase>,
+    index: usize,
+) -> Result<(), String> {
+    let id = queue_item_id(&music_db, &settings_db, index).await?;
+    let mut pins = settings_db.get_queue_pins().await.map_err(|e| e.to_string())?;
+    pins.retain(|pinned| *pinned != id);
+    settings_db
+        .update_setting("queue_pins", pins)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Empties the queue except for the pinned entries.
+#[tauri::command]
+pub async fn clear_queue(settings_db: State<'_, SettingsDatabase>) -> Result<(), String> {
+    let mut queue = settings_db.get_queue_ids().await.map_err(|e| e.to_string())?;
+    let pins = settings_db.get_queue_pins().await.map_err(|e| e.to_string())?;
+    queue.retain(|id| pins.contains(id));
+    settings_db
+        .update_setting("queue", queue)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Updates only the position in `queue_snapshot`, for the player's periodic
+/// saves while the queue itself stays the same.
+#[tauri::command]
+pub async fn set_queue_position(settings_db: State<'_, SettingsDatabase>, position: f64) -> Result<(), String> {
+    if !position.is_finite() || position < 0.0 {
+        return Err(format!("Invalid position {}", position));
+    }
+    sqlx::query("UPDATE settings SET value = json_set(value, '$.position', ?) WHERE key = 'queue_snapshot'")
+        .bind(position)
+        .execute(&settings_db.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Result::<(), String>::Ok(())
+}
+
+#[tauri::command]
+pub async fn set_queue_snapshot(settings_db: State<'_, SettingsDatabase>, queue_snapshot: QueueSnapshot) -> Result<(), String> {
+    settings_db
+        .update_setting("queue_snapshot", queue_snapshot)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_repeat_mode(settings_db: State<'_, SettingsDatabase>, repeat_mode: RepeatMode) -> Result<(), String> {
+    settings_db
+        .update_setting("repeat_mode", repeat_mode)
+        .await
+        .map_err(|e| e.to_string())?;
+    settings_db
+        .update_setting("loop", repeat_mode == RepeatMode::One)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_resume_on_launch(settings_db: State<'_, SettingsDatabase>, resume_on_launch: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("resume_on_launch", resume_on_launch)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_romanize_lyrics(settings_db: State<'_, SettingsDatabase>, romanize_lyrics: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("romanize_lyrics", romanize_lyrics)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_shuffle(settings_db: State<'_, SettingsDatabase>, shuffle: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("shuffle", shuffle)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_silence_threshold_db(settings_db: State<'_, SettingsDatabase>, silence_threshold_db: f64) -> Result<(), String> {
+    settings_db
+        .update_setting("silence_threshold_db", silence_threshold_db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_skip_silence(settings_db: State<'_, SettingsDatabase>, skip_silence: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("skip_silence", skip_silence)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_smart_resume(settings_db: State<'_, SettingsDatabase>, smart_resume: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("smart_resume", smart_resume)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_smart_resume_ramp_ms(settings_db: State<'_, SettingsDatabase>, smart_resume_ramp_ms: u32) -> Result<(), String> {
+    settings_db
+        .update_setting("smart_resume_ramp_ms", smart_resume_ramp_ms)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_streaming(settings_db: State<'_, SettingsDatabase>, streaming: bool) -> Result<(), String> {
+    settings_db
+        .update_setting("streaming", streaming)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Takes 0.0 to 1.0. Anything outside is clamped and logged, since it most
+/// likely comes from a bug. Returns the volume that was stored.
+#[tauri::command]
+pub async fn set_volume(settings_db: State<'_, SettingsDatabase>, volume: f64) -> Result<f64, String> {
+    if !volume.is_finite() {
+        return Err(format!("Invalid volume {}", volume));
+    }
+    let clamped_volume = volume.clamp(0.0, 1.0);
+    if clamped_volume != volume {
+        log::warn!("Volume {} is out of range, using {}", volume, clamped_volume);
+    }
+    settings_db
+        .update_setting("volume", clamped_volume)
+        .await
+        .map_err(|e| e.to_string())?;
+    Result::<f64, String>::Ok(clamped_volume)
+}
+
+struct EqCurve {
+    preamp_db: Option<f64>,
+    points: Vec<(f64, f64)>,
+}
+
+fn parse_eq_curve(text: &str) -> Result<EqCurve, String> {
+    let mut preamp_db = None;
+    let mut body = String::new();
+    for line in text.lines().map(str::trim) {
+        if let Some(preamp) = line.strip_prefix("Preamp:") {
+            preamp_db = Some(parse_preamp(preamp)?);
+        } else {
+            body.push_str(line.strip_prefix("GraphicEQ:").unwrap_or(line));
+            body.push(' ');
+        }
+    }
+
+    let mut points = Vec::new();
+    for entry in body.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let mut parts = entry.split_whitespace();
+        let (Some(freq), Some(gain), None) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(format!("Invalid EQ band \"{}\"", entry));
+        };
+        let freq: f64 = freq.parse().map_err(|_| format!("Invalid frequency \"{}\"", freq))?;
+        let gain: f64 = gain.parse().map_err(|_| format!("Invalid gain \"{}\"", gain))?;
+        if freq <= 0.0 || !gain.is_finite() {
+            return Err(format!("Invalid EQ band \"{}\"", entry));
+        }
+        points.push((freq, gain));
+    }
+
+    if points.is_empty() {
+        return Err("EQ curve has no bands".to_string());
+    }
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Result::<EqCurve, String>::Ok(EqCurve { preamp_db, points })
+}
+
+/// The value of a `Preamp: -6.2 dB` line, with or without the unit.
+fn parse_preamp(value: &str) -> Result<f64, String> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value)
+        .trim();
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|gain| gain.is_finite())
+        .ok_or_else(|| format!("Invalid preamp \"{}\"", value))
+}
+
+/// Linear interpolation between the two nearest points on a log2 frequency
+/// axis, holding the edge gains outside the curve's range.
+fn interpolate_gain(points: &[(f64, f64)], freq: u32) -> f64 {
+    let target = (freq as f64).log2();
+    let (first, last) = (points[0], points[points.len() - 1]);
+    if target <= first.0.log2() {
+        return first.1;
+    }
+    if target >= last.0.log2() {
+        return last.1;
+    }
+
+    let upper = points.iter().position(|p| p.0.log2() >= target).unwrap_or(points.len() - 1);
+    let (low, high) = (points[upper - 1], points[upper]);
+    let (low_x, high_x) = (low.0.log2(), high.0.log2());
+    if high_x == low_x {
+        return high.1;
+    }
+    low.1 + (high.1 - low.1) * (target - low_x) / (high_x - low_x)
+}
+
+pub async fn initialize_settings(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
+    let default_settings = Settings::default();
+
+    sqlx::query(
+        "INSERT INTO settings (api_url, current_song, eq, lossless, loop, muted, queue, shuffle, streaming, volume) 
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&default_settings.api_url)
+    .bind(serde_json::to_string(&default_settings.current_song).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?)
+    .bind(serde_json::to_string(&default_settings.eq).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?)
+    .bind(default_settings.lossless)
+    .bind(default_settings.r#loop)
+    .bind(default_settings.muted)
+    .bind(serde_json::to_string(&default_settings.queue).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?)
+    .bind(default_settings.shuffle)
+    .bind(default_settings.streaming)
+    .bind(default_settings.volume)
+    .execute(pool)
+    .await
+    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+
+    Result::<(), Box<dyn std::error::Error>>::Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_sorts_unsorted_points() {
+        let curve = parse_eq_curve("GraphicEQ: 1000 2; 20 -3; 200 0.5").unwrap();
+        assert_eq!(curve.points, vec![(20.0, -3.0), (200.0, 0.5), (1000.0, 2.0)]);
+        assert_eq!(curve.preamp_db, None);
+    }
+
+    #[test]
+    fn parses_the_preamp_line() {
+        let curve = parse_eq_curve("Preamp: -6.2 dB\nGraphicEQ: 20 -1; 100 2").unwrap();
+        assert_eq!(curve.preamp_db, Some(-6.2));
+        assert_eq!(curve.points, vec![(20.0, -1.0), (100.0, 2.0)]);
+
+        assert_eq!(parse_eq_curve("Preamp: 3\n100 1").unwrap().preamp_db, Some(3.0));
+        assert!(parse_eq_curve("Preamp: loud\n100 1").is_err());
+    }
+
+    #[test]
+    fn exported_curve_reads_back_the_same() {
+        let mut eq = Settings::default().eq;
+        eq.values.insert("64".to_string(), "4.5".to_string());
+        eq.values.insert("1000".to_string(), "-3.0".to_string());
+        eq.values.insert("8000".to_string(), "6.0".to_string());
+        eq.band_enabled[EQ_FREQUENCIES.iter().position(|&f| f == 8000).unwrap()] = false;
+        eq.preamp_db = -2.5;
+
+        let curve = parse_eq_curve(&format_eq_curve(&eq)).unwrap();
+        assert_eq!(curve.preamp_db, Some(-2.5));
+        for &freq in &EQ_FREQUENCIES {
+            let expected = match freq {
+                64 => 4.5,
+                1000 => -3.0,
+                _ => 0.0,
+            };
+            assert_eq!(interpolate_gain(&curve.points, freq), expected, "{} Hz", freq);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_bands() {
+        assert!(parse_eq_curve("").is_err());
+        assert!(parse_eq_curve("GraphicEQ:").is_err());
+        assert!(parse_eq_curve("100").is_err());
+        assert!(parse_eq_curve("100 1 2").is_err());
+        assert!(parse_eq_curve("0 1").is_err());
+        assert!(parse_eq_curve("100 loud").is_err());
+        assert!(parse_eq_curve("100 NaN").is_err());
+    }
+
+    #[test]
+    fn holds_edge_gains_outside_the_curve() {
+        let points = [(100.0, -2.0), (1000.0, 4.0)];
+        assert_eq!(interpolate_gain(&points, 20), -2.0);
+        assert_eq!(interpolate_gain(&points, 100), -2.0);
+        assert_eq!(interpolate_gain(&points, 1000), 4.0);
+        assert_eq!(interpolate_gain(&points, 20000), 4.0);
+    }
+
+    #[test]
+    fn interpolates_on_a_log_axis() {
+        let points = [(100.0, 0.0), (400.0, 6.0)];
+        // 200 Hz is halfway between 100 and 400 Hz in octaves.
+        assert!((interpolate_gain(&points, 200) - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn single_point_applies_everywhere() {
+        let points = [(1000.0, 1.5)];
+        for freq in [20, 1000, 20000] {
+            assert_eq!(interpolate_gain(&points, freq), 1.5);
+        }
+    }
 }
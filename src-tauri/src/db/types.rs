@@ -6,6 +6,23 @@ use serde_with::{serde_as, DisplayFromStr};
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EQSettings {
     pub values: HashMap<String, String>,
+    #[serde(default = "default_band_enabled")]
+    pub band_enabled: Vec<bool>,
+    #[serde(default)]
+    pub preamp_db: f64,
+}
+
+fn default_band_enabled() -> Vec<bool> {
+    vec![true; 10]
+}
+
+/// Named band gains, in the same form as `EQSettings::values`. Built-in
+/// presets ship with the app and can't be changed or deleted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EqPreset {
+    pub name: String,
+    pub values: HashMap<String, String>,
+    pub builtin: bool,
 }
 
 #[serde_as]
@@ -19,6 +36,24 @@ pub struct Song {
     #[serde_as(as = "DisplayFromStr")]
     pub date_added: DateTime<Utc>,
     pub duration: i64, 
+    #[serde(default)]
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub bpm: Option<f64>,
+    #[serde(default)]
+    pub play_count: i64,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub last_played: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub liked: bool,
+    /// Where the file lives when it was indexed in place rather than copied
+    /// into `Songs/`.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Only meaningful in `get_queue`, pinned entries survive `clear_queue`.
+    #[serde(default)]
+    pub pinned: bool,
 }
 
 #[serde_as]
@@ -41,6 +76,17 @@ pub struct Playlist {
     #[serde_as(as = "DisplayFromStr")]
     pub date_created: DateTime<Utc>,
     pub songs: Vec<Song>,
+    /// Base64 encoded cover image, if one was set.
+    #[serde(default)]
+    pub cover: Option<String>,
+}
+
+/// A cover image given either as a path to an image file or as its bytes.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ImageSource {
+    Path(String),
+    Bytes(Vec<u8>),
 }
 
 #[serde_as]
@@ -52,20 +98,356 @@ pub struct History {
     pub song: Song,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Backend {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackendStatus {
+    pub name: String,
+    pub url: String,
+    pub latency_ms: Option<u128>,
+}
+
+/// Why a ping failed. DNS failures mean the host doesn't resolve at all,
+/// which usually isn't fixed by retrying.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PingFailure {
+    InvalidUrl,
+    Dns,
+    Timeout,
+    Connection,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PingResult {
+    pub url: String,
+    pub reachable: bool,
+    pub latency_ms: Option<u64>,
+    pub failure: Option<PingFailure>,
+}
+
+/// Global shortcuts for the media actions, in the global shortcut plugin's
+/// accelerator syntax. `None` leaves the action unbound.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MediaKeyBindings {
+    pub play_pause: Option<String>,
+    pub next: Option<String>,
+    pub previous: Option<String>,
+    pub stop: Option<String>,
+}
+
+impl Default for MediaKeyBindings {
+    fn default() -> Self {
+        MediaKeyBindings {
+            play_pause: Some("MediaPlayPause".to_string()),
+            next: Some("MediaTrackNext".to_string()),
+            previous: Some("MediaTrackPrevious".to_string()),
+            stop: Some("MediaStop".to_string()),
+        }
+    }
+}
+
+/// Everything the player UI needs to rebuild itself, from `get_player_state`.
+/// `progress` and `duration` are in seconds.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlayerState {
+    pub volume: f64,
+    pub muted: bool,
+    pub looping: bool,
+    pub repeat_mode: RepeatMode,
+    pub shuffle: bool,
+    pub current_song: Option<Song>,
+    pub progress: f64,
+    pub duration: f64,
+    pub is_playing: bool,
+}
+
+/// The play queue as `queue_snapshot` stores it: every song by id, the ones
+/// already played included, and where playback of the current one was.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QueueSnapshot {
+    pub song_ids: Vec<String>,
+    pub current_index: i64,
+    pub position: f64,
+}
+
+impl Default for QueueSnapshot {
+    fn default() -> Self {
+        QueueSnapshot {
+            song_ids: Vec::new(),
+            current_index: -1,
+            position: 0.0,
+        }
+    }
+}
+
+/// A `QueueSnapshot` with its songs loaded from the library.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RestoredQueue {
+    pub songs: Vec<Song>,
+    pub current_index: i64,
+    pub position: f64,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
+    pub active_backend: String,
     pub api_url: String,
+    pub auto_select_api: bool,
+    pub backends: Vec<Backend>,
+    pub balance: f64,
+    pub close_to_tray: bool,
+    pub crossfade_ms: u32,
+    pub crossfade_on_skip: bool,
     pub current_song: Option<Song>,
+    pub discord_rpc_enabled: bool,
+    pub download_bandwidth_limit_kbps: u32,
     pub eq: EQSettings,
+    pub fade_duration_ms: u32,
+    pub gapless: bool,
+    pub keep_streamed: bool,
+    pub lastfm_enabled: bool,
+    pub lastfm_session_key: String,
+    pub lastfm_username: String,
+    pub level_meter: bool,
+    pub log_level: String,
+    pub log_max_size_mb: u64,
+    pub log_retention: usize,
     pub lossless: bool,
     pub r#loop: bool,
+    pub lyrics_api_url: String,
+    pub max_audio_memory_mb: u32,
+    pub max_concurrent_downloads: u32,
+    pub media_keys: MediaKeyBindings,
+    pub min_silence_ms: u32,
+    pub mono: bool,
     pub muted: bool,
-    pub queue: Vec<Song>,
+    pub normalization: NormalizationMode,
+    pub output_device: String,
+    pub output_device_id: String,
+    pub playback_speed: f64,
+    pub queue: Vec<String>,
+    pub queue_pins: Vec<String>,
+    pub queue_snapshot: QueueSnapshot,
+    pub repeat_mode: RepeatMode,
+    pub resume_on_launch: bool,
+    pub romanize_lyrics: bool,
     pub shuffle: bool,
+    pub silence_threshold_db: f64,
+    pub skip_silence: bool,
+    pub smart_resume: bool,
+    pub smart_resume_ramp_ms: u32,
     pub streaming: bool,
     pub volume: f64,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArtistCount {
+    pub artist: String,
+    pub count: i64,
+}
+
+/// An album as found in the library, grouped from the songs' album and
+/// artist tags.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlbumSummary {
+    pub id: String,
+    pub name: String,
+    pub artist: String,
+    pub song_count: i64,
+    /// Cover of one of the album's songs.
+    pub cover: String,
+}
+
+/// The day, week or month `get_listening_stats` covers, in local time. Weeks
+/// start on Monday.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SongPlays {
+    pub song: Song,
+    pub plays: i64,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ListeningStats {
+    #[serde_as(as = "DisplayFromStr")]
+    pub from: DateTime<Utc>,
+    #[serde_as(as = "DisplayFromStr")]
+    pub to: DateTime<Utc>,
+    pub plays: i64,
+    /// Sum of the durations of every song played, in seconds.
+    pub total_seconds: i64,
+    pub top_artists: Vec<ArtistCount>,
+    pub top_songs: Vec<SongPlays>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GenreCount {
+    pub genre: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BrokenPlaylist {
+    pub playlist_id: String,
+    pub missing_song_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileMetadata {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    pub duration: i64,
+    pub cover: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct M3uExportReport {
+    pub written: usize,
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct M3uImportReport {
+    pub playlist_id: String,
+    pub added: usize,
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ScanSummary {
+    pub added: usize,
+    pub skipped: usize,
+    pub failed: Vec<String>,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LibraryRebuildReport {
+    pub recovered: usize,
+    pub existing: usize,
+    pub failed: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SongFilter {
+    pub query: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SongPage {
+    pub songs: Vec<Song>,
+    pub total: i64,
+}
+
+/// A quiet part of a song, in milliseconds from its start.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct SilentRange {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// One line of lyrics. `time_ms` is when it starts, or `None` for plain lyrics.
+/// `romanized` is the line in Latin letters, set only when romanizing is on
+/// and the line has something to romanize.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LyricLine {
+    pub time_ms: Option<u64>,
+    pub text: String,
+    #[serde(default)]
+    pub romanized: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lyrics {
+    pub synced: bool,
+    pub lines: Vec<LyricLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SongSort {
+    #[default]
+    Title,
+    Artist,
+    Album,
+    DateAdded,
+    PlayCount,
+}
+
+impl SongSort {
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            SongSort::Title => "title COLLATE NOCASE, id",
+            SongSort::Artist => "artist COLLATE NOCASE, title COLLATE NOCASE, id",
+            SongSort::Album => "album COLLATE NOCASE, title COLLATE NOCASE, id",
+            SongSort::DateAdded => "added_at DESC, id",
+            SongSort::PlayCount => "play_count DESC, title COLLATE NOCASE, id",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+impl NormalizationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NormalizationMode::Off => "off",
+            NormalizationMode::Track => "track",
+            NormalizationMode::Album => "album",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    One,
+    All,
+}
+
+impl RepeatMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepeatMode::Off => "off",
+            RepeatMode::One => "one",
+            RepeatMode::All => "all",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ReplayGain {
+    pub track_gain_db: Option<f64>,
+    pub album_gain_db: Option<f64>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SongRow {
     pub id: String,
@@ -75,6 +457,12 @@ pub struct SongRow {
     pub cover: String,
     pub date_added: String,  
     pub duration: i64, 
+    pub genre: Option<String>,
+    pub bpm: Option<f64>,
+    pub play_count: i64,
+    pub last_played: Option<String>,
+    pub liked: bool,
+    pub path: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -124,6 +512,16 @@ impl From<SongRow> for Song {
                 .unwrap()
                 .with_timezone(&Utc),
             duration: row.duration,
+            genre: row.genre,
+            bpm: row.bpm,
+            play_count: row.play_count,
+            last_played: row
+                .last_played
+                .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+                .map(|date| date.with_timezone(&Utc)),
+            liked: row.liked,
+            path: row.path,
+            pinned: false,
         }
     }
 }
@@ -138,6 +536,12 @@ impl From<Song> for SongRow {
             cover: song.cover,
             date_added: song.date_added.to_rfc3339(),
             duration: song.duration,
+            genre: song.genre,
+            bpm: song.bpm,
+            play_count: song.play_count,
+            last_played: song.last_played.map(|date| date.to_rfc3339()),
+            liked: song.liked,
+            path: song.path,
         }
     }
 }
@@ -145,8 +549,17 @@ impl From<Song> for SongRow {
 impl Settings {
     pub fn default() -> Self {
         Settings {
+            active_backend: String::new(),
             api_url: "https://api.vleer.app".to_string(),
+            auto_select_api: false,
+            backends: Vec::new(),
+            balance: 0.0,
+            close_to_tray: false,
+            crossfade_ms: 0,
+            crossfade_on_skip: true,
             current_song: None,
+            discord_rpc_enabled: true,
+            download_bandwidth_limit_kbps: 0,
             eq: EQSettings {
                 values: [
                     ("32", "0.0"),
@@ -163,12 +576,43 @@ impl Settings {
                 .iter()
                 .map(|(k, v)| (k.to_string(), v.to_string()))
                 .collect(),
+                band_enabled: default_band_enabled(),
+                preamp_db: 0.0,
             },
+            fade_duration_ms: 150,
+            gapless: false,
+            keep_streamed: false,
+            lastfm_enabled: false,
+            lastfm_session_key: String::new(),
+            lastfm_username: String::new(),
+            level_meter: false,
+            log_level: "info".to_string(),
+            log_max_size_mb: 5,
+            log_retention: 3,
             lossless: true,
             r#loop: false,
+            lyrics_api_url: "https://lrclib.net/api".to_string(),
+            max_audio_memory_mb: 512,
+            max_concurrent_downloads: 3,
+            media_keys: MediaKeyBindings::default(),
+            min_silence_ms: 2000,
+            mono: false,
             muted: false,
+            normalization: NormalizationMode::Off,
+            output_device: String::new(),
+            output_device_id: String::new(),
+            playback_speed: 1.0,
             queue: Vec::new(),
+            queue_pins: Vec::new(),
+            queue_snapshot: QueueSnapshot::default(),
+            repeat_mode: RepeatMode::Off,
+            resume_on_launch: false,
+            romanize_lyrics: false,
             shuffle: false,
+            silence_threshold_db: -50.0,
+            skip_silence: false,
+            smart_resume: false,
+            smart_resume_ramp_ms: 3000,
             streaming: true,
             volume: 0.5,
         }
@@ -7,11 +7,8 @@ mod api;
 mod db;
 mod utils;
 
-use crate::db::{music::MusicDatabase, settings::SettingsDatabase};
-use sqlx::sqlite::SqlitePoolOptions;
 use tauri_plugin_aptabase::{InitOptions, EventTracker};
 use std::env;
-use std::fs;
 use tauri::Manager;
 use tauri_plugin_prevent_default::Flags;
 
@@ -50,82 +47,229 @@ async fn main() {
 
             let _ = app.track_event("app_started", None);
 
-            let db_path = app_data_dir.join("data.db");
-            let is_new_db = !db_path.exists();
-            if is_new_db {
-                fs::File::create(&db_path).expect("Failed to create database file");
-            }
+            // The databases must be managed before setup returns, otherwise a
+            // command invoked early would look up state that doesn't exist yet.
+            db::database::setup(app)?;
+            app.manage(api::commands::DownloadTokens::default());
+            app.manage(utils::commands::ScanToken::default());
+            api::downloads::setup(app);
+            utils::logger::load_settings(app);
 
-            let db_url = format!("sqlite:{}", db_path.to_str().unwrap());
-            
-            let app_handle = app.handle().clone();
-            let update_handle = app_handle.clone();
+            db::settings::auto_select_api(app);
 
+            let update_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 api::updater::check_for_updates(update_handle).await;
-
-                let pool = SqlitePoolOptions::new()
-                    .max_connections(5)
-                    .connect(&db_url)
-                    .await
-                    .expect("Failed to create pool");
-
-                let music_db = MusicDatabase { pool: pool.clone() };
-                let settings_db = SettingsDatabase { pool };
-
-                app_handle.manage(music_db);
-                app_handle.manage(settings_db);
             });
 
-            let _ = db::database::setup(app);
-            api::discord_rpc::connect_rpc().ok();
+            api::tray::setup(app)?;
+            api::media_controls::setup(app);
+            api::media_keys::setup(app);
+            api::discord_rpc::start_connection_manager(app);
+            utils::scrobble::start(app);
 
             Ok(())
         })
+        .on_window_event(api::tray::on_window_event)
         .invoke_handler(tauri::generate_handler![
+            db::database::backup_database,
+            db::database::restore_database,
+            db::export::export_settings,
+            db::export::import_settings,
             db::music::add_playlist,
             db::music::add_song,
             db::music::add_song_to_history,
             db::music::add_song_to_playlist,
+            db::music::add_song_to_playlists,
+            db::music::add_songs_to_playlist,
             db::music::clear_history,
+            db::music::export_playlist_m3u,
+            db::music::get_genres,
+            db::music::get_full_history,
             db::music::get_history,
+            db::music::get_history_range,
+            db::music::get_liked_songs,
+            db::music::get_listening_stats,
+            db::music::get_most_played,
             db::music::get_playlist,
             db::music::get_playlists,
+            db::music::get_recently_added,
+            db::music::get_recently_played,
             db::music::get_song,
             db::music::get_songs,
+            db::music::get_songs_by_bpm_range,
+            db::music::get_songs_by_genre,
+            db::music::get_songs_paged,
+            db::music::get_songs_without_bpm,
+            db::music::import_playlist_m3u,
+            db::music::increment_play_count,
+            db::music::set_song_bpm,
+            db::music::toggle_like,
+            db::music::update_song_duration,
             db::music::remove_song,
             db::music::remove_song_from_history,
             db::music::remove_song_from_playlist,
             db::music::remove_playlist,
+            db::music::rename_playlist,
+            db::music::set_playlist_cover,
             db::music::remove_album,
             db::music::add_album,
             db::music::get_album,
+            db::music::get_albums_by_artist,
+            db::music::get_artists,
+            db::music::get_songs_by_album,
+            db::music::get_album_art,
+            db::music::set_album_art,
+            db::music::queue_filtered,
+            db::music::restore_queue,
+            db::music::search_songs,
+            db::music::rebuild_library_from_files,
+            db::music::validate_playlists,
+            db::music::prune_playlist_broken_entries,
+            db::settings::add_backend,
+            db::settings::apply_eq_preset,
+            db::settings::clear_queue,
+            db::settings::delete_eq_preset,
+            db::settings::export_eq_curve,
+            db::settings::get_active_backend,
+            db::settings::get_all_settings,
             db::settings::get_api_url,
+            db::settings::get_auto_select_api,
+            db::settings::get_balance,
+            db::settings::get_close_to_tray,
+            db::settings::get_crossfade_ms,
+            db::settings::get_crossfade_on_skip,
             db::settings::get_current_song,
+            db::settings::get_discord_rpc_enabled,
+            db::settings::get_download_bandwidth_limit_kbps,
             db::settings::get_eq,
+            db::settings::get_eq_presets,
+            db::settings::get_fade_duration_ms,
+            db::settings::get_gapless,
+            db::settings::get_keep_streamed,
+            db::settings::get_lastfm_enabled,
+            db::settings::get_lastfm_username,
+            db::settings::get_level_meter,
+            db::settings::get_log_level,
+            db::settings::get_log_max_size_mb,
+            db::settings::get_log_retention,
             db::settings::get_lossless,
             db::settings::get_loop,
+            db::settings::get_lyrics_api_url,
+            db::settings::get_max_audio_memory_mb,
+            db::settings::get_max_concurrent_downloads,
+            db::settings::get_media_keys,
+            db::settings::get_min_silence_ms,
+            db::settings::get_mono,
             db::settings::get_muted,
+            db::settings::get_normalization,
+            db::settings::get_output_device,
+            db::settings::get_output_device_id,
+            db::settings::get_playback_speed,
+            db::settings::get_player_state,
             db::settings::get_queue,
+            db::settings::get_queue_snapshot,
+            db::settings::get_repeat_mode,
+            db::settings::get_resume_on_launch,
+            db::settings::get_romanize_lyrics,
             db::settings::get_shuffle,
+            db::settings::get_silence_threshold_db,
+            db::settings::get_skip_silence,
+            db::settings::get_smart_resume,
+            db::settings::get_smart_resume_ramp_ms,
             db::settings::get_streaming,
             db::settings::get_volume,
+            db::settings::import_eq_curve,
+            db::settings::list_backends,
+            db::settings::pin_queue_item,
+            db::settings::ping_backends,
+            db::settings::refresh_api_url,
+            db::settings::remove_backend,
+            db::settings::save_eq_preset,
+            db::settings::set_active_backend,
+            db::settings::set_all_settings,
             db::settings::set_api_url,
+            db::settings::set_auto_select_api,
+            db::settings::set_balance,
+            db::settings::set_close_to_tray,
+            db::settings::set_crossfade_ms,
+            db::settings::set_crossfade_on_skip,
             db::settings::set_current_song,
+            db::settings::set_discord_rpc_enabled,
+            db::settings::set_download_bandwidth_limit_kbps,
             db::settings::set_eq,
+            db::settings::set_fade_duration_ms,
+            db::settings::set_gapless,
+            db::settings::set_keep_streamed,
+            db::settings::set_lastfm_enabled,
+            db::settings::set_level_meter,
+            db::settings::set_log_level,
+            db::settings::set_log_max_size_mb,
+            db::settings::set_log_retention,
             db::settings::set_lossless,
             db::settings::set_loop,
+            db::settings::set_lyrics_api_url,
+            db::settings::set_max_audio_memory_mb,
+            db::settings::set_max_concurrent_downloads,
+            db::settings::set_media_keys,
+            db::settings::set_min_silence_ms,
+            db::settings::set_mono,
             db::settings::set_muted,
+            db::settings::set_normalization,
+            db::settings::set_output_device,
+            db::settings::set_output_device_id,
+            db::settings::set_playback_speed,
             db::settings::set_queue,
+            db::settings::set_queue_position,
+            db::settings::set_queue_snapshot,
+            db::settings::set_repeat_mode,
+            db::settings::set_resume_on_launch,
+            db::settings::set_romanize_lyrics,
             db::settings::set_shuffle,
+            db::settings::set_silence_threshold_db,
+            db::settings::set_skip_silence,
+            db::settings::set_smart_resume,
+            db::settings::set_smart_resume_ramp_ms,
             db::settings::set_streaming,
             db::settings::set_volume,
+            db::settings::unpin_queue_item,
+            api::commands::cancel_download,
             api::commands::download_from_backend,
+            api::commands::export_song,
             api::commands::get_music_path,
+            api::commands::get_replay_gain,
+            api::commands::read_song_file,
             api::commands::ping_urls,
+            api::commands::replace_song_file,
+            api::downloads::clear_download_queue,
+            api::downloads::get_download_queue,
+            api::downloads::queue_download,
+            api::lyrics::get_lyrics,
+            api::media_controls::clear_ab_loop,
+            api::media_controls::seek_relative,
+            api::media_controls::set_ab_loop,
+            api::media_controls::update_now_playing,
+            api::tray::minimize_to_tray,
+            api::visualizer::start_visualizer,
+            api::visualizer::stop_visualizer,
+            utils::commands::cancel_scan,
+            utils::commands::clear_logs,
+            utils::commands::get_recent_logs,
+            utils::commands::open_logs_folder,
+            utils::commands::read_metadata,
+            utils::commands::scan_library,
+            utils::scrobble::lastfm_auth,
+            utils::silence::get_silent_ranges,
+            utils::waveform::generate_waveform,
             api::discord_rpc::clear_activity,
             api::discord_rpc::update_activity,
+            utils::romanize::romanize_text,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                api::media_controls::shutdown();
+            }
+        });
 }
@@ -0,0 +1,311 @@
+use crate::api::commands::{get_music_path, AUDIO_EXTENSIONS};
+use crate::db::music::MusicDatabase;
+use crate::db::types::{FileMetadata, ScanSummary};
+use crate::utils::error::AppError;
+use crate::utils::{logger, metadata};
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_LOG_LINES: usize = 200;
+
+/// Cancellation token of the running library scan. Only one scan runs at a
+/// time, so `cancel_scan` can't stop a scan it wasn't meant for.
+#[derive(Default)]
+pub struct ScanToken {
+    token: Mutex<Option<CancellationToken>>,
+}
+
+impl ScanToken {
+    fn lock(&self) -> MutexGuard<'_, Option<CancellationToken>> {
+        self.token
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn start(&self) -> Result<CancellationToken, AppError> {
+        let mut current = self.lock();
+        if current.is_some() {
+            return Err(AppError::InvalidInput(
+                "A library scan is already running".to_string(),
+            ));
+        }
+        let token = CancellationToken::new();
+        *current = Some(token.clone());
+        Ok(token)
+    }
+
+    fn cancel(&self) -> bool {
+        match self.lock().as_ref() {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn finish(&self) {
+        self.lock().take();
+    }
+}
+
+/// Clears the running scan when it finishes, fails or is cancelled.
+struct ActiveScan<'a>(&'a ScanToken);
+
+impl Drop for ActiveScan<'_> {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct ScanProgress {
+    processed: usize,
+    total: usize,
+    added: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+/// Reads the tags of a file picked for import. Files without a title tag
+/// fall back to the file name.
+#[tauri::command]
+pub async fn read_metadata(path: String) -> Result<FileMetadata, AppError> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err(AppError::FileNotFound(path.display().to_string()));
+    }
+
+    let fallback_title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let metadata = tokio::task::spawn_blocking(move || metadata::read_metadata(&path))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?
+        .map_err(AppError::Decode)?;
+
+    Ok(FileMetadata {
+        title: metadata
+            .title
+            .filter(|title| !title.trim().is_empty())
+            .unwrap_or(fallback_title),
+        artist: metadata.artist,
+        album: metadata.album,
+        genre: metadata.genre,
+        track_number: metadata.track_number,
+        duration: metadata.duration,
+        cover: metadata.cover.map(|data| BASE64_STANDARD.encode(data)),
+    })
+}
+
+/// Adds every audio file under `folder` to the library under an id derived
+/// from its path, so scanning the same folder again skips what was already
+/// added. Files are played from where they are unless `copy` is set, which
+/// copies them into `Songs/`.
+#[tauri::command]
+pub async fn scan_library(
+    app: AppHandle,
+    music_db: State<'_, MusicDatabase>,
+    scan: State<'_, ScanToken>,
+    folder: String,
+    copy: Option<bool>,
+) -> Result<ScanSummary, AppError> {
+    let token = scan.start()?;
+    let _active = ActiveScan(&scan);
+
+    let root = PathBuf::from(folder);
+    let files = tokio::task::spawn_blocking(move || find_audio_files(&root))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))??;
+    let songs_dir = copy
+        .unwrap_or(false)
+        .then(|| get_music_path().join("Songs"));
+    let mut summary = ScanSummary::default();
+
+    for (index, path) in files.iter().enumerate() {
+        if token.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+
+        match import_file(&music_db, path, songs_dir.as_deref()).await {
+            Ok(true) => summary.added += 1,
+            Ok(false) => summary.skipped += 1,
+            Err(e) => summary.failed.push(format!("{}: {}", path.display(), e)),
+        }
+
+        let _ = app.emit(
+            "scan-progress",
+            ScanProgress {
+                processed: index + 1,
+                total: files.len(),
+                added: summary.added,
+                skipped: summary.skipped,
+                failed: summary.failed.len(),
+            },
+        );
+    }
+
+    Ok(summary)
+}
+
+/// Stops the running scan after the file it is on. `scan_library` returns
+/// what was imported so far with `cancelled` set.
+#[tauri::command]
+pub fn cancel_scan(scan: State<'_, ScanToken>) -> Result<(), AppError> {
+    if scan.cancel() {
+        Ok(())
+    } else {
+        Err(AppError::NotFound("No library scan is running".to_string()))
+    }
+}
+
+#[tauri::command]
+pub async fn get_recent_logs(lines: Option<usize>) -> Result<Vec<String>, AppError> {
+    let lines = lines.unwrap_or(DEFAULT_LOG_LINES);
+    tokio::task::spawn_blocking(move || logger::recent_lines(lines))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn clear_logs() -> Result<(), AppError> {
+    logger::clear()?;
+    log::info!("Logs cleared");
+    Ok(())
+}
+
+/// Opens the logs folder in the system file manager.
+#[tauri::command]
+pub fn open_logs_folder(app: AppHandle) -> Result<(), AppError> {
+    let logs_dir = match logger::log_path().and_then(|path| path.parent().map(Path::to_path_buf)) {
+        Some(dir) => dir,
+        None => app
+            .path()
+            .app_data_dir()
+            .map_err(|e| AppError::Io(e.to_string()))?
+            .join("logs"),
+    };
+    fs::create_dir_all(&logs_dir)?;
+
+    let opener = match std::env::consts::OS {
+        "windows" => "explorer",
+        "macos" => "open",
+        _ => "xdg-open",
+    };
+    std::process::Command::new(opener)
+        .arg(&logs_dir)
+        .spawn()
+        .map_err(|e| AppError::from(e).with_context(opener))?;
+    Ok(())
+}
+
+/// Adds a local file to the library. With `copy_to` the file is copied there
+/// first, otherwise the song points at the file where it is.
+pub(crate) async fn import_file(
+    music_db: &MusicDatabase,
+    path: &Path,
+    copy_to: Option<&Path>,
+) -> Result<bool, AppError> {
+    let id = path_id(path);
+    let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM songs WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&music_db.pool)
+        .await?;
+    if exists.is_some() {
+        return Ok(false);
+    }
+
+    let source = path.to_path_buf();
+    let mut metadata = tokio::task::spawn_blocking(move || metadata::read_metadata(&source))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?
+        .map_err(AppError::Decode)?;
+    if metadata
+        .title
+        .as_deref()
+        .is_none_or(|t| t.trim().is_empty())
+    {
+        metadata.title = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned());
+    }
+
+    let date_added: DateTime<Utc> = Utc::now();
+    let Some(songs_dir) = copy_to else {
+        let source = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        return Ok(music_db
+            .insert_imported_song(&id, Some(&source), metadata, date_added)
+            .await?);
+    };
+
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let dest = songs_dir.join(format!("{}.{}", id, extension));
+    tokio::fs::copy(path, &dest).await?;
+
+    match music_db
+        .insert_imported_song(&id, None, metadata, date_added)
+        .await
+    {
+        Ok(inserted) => Ok(inserted),
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&dest).await;
+            Err(e)
+        }
+    }
+}
+
+fn find_audio_files(root: &Path) -> Result<Vec<PathBuf>, AppError> {
+    if !root.is_dir() {
+        return Err(AppError::FileNotFound(root.display().to_string()));
+    }
+
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_audio_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Stable id for an imported file: FNV-1a over its canonical path.
+pub(crate) fn path_id(path: &Path) -> String {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let hash = canonical
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("local-{:016x}", hash)
+}
@@ -0,0 +1,111 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Error returned from commands, serialized as `{ kind, message }` so the
+/// frontend can match on `kind` instead of parsing the message.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum AppError {
+    FileNotFound(String),
+    NotFound(String),
+    InvalidInput(String),
+    Decode(String),
+    /// The audio output device is gone or can't be opened.
+    DeviceUnavailable(String),
+    Db(String),
+    DbLocked(String),
+    Network(String),
+    Io(String),
+    Cancelled(String),
+}
+
+impl AppError {
+    /// Prefixes the message, e.g. with the path an I/O error happened on.
+    pub fn with_context(self, context: &str) -> Self {
+        let add = |msg: String| format!("{}: {}", context, msg);
+        match self {
+            AppError::FileNotFound(msg) => AppError::FileNotFound(add(msg)),
+            AppError::NotFound(msg) => AppError::NotFound(add(msg)),
+            AppError::InvalidInput(msg) => AppError::InvalidInput(add(msg)),
+            AppError::Decode(msg) => AppError::Decode(add(msg)),
+            AppError::DeviceUnavailable(msg) => AppError::DeviceUnavailable(add(msg)),
+            AppError::Db(msg) => AppError::Db(add(msg)),
+            AppError::DbLocked(msg) => AppError::DbLocked(add(msg)),
+            AppError::Network(msg) => AppError::Network(add(msg)),
+            AppError::Io(msg) => AppError::Io(add(msg)),
+            AppError::Cancelled(msg) => AppError::Cancelled(add(msg)),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::FileNotFound(path) => write!(f, "File not found: {}", path),
+            AppError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            AppError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            AppError::Decode(msg) => write!(f, "Decode error: {}", msg),
+            AppError::DeviceUnavailable(msg) => write!(f, "Audio device unavailable: {}", msg),
+            AppError::Db(msg) => write!(f, "Database error: {}", msg),
+            AppError::DbLocked(msg) => write!(f, "Database is locked: {}", msg),
+            AppError::Network(msg) => write!(f, "Network error: {}", msg),
+            AppError::Io(msg) => write!(f, "I/O error: {}", msg),
+            AppError::Cancelled(msg) => write!(f, "Cancelled: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        let locked = e
+            .as_database_error()
+            .and_then(|db| db.code())
+            .is_some_and(|code| is_busy_or_locked(&code));
+        if locked {
+            AppError::DbLocked(e.to_string())
+        } else {
+            AppError::Db(e.to_string())
+        }
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        AppError::Network(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::FileNotFound(e.to_string()),
+            _ => AppError::Io(e.to_string()),
+        }
+    }
+}
+
+/// SQLITE_BUSY and SQLITE_LOCKED, usually worth retrying, including extended
+/// codes such as SQLITE_BUSY_SNAPSHOT whose low byte is the primary code.
+fn is_busy_or_locked(code: &str) -> bool {
+    code.parse::<i32>()
+        .is_ok_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busy_and_locked_codes() {
+        assert!(is_busy_or_locked("5"));
+        assert!(is_busy_or_locked("6"));
+        // SQLITE_BUSY_SNAPSHOT and SQLITE_LOCKED_SHAREDCACHE.
+        assert!(is_busy_or_locked("517"));
+        assert!(is_busy_or_locked("262"));
+        assert!(!is_busy_or_locked("19"));
+        assert!(!is_busy_or_locked("2067"));
+        assert!(!is_busy_or_locked("busy"));
+    }
+}
@@ -1,10 +1,59 @@
+use crate::db::settings::SettingsDatabase;
 use chrono;
 use log::{LevelFilter, SetLoggerError};
-use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tauri::Manager;
 
-pub struct FileLogger {
+static MAX_LOG_BYTES: AtomicU64 = AtomicU64::new(5 * 1024 * 1024);
+static LOG_RETENTION: AtomicUsize = AtomicUsize::new(3);
+static LOGGER: OnceLock<&'static FileLogger> = OnceLock::new();
+const TAIL_CHUNK_SIZE: u64 = 8 * 1024;
+/// Largest `log_max_size_mb` accepted, larger values are clamped to it.
+pub const MAX_LOG_SIZE_MB: u64 = 1024;
+
+struct LogFile {
+    path: PathBuf,
     file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { path, file, size })
+    }
+
+    /// Shifts `app.log.N` to `app.log.N+1`, dropping the oldest, moves the
+    /// current file to `app.log.1` and starts a fresh one.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let retention = LOG_RETENTION.load(Ordering::Relaxed);
+        let archive = |n: usize| archive_path(&self.path, n);
+
+        if retention == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(archive(retention));
+            for n in (1..retention).rev() {
+                let _ = fs::rename(archive(n), archive(n + 1));
+            }
+            fs::rename(&self.path, archive(1))?;
+        }
+
+        *self = LogFile::open(self.path.clone())?;
+        Ok(())
+    }
+}
+
+/// The one logger of the app, installed as the `log` facade by `init_logger`.
+/// Everything logged through `log::info!` and friends ends up in
+/// `logs/app.log` inside the app data directory.
+pub struct FileLogger {
+    file: Mutex<LogFile>,
 }
 
 impl log::Log for FileLogger {
@@ -14,36 +63,143 @@ impl log::Log for FileLogger {
 
     fn log(&self, record: &log::Record) {
         if self.enabled(record.metadata()) {
-            let mut file = self.file.try_clone().expect("Failed to clone file handle");
-            writeln!(
-                file,
-                "{} - {}: {}",
+            let line = format!(
+                "{} - {}: {}\n",
                 chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
                 record.level(),
                 record.args()
-            )
-            .expect("Failed to write to log file");
+            );
+
+            let mut log_file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            let max_bytes = MAX_LOG_BYTES.load(Ordering::Relaxed);
+            if max_bytes > 0 && log_file.size > 0 && log_file.size + line.len() as u64 > max_bytes {
+                let _ = log_file.rotate();
+            }
+            // Nowhere to report a failed write to, don't take the app down over it.
+            if log_file.file.write_all(line.as_bytes()).is_ok() {
+                log_file.size += line.len() as u64;
+            }
         }
     }
 
     fn flush(&self) {
-        self.file.sync_all().expect("Failed to flush log file");
+        let log_file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = log_file.file.sync_all();
     }
 }
 
-pub fn init_logger(app_data_dir: &std::path::Path) -> Result<(), SetLoggerError> {
+pub fn init_logger(app_data_dir: &Path) -> Result<(), SetLoggerError> {
     let logs_dir = app_data_dir.join("logs");
     std::fs::create_dir_all(&logs_dir).expect("Failed to create logs directory");
 
-    let log_path = logs_dir.join("app.log");
-    let file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(log_path)
-        .expect("Failed to open log file");
+    let log_file = LogFile::open(logs_dir.join("app.log")).expect("Failed to open log file");
 
-    let logger = Box::new(FileLogger { file });
-    unsafe { log::set_logger_racy(Box::leak(logger))? };
+    let logger: &'static FileLogger = Box::leak(Box::new(FileLogger {
+        file: Mutex::new(log_file),
+    }));
+    log::set_logger(logger)?;
+    let _ = LOGGER.set(logger);
+    // Until the stored level is read, keep everything that happens at startup.
     log::set_max_level(LevelFilter::Debug);
     Ok(())
 }
+
+/// Applies the stored level and rotation settings once the settings database is available.
+pub fn load_settings(app: &tauri::App) {
+    let app_handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let settings_db = app_handle.state::<SettingsDatabase>();
+        let max_size_mb = settings_db.get_setting("log_max_size_mb").await;
+        let retention = settings_db.get_setting("log_retention").await;
+        let level = settings_db.get_setting("log_level").await;
+        if let Ok(Ok(max_size_mb)) = max_size_mb.map(|v| v.parse()) {
+            set_max_size_mb(max_size_mb);
+        }
+        if let Ok(Ok(retention)) = retention.map(|v| v.parse()) {
+            set_retention(retention);
+        }
+        if let Ok(Ok(level)) = level.map(|v| parse_level(&v)) {
+            log::set_max_level(level);
+        }
+    });
+}
+
+/// Returns up to `count` of the last lines of the current log file, oldest
+/// first. Reads backwards from the end so large logs aren't loaded whole.
+pub fn recent_lines(count: usize) -> std::io::Result<Vec<String>> {
+    let Some(path) = log_path() else {
+        return Ok(Vec::new());
+    };
+    let mut file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut pos = file.metadata()?.len();
+    let mut buffer: Vec<u8> = Vec::new();
+    // One extra newline is needed to know the first wanted line is complete.
+    while pos > 0 && buffer.iter().filter(|&&b| b == b'\n').count() <= count {
+        let chunk = TAIL_CHUNK_SIZE.min(pos);
+        pos -= chunk;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut bytes = vec![0; chunk as usize];
+        file.read_exact(&mut bytes)?;
+        bytes.extend_from_slice(&buffer);
+        buffer = bytes;
+    }
+
+    let text = String::from_utf8_lossy(&buffer);
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.len().saturating_sub(count);
+    Ok(lines[start..].iter().map(|line| line.to_string()).collect())
+}
+
+/// Empties the current log file and removes its archives.
+pub fn clear() -> std::io::Result<()> {
+    let Some(logger) = LOGGER.get() else {
+        return Ok(());
+    };
+    let mut log_file = logger.file.lock().unwrap_or_else(|e| e.into_inner());
+    // The logger's own handle is append-only, truncate through a second one.
+    OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(&log_file.path)?;
+    log_file.size = 0;
+
+    let mut n = 1;
+    while fs::remove_file(archive_path(&log_file.path, n)).is_ok() {
+        n += 1;
+    }
+    Ok(())
+}
+
+pub fn log_path() -> Option<PathBuf> {
+    let logger = LOGGER.get()?;
+    let log_file = logger.file.lock().unwrap_or_else(|e| e.into_inner());
+    Some(log_file.path.clone())
+}
+
+/// A size of 0 turns rotation off.
+pub fn set_max_size_mb(max_size_mb: u64) {
+    MAX_LOG_BYTES.store(max_size_mb.saturating_mul(1024 * 1024), Ordering::Relaxed);
+}
+
+pub fn set_retention(retention: usize) {
+    LOG_RETENTION.store(retention, Ordering::Relaxed);
+}
+
+/// Parses a level name such as `info` or `debug`, case-insensitively.
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .trim()
+        .parse()
+        .map_err(|_| format!("Unknown log level: {}", level))
+}
+
+fn archive_path(path: &Path, n: usize) -> PathBuf {
+    let mut archive = path.as_os_str().to_owned();
+    archive.push(format!(".{}", n));
+    PathBuf::from(archive)
+}
@@ -0,0 +1,127 @@
+use lofty::file::{AudioFile, TaggedFileExt};
+use lofty::probe::Probe;
+use lofty::tag::{Accessor, ItemKey};
+use std::path::Path;
+
+/// Tags read from an audio file, with everything the file didn't provide left empty.
+#[derive(Debug, Default)]
+pub struct TrackMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    pub duration: i64,
+    pub cover: Option<Vec<u8>>,
+    pub track_gain_db: Option<f64>,
+    pub album_gain_db: Option<f64>,
+}
+
+pub fn read_metadata(path: &Path) -> Result<TrackMetadata, String> {
+    let tagged_file = lofty::read_from_path(path).map_err(|e| e.to_string())?;
+    let mut metadata = TrackMetadata {
+        duration: tagged_file.properties().duration().as_secs() as i64,
+        ..Default::default()
+    };
+
+    if let Some(tag) = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+    {
+        metadata.title = tag.title().map(|s| s.into_owned());
+        metadata.artist = tag.artist().map(|s| s.into_owned());
+        metadata.album = tag.album().map(|s| s.into_owned());
+        metadata.genre = tag.genre().and_then(|s| normalize_genre(&s));
+        metadata.track_number = tag.track();
+        metadata.cover = tag.pictures().first().map(|p| p.data().to_vec());
+        metadata.track_gain_db = tag
+            .get_string(&ItemKey::ReplayGainTrackGain)
+            .and_then(parse_gain);
+        metadata.album_gain_db = tag
+            .get_string(&ItemKey::ReplayGainAlbumGain)
+            .and_then(parse_gain);
+    }
+
+    Ok(metadata)
+}
+
+/// Checks that `path` holds audio that can be parsed, going by its contents
+/// rather than its extension.
+pub fn probe_audio(path: &Path) -> Result<(), String> {
+    let probe = Probe::open(path)
+        .map_err(|e| e.to_string())?
+        .guess_file_type()
+        .map_err(|e| e.to_string())?;
+    probe.read().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Splits a multi-value genre tag such as `Rock; Indie` or `Pop/Dance`.
+pub(crate) fn split_genres(raw: &str) -> Vec<String> {
+    raw.split(|c| matches!(c, ';' | '/' | ',' | '\0'))
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Stores multi-value genre tags one way, `Rock; Indie`, with duplicates
+/// dropped regardless of case.
+fn normalize_genre(raw: &str) -> Option<String> {
+    let mut genres: Vec<String> = Vec::new();
+    for genre in split_genres(raw) {
+        if !genres.iter().any(|g| g.eq_ignore_ascii_case(&genre)) {
+            genres.push(genre);
+        }
+    }
+    (!genres.is_empty()).then(|| genres.join("; "))
+}
+
+/// Parses a ReplayGain value such as `-6.48 dB`.
+fn parse_gain(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    number
+        .trim()
+        .parse()
+        .ok()
+        .filter(|gain: &f64| gain.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_common_separators() {
+        assert_eq!(split_genres("Rock; Indie"), vec!["Rock", "Indie"]);
+        assert_eq!(split_genres("Pop/Dance"), vec!["Pop", "Dance"]);
+        assert_eq!(split_genres("Jazz\0Soul"), vec!["Jazz", "Soul"]);
+    }
+
+    #[test]
+    fn drops_empty_parts() {
+        assert_eq!(split_genres("a,,b"), vec!["a", "b"]);
+        assert!(split_genres("").is_empty());
+        assert!(split_genres(" ; / ").is_empty());
+    }
+
+    #[test]
+    fn normalizes_separators_and_duplicates() {
+        assert_eq!(normalize_genre("Pop/Dance").as_deref(), Some("Pop; Dance"));
+        assert_eq!(normalize_genre("Rock; rock; ROCK").as_deref(), Some("Rock"));
+        assert_eq!(normalize_genre("a,,b").as_deref(), Some("a; b"));
+        assert_eq!(normalize_genre(""), None);
+        assert_eq!(normalize_genre(" ; "), None);
+    }
+
+    #[test]
+    fn parses_replay_gain() {
+        assert_eq!(parse_gain("-6.48 dB"), Some(-6.48));
+        assert_eq!(parse_gain("+2.1db"), Some(2.1));
+        assert_eq!(parse_gain("loud"), None);
+    }
+}
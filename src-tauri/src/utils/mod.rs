@@ -1 +1,8 @@
-pub mod logger;
\ No newline at end of file
+pub mod commands;
+pub mod error;
+pub mod logger;
+pub mod metadata;
+pub mod romanize;
+pub mod scrobble;
+pub mod silence;
+pub mod waveform;
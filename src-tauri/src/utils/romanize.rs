@@ -0,0 +1,243 @@
+//! Latin transcription of Korean and Japanese lyrics. Hangul follows the
+//! Revised Romanization letter by letter, without the sound changes between
+//! syllables, and kana follows Hepburn. Kanji and every other script are
+//! left as they are, a hand-corrected `.romanized.lrc` is the way to fix
+//! those up.
+
+const HANGUL_FIRST: u32 = 0xAC00;
+const HANGUL_LAST: u32 = 0xD7A3;
+
+const INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+const VOWELS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+const FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p",
+    "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+
+/// Returns `text` with Hangul and kana written out in Latin letters, or
+/// `None` if it has neither and there is nothing to romanize.
+pub fn romanize(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut changed = false;
+    // Set by a small tsu, doubles the consonant of the next kana.
+    let mut double_next = false;
+
+    for c in text.chars() {
+        if let Some(syllable) = hangul(c) {
+            push_syllable(&mut out, &syllable, &mut double_next);
+            changed = true;
+            continue;
+        }
+        let Some(kana) = to_hiragana(c) else {
+            double_next = false;
+            out.push(c);
+            continue;
+        };
+        changed = true;
+        match kana {
+            'っ' => double_next = true,
+            'ー' => {
+                if let Some(vowel) = out.chars().last().filter(|c| "aeiou".contains(*c)) {
+                    out.push(vowel);
+                }
+            }
+            'ゃ' | 'ゅ' | 'ょ' => {
+                let glide = match kana {
+                    'ゃ' => "a",
+                    'ゅ' => "u",
+                    _ => "o",
+                };
+                // きゃ is kya, but しゃ and ちゃ drop the y: sha, cha, ja.
+                if out.ends_with("shi") || out.ends_with("chi") || out.ends_with("ji") {
+                    out.pop();
+                } else if out.ends_with('i') {
+                    out.pop();
+                    out.push('y');
+                } else {
+                    out.push('y');
+                }
+                out.push_str(glide);
+            }
+            'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' => {
+                // Extended katakana: ファ is fa, ティ is ti, シェ is she, ウェ is we.
+                let vowel = hepburn(kana).unwrap_or_default();
+                if ["fu", "vu", "tsu"].iter().any(|end| out.ends_with(end))
+                    || out.ends_with('e')
+                    || out.ends_with('i')
+                {
+                    out.pop();
+                } else if out.ends_with('u') {
+                    out.pop();
+                    out.push('w');
+                }
+                out.push_str(vowel);
+            }
+            _ => match hepburn(kana) {
+                Some(romaji) => push_syllable(&mut out, romaji, &mut double_next),
+                None => out.push(c),
+            },
+        }
+    }
+
+    changed.then_some(out)
+}
+
+/// `romanize` for the UI, e.g. for a song title. `None` when there is
+/// nothing to romanize.
+#[tauri::command]
+pub fn romanize_text(text: String) -> Option<String> {
+    romanize(&text)
+}
+
+fn push_syllable(out: &mut String, syllable: &str, double_next: &mut bool) {
+    if std::mem::take(double_next) {
+        if syllable.starts_with("ch") {
+            out.push('t');
+        } else if let Some(first) = syllable.chars().next().filter(|c| !"aeiou".contains(*c)) {
+            out.push(first);
+        }
+    }
+    out.push_str(syllable);
+}
+
+fn hangul(c: char) -> Option<String> {
+    let code = c as u32;
+    if !(HANGUL_FIRST..=HANGUL_LAST).contains(&code) {
+        return None;
+    }
+    let index = (code - HANGUL_FIRST) as usize;
+    Some(format!(
+        "{}{}{}",
+        INITIALS[index / 588],
+        VOWELS[index % 588 / 28],
+        FINALS[index % 28]
+    ))
+}
+
+/// Maps katakana onto the matching hiragana so one table covers both. The
+/// long vowel mark is passed through as is.
+fn to_hiragana(c: char) -> Option<char> {
+    match c {
+        'ぁ'..='ゖ' | 'ー' => Some(c),
+        'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60),
+        _ => None,
+    }
+}
+
+fn hepburn(kana: char) -> Option<&'static str> {
+    Some(match kana {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' => "e",
+        'お' | 'ぉ' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ゐ' => "i",
+        'ゑ' => "e",
+        'を' => "o",
+        'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hangul_syllables() {
+        assert_eq!(romanize("사랑해").as_deref(), Some("saranghae"));
+        assert_eq!(romanize("안녕 하세요").as_deref(), Some("annyeong haseyo"));
+    }
+
+    #[test]
+    fn kana_with_digraphs_and_small_tsu() {
+        assert_eq!(romanize("きょう").as_deref(), Some("kyou"));
+        assert_eq!(romanize("しゃしん").as_deref(), Some("shashin"));
+        assert_eq!(romanize("がっこう").as_deref(), Some("gakkou"));
+        assert_eq!(romanize("まっちゃ").as_deref(), Some("matcha"));
+    }
+
+    #[test]
+    fn katakana_and_long_vowels() {
+        assert_eq!(romanize("ラーメン").as_deref(), Some("raamen"));
+        assert_eq!(romanize("ファン").as_deref(), Some("fan"));
+        assert_eq!(romanize("シェア").as_deref(), Some("shea"));
+        assert_eq!(romanize("パーティー").as_deref(), Some("paatii"));
+    }
+
+    #[test]
+    fn other_text_is_kept() {
+        assert_eq!(romanize("君の名は").as_deref(), Some("君no名ha"));
+        assert_eq!(romanize("Hello, world"), None);
+        assert_eq!(romanize(""), None);
+    }
+}
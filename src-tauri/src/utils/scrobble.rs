@@ -0,0 +1,407 @@
+use crate::api::media_controls::NowPlaying;
+use crate::db::music::MusicDatabase;
+use crate::db::settings::SettingsDatabase;
+use crate::db::types::Song;
+use crate::utils::error::AppError;
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+// Provided at build time; builds without them can't scrobble.
+const API_KEY: Option<&str> = option_env!("LASTFM_API_KEY");
+const API_SECRET: Option<&str> = option_env!("LASTFM_API_SECRET");
+// Last.fm only takes tracks longer than 30 seconds, once half of the track or
+// four minutes have been played, whichever comes first.
+const MIN_TRACK_SECS: f64 = 30.0;
+const MAX_THRESHOLD_SECS: f64 = 240.0;
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+const BATCH_SIZE: i64 = 50;
+// Operation failed, service offline, temporarily unavailable and rate
+// limited. Queued scrobbles are kept for these.
+const RETRY_ERRORS: [i64; 4] = [8, 11, 16, 29];
+// The session key was revoked or expired; retrying with it never succeeds.
+const INVALID_SESSION: i64 = 9;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static CURRENT: Mutex<Option<Track>> = Mutex::new(None);
+
+/// The track being listened to and how long it has actually been playing.
+struct Track {
+    song: Song,
+    duration: f64,
+    started_at: i64,
+    listened: Duration,
+    playing_since: Option<Instant>,
+    now_playing_sent: bool,
+    scrobbled: bool,
+}
+
+impl Track {
+    fn new(song: Song) -> Self {
+        Track {
+            duration: song.duration as f64,
+            song,
+            started_at: unix_time(),
+            listened: Duration::ZERO,
+            playing_since: None,
+            now_playing_sent: false,
+            scrobbled: false,
+        }
+    }
+
+    fn listened(&self) -> Duration {
+        self.listened
+            + self
+                .playing_since
+                .map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    fn should_scrobble(&self) -> bool {
+        if self.scrobbled || self.duration <= MIN_TRACK_SECS {
+            return false;
+        }
+        let threshold = (self.duration / 2.0).min(MAX_THRESHOLD_SECS);
+        self.listened() >= Duration::from_secs_f64(threshold)
+    }
+}
+
+enum CallError {
+    /// Worth retrying later, e.g. while offline.
+    Temporary(String),
+    Rejected(String),
+    /// The user has to sign in again.
+    InvalidSession(String),
+}
+
+impl From<CallError> for AppError {
+    fn from(e: CallError) -> Self {
+        match e {
+            CallError::Temporary(msg) => AppError::Network(msg),
+            CallError::Rejected(msg) | CallError::InvalidSession(msg) => {
+                AppError::InvalidInput(msg)
+            }
+        }
+    }
+}
+
+fn lock_current() -> MutexGuard<'static, Option<Track>> {
+    CURRENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Starts the background task that sends now playing updates, queues
+/// scrobbles and submits the queue, retrying while Last.fm is unreachable.
+pub fn start(app: &tauri::App) {
+    let app_handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let settings_db = app_handle.state::<SettingsDatabase>();
+        if let Ok(value) = settings_db.get_setting("lastfm_enabled").await {
+            ENABLED.store(value == "true", Ordering::Relaxed);
+        }
+
+        let mut next_flush = Instant::now();
+        loop {
+            if ENABLED.load(Ordering::Relaxed) {
+                if let Some(session_key) = session_key(&settings_db).await {
+                    let queued = process_current(&app_handle, &session_key).await;
+                    if queued || Instant::now() >= next_flush {
+                        if let Err(e) = flush(&app_handle, &session_key).await {
+                            log::debug!("Scrobbles stay queued: {}", e);
+                        }
+                        next_flush = Instant::now() + RETRY_INTERVAL;
+                    }
+                }
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Tracks playback from the player's now playing updates. Only time spent
+/// playing counts towards the scrobble threshold.
+pub fn update(now_playing: &NowPlaying) {
+    let mut current = lock_current();
+    let Some(song) = &now_playing.song else {
+        *current = None;
+        return;
+    };
+
+    // Restarting a song that was already scrobbled, e.g. on repeat, is a new listen.
+    let is_new = current.as_ref().is_none_or(|track| {
+        track.song.id != song.id || (track.scrobbled && now_playing.position < 1.0)
+    });
+    if is_new {
+        *current = Some(Track::new(song.clone()));
+    }
+
+    if let Some(track) = current.as_mut() {
+        if now_playing.duration > 0.0 {
+            track.duration = now_playing.duration;
+        }
+        match (track.playing_since, now_playing.paused) {
+            (Some(since), true) => {
+                track.listened += since.elapsed();
+                track.playing_since = None;
+            }
+            (None, false) => track.playing_since = Some(Instant::now()),
+            _ => {}
+        }
+    }
+}
+
+async fn session_key(settings_db: &SettingsDatabase) -> Option<String> {
+    API_KEY?;
+    settings_db
+        .get_setting("lastfm_session_key")
+        .await
+        .ok()
+        .filter(|key| !key.is_empty())
+}
+
+/// Sends the now playing update for a new track and queues the scrobble once
+/// it has played long enough. Returns whether a scrobble was queued.
+async fn process_current(app: &AppHandle, session_key: &str) -> bool {
+    let (now_playing, scrobble) = {
+        let mut current = lock_current();
+        let Some(track) = current.as_mut() else {
+            return false;
+        };
+        let now_playing = if track.now_playing_sent {
+            None
+        } else {
+            track.now_playing_sent = true;
+            Some((track.song.clone(), track.duration))
+        };
+        let scrobble = if track.should_scrobble() {
+            track.scrobbled = true;
+            Some((track.song.clone(), track.duration, track.started_at))
+        } else {
+            None
+        };
+        (now_playing, scrobble)
+    };
+
+    if let Some((song, duration)) = now_playing {
+        let mut params = track_params(&song, duration);
+        params.insert("method".to_string(), "track.updateNowPlaying".to_string());
+        params.insert("sk".to_string(), session_key.to_string());
+        match call(params).await {
+            Err(CallError::InvalidSession(msg)) => {
+                sign_out(app, &msg).await;
+                return false;
+            }
+            Err(e) => log::debug!(
+                "Failed to update Last.fm now playing: {}",
+                AppError::from(e)
+            ),
+            Ok(_) => {}
+        }
+    }
+
+    let Some((song, duration, started_at)) = scrobble else {
+        return false;
+    };
+    let pool = &app.state::<MusicDatabase>().pool;
+    let result = sqlx::query(
+        "INSERT INTO scrobble_queue (artist, track, album, duration, timestamp)
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(&song.artist)
+    .bind(&song.title)
+    .bind(&song.album)
+    .bind(duration.round() as i64)
+    .bind(started_at)
+    .execute(pool)
+    .await;
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("Failed to queue scrobble for {}: {}", song.id, e);
+            false
+        }
+    }
+}
+
+/// Submits queued scrobbles in batches, oldest first. Stops at the first
+/// batch that can't be sent right now and leaves it queued.
+async fn flush(app: &AppHandle, session_key: &str) -> Result<(), AppError> {
+    let pool = &app.state::<MusicDatabase>().pool;
+    loop {
+        let batch: Vec<(i64, String, String, String, i64, i64)> = sqlx::query_as(
+            "SELECT id, artist, track, album, duration, timestamp
+             FROM scrobble_queue ORDER BY id LIMIT ?",
+        )
+        .bind(BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+        let Some(&(last_id, ..)) = batch.last() else {
+            return Ok(());
+        };
+
+        let mut params = BTreeMap::new();
+        params.insert("method".to_string(), "track.scrobble".to_string());
+        params.insert("sk".to_string(), session_key.to_string());
+        for (i, (_, artist, track, album, duration, timestamp)) in batch.iter().enumerate() {
+            params.insert(format!("artist[{}]", i), artist.clone());
+            params.insert(format!("track[{}]", i), track.clone());
+            params.insert(format!("timestamp[{}]", i), timestamp.to_string());
+            if !album.is_empty() {
+                params.insert(format!("album[{}]", i), album.clone());
+            }
+            if *duration > 0 {
+                params.insert(format!("duration[{}]", i), duration.to_string());
+            }
+        }
+
+        match call(params).await {
+            Ok(_) => log::info!("Scrobbled {} tracks to Last.fm", batch.len()),
+            Err(CallError::Temporary(msg)) => return Err(AppError::Network(msg)),
+            Err(CallError::InvalidSession(msg)) => {
+                sign_out(app, &msg).await;
+                return Err(AppError::InvalidInput(msg));
+            }
+            Err(CallError::Rejected(msg)) => {
+                log::warn!("Last.fm rejected {} scrobbles: {}", batch.len(), msg)
+            }
+        }
+        sqlx::query("DELETE FROM scrobble_queue WHERE id <= ?")
+            .bind(last_id)
+            .execute(pool)
+            .await?;
+    }
+}
+
+/// Forgets the session and turns scrobbling off after Last.fm rejected the
+/// session key. Queued scrobbles are kept and sent after signing in again.
+async fn sign_out(app: &AppHandle, reason: &str) {
+    log::warn!(
+        "Last.fm session is no longer valid, disabling scrobbling: {}",
+        reason
+    );
+    ENABLED.store(false, Ordering::Relaxed);
+    let settings_db = app.state::<SettingsDatabase>();
+    for (key, value) in [("lastfm_session_key", ""), ("lastfm_enabled", "false")] {
+        if let Err(e) = settings_db.update_setting(key, value).await {
+            log::error!("Failed to clear {}: {}", key, e);
+        }
+    }
+}
+
+fn track_params(song: &Song, duration: f64) -> BTreeMap<String, String> {
+    let mut params = BTreeMap::new();
+    params.insert("artist".to_string(), song.artist.clone());
+    params.insert("track".to_string(), song.title.clone());
+    if !song.album.is_empty() {
+        params.insert("album".to_string(), song.album.clone());
+    }
+    if duration > 0.0 {
+        params.insert(
+            "duration".to_string(),
+            (duration.round() as i64).to_string(),
+        );
+    }
+    params
+}
+
+/// Signs and sends an API call. The signature is the MD5 of every parameter
+/// as `keyvalue`, sorted by key, followed by the shared secret.
+async fn call(mut params: BTreeMap<String, String>) -> Result<Value, CallError> {
+    let (Some(api_key), Some(api_secret)) = (API_KEY, API_SECRET) else {
+        return Err(CallError::Rejected(
+            "This build has no Last.fm API key".to_string(),
+        ));
+    };
+    params.insert("api_key".to_string(), api_key.to_string());
+    let mut signature: String = params.iter().map(|(k, v)| format!("{}{}", k, v)).collect();
+    signature.push_str(api_secret);
+    params.insert(
+        "api_sig".to_string(),
+        format!("{:x}", md5::compute(signature)),
+    );
+    params.insert("format".to_string(), "json".to_string());
+
+    let response = Client::new()
+        .post(API_URL)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| CallError::Temporary(e.to_string()))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| CallError::Temporary(e.to_string()))?;
+    let value: Value = match serde_json::from_str(&body) {
+        Ok(value) => value,
+        Err(_) if status.is_server_error() => {
+            return Err(CallError::Temporary(format!("Last.fm returned {}", status)))
+        }
+        Err(e) => return Err(CallError::Rejected(e.to_string())),
+    };
+
+    if let Some(code) = value.get("error").and_then(Value::as_i64) {
+        let message = value
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error");
+        let message = format!("Last.fm error {}: {}", code, message);
+        return Err(if code == INVALID_SESSION {
+            CallError::InvalidSession(message)
+        } else if RETRY_ERRORS.contains(&code) {
+            CallError::Temporary(message)
+        } else {
+            CallError::Rejected(message)
+        });
+    }
+    if status.is_server_error() {
+        return Err(CallError::Temporary(format!("Last.fm returned {}", status)));
+    }
+    Ok(value)
+}
+
+/// Signs in to Last.fm and stores the session key used for scrobbling.
+/// Returns the account name.
+#[tauri::command]
+pub async fn lastfm_auth(
+    settings_db: State<'_, SettingsDatabase>,
+    username: String,
+    password: String,
+) -> Result<String, AppError> {
+    let mut params = BTreeMap::new();
+    params.insert("method".to_string(), "auth.getMobileSession".to_string());
+    params.insert("username".to_string(), username);
+    params.insert("password".to_string(), password);
+    let response = call(params).await?;
+
+    let session = &response["session"];
+    let (Some(name), Some(key)) = (session["name"].as_str(), session["key"].as_str()) else {
+        return Err(AppError::Decode("Last.fm returned no session".to_string()));
+    };
+    settings_db
+        .update_setting("lastfm_session_key", key)
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    settings_db
+        .update_setting("lastfm_username", name)
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    Ok(name.to_string())
+}
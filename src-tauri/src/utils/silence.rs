@@ -0,0 +1,146 @@
+use crate::api::commands::get_music_path;
+use crate::db::music::MusicDatabase;
+use crate::db::settings::SettingsDatabase;
+use crate::db::types::SilentRange;
+use crate::utils::error::AppError;
+use crate::utils::waveform::for_each_frame;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+const WINDOW_MS: u64 = 50;
+const DEFAULT_THRESHOLD_DB: f64 = -50.0;
+const DEFAULT_MIN_SILENCE_MS: u64 = 2000;
+
+/// What `Silence/{id}.json` holds: the ranges and the settings they were
+/// found with.
+#[derive(Serialize, Deserialize)]
+struct CachedSilence {
+    threshold_db: f64,
+    min_silence_ms: u64,
+    ranges: Vec<SilentRange>,
+}
+
+/// Parts of a song quieter than `silence_threshold_db` for at least
+/// `min_silence_ms`, for the player to skip when `skip_silence` is on.
+/// Cached per song until the audio file or either setting changes.
+#[tauri::command]
+pub async fn get_silent_ranges(
+    music_db: State<'_, MusicDatabase>,
+    settings_db: State<'_, SettingsDatabase>,
+    song_id: String,
+) -> Result<Vec<SilentRange>, AppError> {
+    let threshold_db = settings_db
+        .get_setting("silence_threshold_db")
+        .await
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_THRESHOLD_DB);
+    let min_silence_ms = settings_db
+        .get_setting("min_silence_ms")
+        .await
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MIN_SILENCE_MS);
+
+    let path = music_db.song_file(&song_id).await?;
+    let cache_path = silence_path(&song_id);
+    if let Some(ranges) = read_cached(&cache_path, &path, threshold_db, min_silence_ms) {
+        return Ok(ranges);
+    }
+
+    let ranges =
+        tokio::task::spawn_blocking(move || find_silence(&path, threshold_db, min_silence_ms))
+            .await
+            .map_err(|e| AppError::Io(e.to_string()))?
+            .map_err(AppError::Decode)?;
+
+    let cached = CachedSilence {
+        threshold_db,
+        min_silence_ms,
+        ranges,
+    };
+    let written = serde_json::to_string(&cached)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&cache_path, json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        log::warn!("Failed to cache silent ranges for {}: {}", song_id, e);
+    }
+    Ok(cached.ranges)
+}
+
+fn silence_path(id: &str) -> PathBuf {
+    get_music_path()
+        .join("Silence")
+        .join(format!("{}.json", id))
+}
+
+/// The cached ranges, unless they are older than the audio file or were found
+/// with different settings.
+fn read_cached(
+    cache_path: &Path,
+    audio_path: &Path,
+    threshold_db: f64,
+    min_silence_ms: u64,
+) -> Option<Vec<SilentRange>> {
+    let cached_at = fs::metadata(cache_path).and_then(|m| m.modified()).ok()?;
+    let changed_at = fs::metadata(audio_path).and_then(|m| m.modified()).ok()?;
+    if cached_at < changed_at {
+        return None;
+    }
+
+    let cached: CachedSilence = serde_json::from_str(&fs::read_to_string(cache_path).ok()?).ok()?;
+    (cached.threshold_db == threshold_db && cached.min_silence_ms == min_silence_ms)
+        .then_some(cached.ranges)
+}
+
+/// Measures the RMS level of every `WINDOW_MS` window. A range only counts
+/// once enough windows in a row are below the threshold, so a single quiet
+/// moment in the music isn't skipped.
+fn find_silence(
+    path: &Path,
+    threshold_db: f64,
+    min_silence_ms: u64,
+) -> Result<Vec<SilentRange>, String> {
+    let threshold = 10f64.powf(threshold_db / 20.0);
+    let mut ranges = Vec::new();
+    let mut frames: u64 = 0;
+    let mut window_frames: u64 = 0;
+    let mut window_energy = 0.0f64;
+    let mut silent_since: Option<u64> = None;
+    let mut rate = 0;
+
+    let mut close_run = |silent_since: &mut Option<u64>, end_ms: u64| {
+        if let Some(start_ms) = silent_since.take() {
+            if end_ms - start_ms >= min_silence_ms {
+                ranges.push(SilentRange { start_ms, end_ms });
+            }
+        }
+    };
+
+    for_each_frame(path, |frame, sample_rate| {
+        rate = sample_rate.max(1);
+        let energy: f64 = frame.iter().map(|&s| (s as f64) * (s as f64)).sum();
+        window_energy += energy / frame.len().max(1) as f64;
+        window_frames += 1;
+        frames += 1;
+
+        if window_frames * 1000 >= WINDOW_MS * rate as u64 {
+            let rms = (window_energy / window_frames as f64).sqrt();
+            let window_start_ms = (frames - window_frames) * 1000 / rate as u64;
+            if rms < threshold {
+                silent_since.get_or_insert(window_start_ms);
+            } else {
+                close_run(&mut silent_since, window_start_ms);
+            }
+            window_energy = 0.0;
+            window_frames = 0;
+        }
+    })?;
+
+    if rate > 0 {
+        close_run(&mut silent_since, frames * 1000 / rate as u64);
+    }
+    Ok(ranges)
+}
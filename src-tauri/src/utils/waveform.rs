@@ -0,0 +1,180 @@
+use crate::api::commands::get_music_path;
+use crate::db::music::MusicDatabase;
+use crate::db::types::Song;
+use crate::utils::error::AppError;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tauri::State;
+
+const MAX_BUCKETS: usize = 10_000;
+// Peaks are first taken over blocks this many frames long, then folded into
+// the requested buckets, since the length isn't always known before decoding.
+const BLOCK_FRAMES: usize = 256;
+
+/// Peak amplitude of a song in `buckets` evenly sized slices, scaled so the
+/// loudest is 1.0. Computed once per song and cached in `Waveforms/{id}.json`
+/// until the audio file changes or a different number of buckets is asked for.
+#[tauri::command]
+pub async fn generate_waveform(
+    music_db: State<'_, MusicDatabase>,
+    song: Song,
+    buckets: usize,
+) -> Result<Vec<f32>, AppError> {
+    let buckets = buckets.clamp(1, MAX_BUCKETS);
+    let audio_path = music_db.song_file(&song.id).await?;
+    let cache_path = waveform_path(&song.id);
+    if let Some(peaks) = read_cached(&cache_path, &audio_path, buckets) {
+        return Ok(peaks);
+    }
+
+    let peaks = tokio::task::spawn_blocking(move || compute_waveform(&audio_path, buckets))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?
+        .map_err(AppError::Decode)?;
+
+    let written = serde_json::to_string(&peaks)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&cache_path, json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        log::warn!("Failed to cache waveform for {}: {}", song.id, e);
+    }
+    Ok(peaks)
+}
+
+fn waveform_path(id: &str) -> PathBuf {
+    get_music_path()
+        .join("Waveforms")
+        .join(format!("{}.json", id))
+}
+
+/// The cached peaks, unless they are older than the audio file or have a
+/// different number of buckets.
+fn read_cached(cache_path: &Path, audio_path: &Path, buckets: usize) -> Option<Vec<f32>> {
+    let cached_at = fs::metadata(cache_path).and_then(|m| m.modified()).ok()?;
+    let changed_at = fs::metadata(audio_path).and_then(|m| m.modified()).ok()?;
+    if cached_at < changed_at {
+        return None;
+    }
+
+    let peaks: Vec<f32> = serde_json::from_str(&fs::read_to_string(cache_path).ok()?).ok()?;
+    (peaks.len() == buckets).then_some(peaks)
+}
+
+fn compute_waveform(path: &Path, buckets: usize) -> Result<Vec<f32>, String> {
+    let blocks = block_peaks(path)?;
+    if blocks.is_empty() {
+        return Ok(vec![0.0; buckets]);
+    }
+
+    let mut peaks: Vec<f32> = (0..buckets)
+        .map(|bucket| {
+            let start = (bucket * blocks.len() / buckets).min(blocks.len() - 1);
+            let end = ((bucket + 1) * blocks.len() / buckets).max(start + 1);
+            blocks[start..end].iter().copied().fold(0.0, f32::max)
+        })
+        .collect();
+
+    let loudest = peaks.iter().copied().fold(0.0, f32::max);
+    if loudest > 0.0 {
+        for peak in &mut peaks {
+            *peak /= loudest;
+        }
+    }
+    Ok(peaks)
+}
+
+/// The peak of every `BLOCK_FRAMES` frames across all channels.
+fn block_peaks(path: &Path) -> Result<Vec<f32>, String> {
+    let mut blocks = Vec::new();
+    let mut block_peak = 0.0f32;
+    let mut block_len = 0;
+
+    for_each_frame(path, |frame, _| {
+        for sample in frame {
+            block_peak = block_peak.max(sample.abs());
+        }
+        block_len += 1;
+        if block_len == BLOCK_FRAMES {
+            blocks.push(block_peak);
+            block_peak = 0.0;
+            block_len = 0;
+        }
+    })?;
+
+    if block_len > 0 {
+        blocks.push(block_peak);
+    }
+    Ok(blocks)
+}
+
+/// Decodes the whole file, calling `on_frame` with the samples of every frame
+/// (one per channel) and the sample rate. Packets that fail to decode are
+/// skipped.
+pub(crate) fn for_each_frame(
+    path: &Path,
+    mut on_frame: impl FnMut(&[f32], u32),
+) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| e.to_string())?;
+    let mut format = probed.format;
+    let track = format
+        .default_track()
+        .ok_or_else(|| format!("No audio track in {}", path.display()))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| e.to_string())?;
+
+    let mut samples: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(e)) => {
+                log::debug!("Skipping undecodable packet in {}: {}", path.display(), e);
+                continue;
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let rate = decoded.spec().rate;
+        let channels = decoded.spec().channels.count().max(1);
+        let buffer = samples
+            .get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+        buffer.copy_interleaved_ref(decoded);
+
+        for frame in buffer.samples().chunks(channels) {
+            on_frame(frame, rate);
+        }
+    }
+    Ok(())
+}
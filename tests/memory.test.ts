@@ -0,0 +1,16 @@
+import { describe, expect, it } from 'vitest'
+import { estimateDecodedBytes } from '../utils/memory'
+
+describe('estimateDecodedBytes', () => {
+  it('counts stereo float samples at 44.1 kHz', () => {
+    expect(estimateDecodedBytes(1)).toBe(352_800)
+    expect(estimateDecodedBytes(180)).toBe(63_504_000)
+  })
+
+  it('counts nothing for an unknown duration', () => {
+    expect(estimateDecodedBytes(0)).toBe(0)
+    expect(estimateDecodedBytes(-1)).toBe(0)
+    expect(estimateDecodedBytes(NaN)).toBe(0)
+    expect(estimateDecodedBytes(Infinity)).toBe(0)
+  })
+})
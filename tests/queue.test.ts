@@ -0,0 +1,229 @@
+import { describe, expect, it } from 'vitest'
+import { createQueue, type QueueStore } from '../utils/queue'
+import type { Song } from '../types/types'
+
+function song(id: string): Song {
+  return {
+    id,
+    title: `Title ${id}`,
+    artist: 'Artist',
+    album: 'Album',
+    cover: '',
+    date_added: new Date(0),
+    duration: 180
+  }
+}
+
+// Stands in for the settings database: it keeps ids like set_queue does and
+// resolves them against the library like get_queue, skipping unknown ones.
+function memoryStore(library: Song[]) {
+  const byId = new Map(library.map(s => [s.id, s]))
+  let ids: string[] = []
+  const store: QueueStore & { ids(): string[] } = {
+    async getQueue() {
+      return ids.flatMap(id => byId.get(id) ?? [])
+    },
+    async setQueue(queue) {
+      ids = queue.map(s => typeof s === 'string' ? s : s.id)
+    },
+    ids: () => ids
+  }
+  return store
+}
+
+function setup(count: number, maxPlayed?: number) {
+  const songs = Array.from({ length: count }, (_, i) => song(String(i)))
+  const store = memoryStore(songs)
+  let writes = 0
+  const queue = createQueue(store, async () => { writes++ }, maxPlayed)
+  return { songs, store, queue, writes: () => writes }
+}
+
+const ids = (songs: Song[]) => songs.map(s => s.id)
+
+describe('read and write', () => {
+  it('splits the queue around the current song', async () => {
+    const { songs, store, queue, writes } = setup(5)
+    await queue.write(songs, 2)
+
+    expect(store.ids()).toEqual(['3', '4'])
+    expect(queue.hasPlayed()).toBe(true)
+    expect(writes()).toBe(1)
+
+    const state = await queue.read(songs[2])
+    expect(ids(state.songs)).toEqual(['0', '1', '2', '3', '4'])
+    expect(state.currentIndex).toBe(2)
+  })
+
+  it('has no current index with nothing loaded', async () => {
+    const { songs, queue } = setup(3)
+    await queue.write(songs, -1)
+
+    const state = await queue.read(null)
+    expect(ids(state.songs)).toEqual(['0', '1', '2'])
+    expect(state.currentIndex).toBe(-1)
+    expect(queue.hasPlayed()).toBe(false)
+  })
+
+  it('round-trips an unchanged queue', async () => {
+    const { songs, store, queue } = setup(4)
+    await queue.write(songs, 1)
+    const { songs: read, currentIndex } = await queue.read(songs[1])
+    await queue.write(read, currentIndex)
+
+    expect(ids((await queue.read(songs[1])).songs)).toEqual(['0', '1', '2', '3'])
+    expect(store.ids()).toEqual(['2', '3'])
+  })
+})
+
+describe('played songs', () => {
+  it('keeps the ids of songs past the cap in the snapshot', async () => {
+    const { songs, queue } = setup(5, 2)
+    await queue.write(songs, 4)
+
+    expect(ids((await queue.read(songs[4])).songs)).toEqual(['2', '3', '4'])
+    expect(await queue.snapshot(songs[4])).toEqual({
+      song_ids: ['0', '1', '2', '3', '4'],
+      current_index: 4
+    })
+  })
+
+  it('restores a long queue with the oldest songs dropped', async () => {
+    const { songs, store, queue, writes } = setup(6, 2)
+    await queue.restore(songs, 4)
+
+    expect(ids((await queue.read(songs[4])).songs)).toEqual(['2', '3', '4', '5'])
+    expect(store.ids()).toEqual(['5'])
+    expect((await queue.snapshot(songs[4])).current_index).toBe(4)
+    expect(writes()).toBe(0)
+  })
+
+  it('drops the oldest song when skipping past the cap', async () => {
+    const { songs, queue } = setup(4, 2)
+    for (const s of songs.slice(0, 3)) queue.pushPlayed(s)
+
+    expect(ids((await queue.read(songs[3])).songs)).toEqual(['1', '2', '3'])
+    expect((await queue.snapshot(songs[3])).song_ids).toEqual(['0', '1', '2', '3'])
+  })
+
+  it('forgets dropped songs once the queue is emptied', async () => {
+    const { songs, queue } = setup(4, 1)
+    await queue.write(songs, 3)
+    await queue.write([], -1)
+
+    expect(await queue.snapshot(null)).toEqual({ song_ids: [], current_index: -1 })
+  })
+
+  it('steps back to the last played song', async () => {
+    const { songs, store, queue } = setup(3)
+    await queue.write(songs, 1)
+
+    expect((await queue.stepBack(songs[1]))?.id).toBe('0')
+    expect(store.ids()).toEqual(['1', '2'])
+    expect(queue.hasPlayed()).toBe(false)
+    expect(await queue.stepBack(songs[0])).toBeUndefined()
+    expect(store.ids()).toEqual(['1', '2'])
+  })
+})
+
+describe('repeat all', () => {
+  it('restarts from the first song, dropped ones included', async () => {
+    const { songs, store, queue } = setup(4, 1)
+    await queue.write(songs, 3)
+
+    expect(await queue.restart(songs[3])).toBe(true)
+    expect(store.ids()).toEqual(['0', '1', '2', '3'])
+    expect(queue.hasPlayed()).toBe(false)
+    expect(await queue.snapshot(null)).toEqual({
+      song_ids: ['0', '1', '2', '3'],
+      current_index: -1
+    })
+  })
+
+  it('has nothing to restart in an empty queue', async () => {
+    const { store, queue } = setup(0)
+    expect(await queue.restart(null)).toBe(false)
+    expect(store.ids()).toEqual([])
+  })
+})
+
+describe('shuffle', () => {
+  // A random() that always returns 0 swaps every song with the first
+  // upcoming one, which rotates them by one.
+  const first = () => 0
+
+  it('only shuffles the songs after the current one', async () => {
+    const { songs, store, queue } = setup(5)
+    await queue.write(songs, 1)
+    await queue.setShuffled(songs[1], true, first)
+
+    expect(ids((await queue.read(songs[1])).songs).slice(0, 2)).toEqual(['0', '1'])
+    expect(store.ids()).toEqual(['3', '4', '2'])
+  })
+
+  it('restores the original order around the current song', async () => {
+    const { songs, store, queue } = setup(5)
+    await queue.write(songs, 0)
+    await queue.setShuffled(songs[0], true, first)
+    // Skip to what is now the second song in the shuffled order, '3'.
+    const { songs: shuffled } = await queue.read(songs[0])
+    const current = shuffled[2]
+    await queue.write(shuffled, 2)
+
+    await queue.setShuffled(current, false)
+    const state = await queue.read(current)
+    expect(ids(state.songs)).toEqual(['0', '1', '2', '3', '4'])
+    expect(state.currentIndex).toBe(3)
+    expect(store.ids()).toEqual(['4'])
+  })
+
+  it('keeps songs added while shuffled at the end', async () => {
+    const { songs, queue } = setup(4)
+    await queue.write(songs.slice(0, 3), 0)
+    await queue.setShuffled(songs[0], true, first)
+    const { songs: shuffled, currentIndex } = await queue.read(songs[0])
+    await queue.write([...shuffled, songs[3]], currentIndex)
+
+    await queue.setShuffled(songs[0], false)
+    expect(ids((await queue.read(songs[0])).songs)).toEqual(['0', '1', '2', '3'])
+  })
+
+  it('ignores turning it on twice', async () => {
+    const { songs, store, queue, writes } = setup(4)
+    await queue.write(songs, 0)
+    await queue.setShuffled(songs[0], true, first)
+    const order = store.ids()
+    await queue.setShuffled(songs[0], true, first)
+
+    expect(store.ids()).toEqual(order)
+    expect(writes()).toBe(2)
+  })
+})
+
+describe('reorder', () => {
+  it('follows the current song when it is moved', async () => {
+    const { songs, queue } = setup(4)
+    await queue.write(songs, 1)
+    await queue.reorder(songs[1], 1, 3)
+
+    const state = await queue.read(songs[1])
+    expect(ids(state.songs)).toEqual(['0', '2', '3', '1'])
+    expect(state.currentIndex).toBe(3)
+  })
+
+  it('shifts the current index when a song moves past it', async () => {
+    const { songs, queue } = setup(4)
+    await queue.write(songs, 2)
+    await queue.reorder(songs[2], 3, 0)
+
+    const state = await queue.read(songs[2])
+    expect(ids(state.songs)).toEqual(['3', '0', '1', '2'])
+    expect(state.currentIndex).toBe(3)
+  })
+
+  it('rejects indexes outside the queue', async () => {
+    const { songs, queue } = setup(2)
+    await queue.write(songs, 0)
+    await expect(queue.reorder(songs[0], 0, 2)).rejects.toThrow('out of range')
+  })
+})
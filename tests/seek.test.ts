@@ -0,0 +1,30 @@
+import { describe, expect, it } from 'vitest'
+import { clampPosition } from '../utils/seek'
+
+describe('clampPosition', () => {
+  it('keeps positions inside the song', () => {
+    expect(clampPosition(0, 180)).toBe(0)
+    expect(clampPosition(42.5, 180)).toBe(42.5)
+    expect(clampPosition(180, 180)).toBe(180)
+  })
+
+  it('stops at the start when seeking back past it', () => {
+    expect(clampPosition(-10, 180)).toBe(0)
+    expect(clampPosition(-Infinity, 180)).toBe(0)
+  })
+
+  it('stops at the end when seeking past it', () => {
+    expect(clampPosition(190, 180)).toBe(180)
+  })
+
+  it('goes back to the start for NaN and infinity', () => {
+    expect(clampPosition(NaN, 180)).toBe(0)
+    expect(clampPosition(Infinity, 180)).toBe(0)
+  })
+
+  it('has nowhere to go in a song without a duration', () => {
+    expect(clampPosition(10, 0)).toBe(0)
+    expect(clampPosition(-10, 0)).toBe(0)
+    expect(clampPosition(10, -1)).toBe(0)
+  })
+})
@@ -1,5 +1,126 @@
+export interface AppError {
+  kind: 'file_not_found' | 'not_found' | 'invalid_input' | 'decode' | 'device_unavailable' | 'db' | 'db_locked' | 'network' | 'io' | 'cancelled';
+  message: string;
+}
+
+export interface Backend {
+  name: string;
+  url: string;
+}
+
+export interface BackendStatus extends Backend {
+  latency_ms: number | null;
+}
+
+export type PingFailure = 'invalid_url' | 'dns' | 'timeout' | 'connection';
+
+export interface PingResult {
+  url: string;
+  reachable: boolean;
+  latency_ms: number | null;
+  failure: PingFailure | null;
+}
+
+export type DownloadState = 'queued' | 'active' | 'done' | 'failed' | 'cancelled';
+
+export interface DownloadJobStatus {
+  id: string;
+  quality: string;
+  state: DownloadState;
+  error: string | null;
+}
+
 export interface EQSettings {
   values: { [key: string]: string };
+  band_enabled: boolean[];
+  preamp_db?: number;
+}
+
+export interface EqPreset {
+  name: string;
+  values: { [key: string]: string };
+  builtin: boolean;
+}
+
+export interface ArtistCount {
+  artist: string;
+  count: number;
+}
+
+export interface AlbumSummary {
+  id: string;
+  name: string;
+  artist: string;
+  song_count: number;
+  cover: string;
+}
+
+export type StatsPeriod = 'day' | 'week' | 'month';
+
+export interface SongPlays {
+  song: Song;
+  plays: number;
+}
+
+export interface ListeningStats {
+  from: Date;
+  to: Date;
+  plays: number;
+  total_seconds: number;
+  top_artists: ArtistCount[];
+  top_songs: SongPlays[];
+}
+
+export interface GenreCount {
+  genre: string;
+  count: number;
+}
+
+export interface BrokenPlaylist {
+  playlist_id: string;
+  missing_song_ids: string[];
+}
+
+export interface FileMetadata {
+  title: string;
+  artist: string | null;
+  album: string | null;
+  genre: string | null;
+  track_number: number | null;
+  duration: number;
+  cover: string | null;
+}
+
+export interface LibraryRebuildReport {
+  recovered: number;
+  existing: number;
+  failed: string[];
+}
+
+export interface M3uExportReport {
+  written: number;
+  skipped: string[];
+}
+
+export interface M3uImportReport {
+  playlist_id: string;
+  added: number;
+  skipped: string[];
+}
+
+export interface ScanSummary {
+  added: number;
+  skipped: number;
+  failed: string[];
+  cancelled: boolean;
+}
+
+export interface ScanProgress {
+  processed: number;
+  total: number;
+  added: number;
+  skipped: number;
+  failed: number;
 }
 
 export interface History {
@@ -22,17 +143,56 @@ export interface Playlist {
   name: string;
   date_created: Date;
   songs: Song[];
+  cover?: string | null;
 }
 
 export interface Settings {
+  active_backend: string;
   api_url: string;
+  auto_select_api: boolean;
+  backends: Backend[];
+  balance: number;
+  close_to_tray: boolean;
+  crossfade_ms: number;
+  crossfade_on_skip: boolean;
   current_song: Song | null;
+  discord_rpc_enabled: boolean;
+  download_bandwidth_limit_kbps: number;
   eq: EQSettings;
+  fade_duration_ms: number;
+  gapless: boolean;
+  keep_streamed: boolean;
+  lastfm_enabled: boolean;
+  lastfm_session_key: string;
+  lastfm_username: string;
+  level_meter: boolean;
+  log_level: LogLevel;
+  log_max_size_mb: number;
+  log_retention: number;
   lossless: boolean;
   loop: boolean;
+  lyrics_api_url: string;
+  max_audio_memory_mb: number;
+  max_concurrent_downloads: number;
+  media_keys: MediaKeyBindings;
+  min_silence_ms: number;
+  mono: boolean;
   muted: boolean;
-  queue: Song[];
+  normalization: NormalizationMode;
+  output_device: string;
+  output_device_id: string;
+  playback_speed: number;
+  queue: string[];
+  queue_pins: string[];
+  queue_snapshot: QueueSnapshot;
+  repeat_mode: RepeatMode;
+  resume_on_launch: boolean;
+  romanize_lyrics: boolean;
   shuffle: boolean;
+  silence_threshold_db: number;
+  skip_silence: boolean;
+  smart_resume: boolean;
+  smart_resume_ramp_ms: number;
   streaming: boolean;
   volume: number;
 }
@@ -45,4 +205,127 @@ export interface Song {
   cover: string;
   date_added: Date;
   duration: number;
-}
\ No newline at end of file
+  genre?: string | null;
+  bpm?: number | null;
+  play_count?: number;
+  last_played?: Date | null;
+  liked?: boolean;
+  path?: string | null;
+  pinned?: boolean;
+}
+export interface SongFilter {
+  query?: string;
+  artist?: string;
+  album?: string;
+}
+
+export type SongSort = 'title' | 'artist' | 'album' | 'date_added' | 'play_count';
+
+export interface AbLoop {
+  start_ms: number;
+  end_ms: number;
+}
+
+export interface SilentRange {
+  start_ms: number;
+  end_ms: number;
+}
+
+export interface LyricLine {
+  time_ms: number | null;
+  text: string;
+  romanized: string | null;
+}
+
+export interface Lyrics {
+  synced: boolean;
+  lines: LyricLine[];
+}
+
+export interface DatabaseTransfer {
+  path: string;
+  schema_version: number;
+  downgrade: boolean;
+}
+
+export interface PlayerState {
+  volume: number;
+  muted: boolean;
+  looping: boolean;
+  repeat_mode: RepeatMode;
+  shuffle: boolean;
+  current_song: Song | null;
+  progress: number;
+  duration: number;
+  is_playing: boolean;
+}
+
+export interface QueueSnapshot {
+  song_ids: string[];
+  current_index: number;
+  position: number;
+}
+
+export interface RestoredQueue {
+  songs: Song[];
+  current_index: number;
+  position: number;
+}
+
+export interface LyricLineEvent {
+  id: string | undefined;
+  index: number;
+  timeMs: number | null;
+  text: string;
+  romanized: string | null;
+}
+
+export interface SongPage {
+  songs: Song[];
+  total: number;
+}
+
+export type LogLevel = 'off' | 'error' | 'warn' | 'info' | 'debug' | 'trace';
+
+export type NormalizationMode = 'off' | 'track' | 'album';
+
+// Accelerators for the global shortcut plugin; null leaves the action unbound.
+export interface MediaKeyBindings {
+  play_pause: string | null;
+  next: string | null;
+  previous: string | null;
+  stop: string | null;
+}
+
+export type RepeatMode = 'off' | 'one' | 'all';
+
+export interface ReplayGain {
+  track_gain_db: number | null;
+  album_gain_db: number | null;
+}
+
+export interface ChannelLevels {
+  peakDb: number;
+  rmsDb: number;
+}
+
+export interface OutputLevels extends ChannelLevels {
+  left: ChannelLevels;
+  right: ChannelLevels;
+}
+
+export interface OutputDevice {
+  id: string;
+  name: string;
+}
+
+export interface QueueState {
+  songs: Song[];
+  currentIndex: number;
+}
+
+export interface PlaybackProgress {
+  positionMs: number;
+  durationMs: number;
+  isPlaying: boolean;
+}
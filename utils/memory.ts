@@ -0,0 +1,7 @@
+// Web Audio decodes the whole file into 32-bit float samples. The sample
+// rate isn't known before decoding, so this assumes 44.1 kHz stereo. Songs
+// with an unknown duration count as nothing.
+export function estimateDecodedBytes(durationSecs: number): number {
+  if (!Number.isFinite(durationSecs) || durationSecs <= 0) return 0
+  return durationSecs * 44100 * 2 * 4
+}
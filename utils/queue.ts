@@ -0,0 +1,150 @@
+import type { QueueState, Song } from '~/types/types'
+
+export const MAX_PLAYED_SONGS = 100
+
+// Where the upcoming songs are kept. The player stores them in settings.
+export interface QueueStore {
+  getQueue(): Promise<Song[]>
+  setQueue(queue: (Song | string)[]): Promise<unknown>
+}
+
+export type Queue = ReturnType<typeof createQueue>
+
+// The queue without the playback: the songs already played, the current one
+// and the upcoming songs in the store. onWrite runs after every change made
+// through write, the player saves the queue snapshot there.
+export function createQueue(store: QueueStore, onWrite: () => Promise<void>, maxPlayed = MAX_PLAYED_SONGS) {
+  const played: Song[] = []
+  // Songs that fell off the front of played. Only their ids are kept, so
+  // repeat-all can still start over from the first song of a long queue.
+  const droppedIds: string[] = []
+  let unshuffled: Song[] | null = null
+
+  function setPlayed(songs: Song[]) {
+    played.splice(0, played.length, ...songs)
+    if (played.length > maxPlayed) {
+      droppedIds.push(...played.splice(0, played.length - maxPlayed).map(song => song.id))
+    }
+  }
+
+  const queue = {
+    // currentIndex is -1 when nothing is loaded.
+    async read(current: Song | null): Promise<QueueState> {
+      const upcoming = await store.getQueue()
+      if (!current) {
+        return { songs: [...played, ...upcoming], currentIndex: -1 }
+      }
+      return { songs: [...played, current, ...upcoming], currentIndex: played.length }
+    },
+
+    // Takes the whole queue back, usually after changing what read returned.
+    // Emptying it forgets the dropped songs too.
+    async write(songs: Song[], currentIndex: number) {
+      if (songs.length === 0) droppedIds.length = 0
+      setPlayed(songs.slice(0, Math.max(currentIndex, 0)))
+      await store.setQueue(songs.slice(currentIndex + 1))
+      await onWrite()
+    },
+
+    // Like write, for a queue restored on launch: songs before currentIndex
+    // count as played, dropped ones included.
+    async restore(songs: Song[], currentIndex: number) {
+      droppedIds.length = 0
+      setPlayed(songs.slice(0, Math.max(currentIndex, 0)))
+      await store.setQueue(songs.slice(currentIndex + 1))
+    },
+
+    // The song ids for the queue snapshot, dropped songs included.
+    async snapshot(current: Song | null): Promise<{ song_ids: string[], current_index: number }> {
+      const { songs, currentIndex } = await queue.read(current)
+      return {
+        song_ids: [...droppedIds, ...songs.map(song => song.id)],
+        current_index: currentIndex < 0 ? -1 : currentIndex + droppedIds.length
+      }
+    },
+
+    hasPlayed(): boolean {
+      return played.length > 0
+    },
+
+    // Called when skipping past a song.
+    pushPlayed(song: Song) {
+      setPlayed([...played, song])
+    },
+
+    // Takes the last played song back out, putting the current one back at
+    // the front of the upcoming songs. Returns undefined when nothing was
+    // played yet.
+    async stepBack(current: Song | null): Promise<Song | undefined> {
+      const previous = played.pop()
+      if (previous && current) {
+        await store.setQueue([current, ...await store.getQueue()])
+      }
+      return previous
+    },
+
+    // Puts every song played so far, plus the current one, back into the
+    // upcoming songs for repeat-all. Returns false if there were none.
+    async restart(current: Song | null): Promise<boolean> {
+      const songs = [...droppedIds, ...played, ...(current ? [current] : [])]
+      if (songs.length === 0) return false
+
+      droppedIds.length = 0
+      played.length = 0
+      await store.setQueue(songs)
+      return true
+    },
+
+    // Shuffles the songs after the current one. Turning it off restores the
+    // order from before shuffling and carries on from the current song's
+    // place in it; songs added while shuffled are kept at the end.
+    async setShuffled(current: Song | null, enabled: boolean, random = Math.random) {
+      const { songs, currentIndex } = await queue.read(current)
+
+      if (enabled && !unshuffled) {
+        unshuffled = [...songs]
+        const upcoming = songs.slice(currentIndex + 1)
+        for (let i = upcoming.length - 1; i > 0; i--) {
+          const j = Math.floor(random() * (i + 1))
+          ;[upcoming[i], upcoming[j]] = [upcoming[j], upcoming[i]]
+        }
+        await queue.write([...songs.slice(0, currentIndex + 1), ...upcoming], currentIndex)
+      } else if (!enabled && unshuffled) {
+        const ids = new Set(songs.map(song => song.id))
+        const originalIds = new Set(unshuffled.map(song => song.id))
+        const restored = [
+          ...unshuffled.filter(song => ids.has(song.id)),
+          ...songs.filter(song => !originalIds.has(song.id))
+        ]
+        const restoredIndex = current ? restored.findIndex(song => song.id === current.id) : -1
+        await queue.write(restored, restoredIndex)
+        unshuffled = null
+      }
+    },
+
+    // The current index follows the playing song rather than the slot, so it
+    // keeps playing whether it is moved itself or something moves past it.
+    async reorder(current: Song | null, from: number, to: number) {
+      const { songs, currentIndex } = await queue.read(current)
+      if (from < 0 || from >= songs.length || to < 0 || to >= songs.length) {
+        throw new Error(`Queue index out of range: ${from} -> ${to}`)
+      }
+
+      const [moved] = songs.splice(from, 1)
+      songs.splice(to, 0, moved)
+
+      let newIndex = currentIndex
+      if (currentIndex >= 0) {
+        if (from === currentIndex) {
+          newIndex = to
+        } else if (from < currentIndex && to >= currentIndex) {
+          newIndex--
+        } else if (from > currentIndex && to <= currentIndex) {
+          newIndex++
+        }
+      }
+      await queue.write(songs, newIndex)
+    }
+  }
+  return queue
+}
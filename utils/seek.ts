@@ -0,0 +1,6 @@
+// Keeps a seek target inside the song; anything that isn't a number goes
+// back to the start.
+export function clampPosition(seconds: number, duration: number): number {
+  if (!Number.isFinite(seconds)) return 0
+  return Math.min(Math.max(seconds, 0), Math.max(duration, 0))
+}